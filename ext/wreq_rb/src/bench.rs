@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use magnus::{RHash, Ruby};
+
+/// One timed request: its status, or None when it failed before a
+/// response (connection, TLS, timeout) or while reading the body.
+struct Sample {
+    latency: Duration,
+    status: Option<u16>,
+}
+
+/// Results of `Wreq.benchmark`.
+pub struct Summary {
+    elapsed: Duration,
+    /// Latencies of the requests that got a response, sorted.
+    latencies: Vec<Duration>,
+    errors: usize,
+    statuses: BTreeMap<u16, usize>,
+}
+
+/// Run `requests` with at most `concurrency` in flight, timing each one.
+/// Requests are only taken from the iterator as slots free up. A request
+/// resolves to its status once its body has been read in full, or None if
+/// it failed. Must run on the runtime.
+pub async fn run<I, F>(requests: I, concurrency: usize) -> Summary
+where
+    I: IntoIterator<Item = F>,
+    F: Future<Output = Option<u16>>,
+{
+    let started = Instant::now();
+    let samples: Vec<Sample> = futures_util::stream::iter(requests)
+        .map(|request| async move {
+            let sent = Instant::now();
+            let status = request.await;
+            Sample { latency: sent.elapsed(), status }
+        })
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+    summarize(samples, started.elapsed())
+}
+
+fn summarize(samples: Vec<Sample>, elapsed: Duration) -> Summary {
+    let mut latencies = Vec::with_capacity(samples.len());
+    let mut errors = 0;
+    let mut statuses = BTreeMap::new();
+    for sample in samples {
+        match sample.status {
+            Some(status) => {
+                latencies.push(sample.latency);
+                *statuses.entry(status).or_insert(0) += 1;
+            }
+            None => errors += 1,
+        }
+    }
+    latencies.sort_unstable();
+    Summary { elapsed, latencies, errors, statuses }
+}
+
+impl Summary {
+    /// Nearest-rank percentile of the successful latencies.
+    fn percentile(&self, p: f64) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let rank = (p * self.latencies.len() as f64).ceil() as usize;
+        Some(self.latencies[rank.clamp(1, self.latencies.len()) - 1])
+    }
+
+    fn mean(&self) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        Some(self.latencies.iter().sum::<Duration>() / self.latencies.len() as u32)
+    }
+
+    /// The summary as a Hash of Symbols to numbers; latencies are Float
+    /// seconds, or nil when no request got a response.
+    pub fn to_hash(&self, ruby: &Ruby) -> Result<RHash, magnus::Error> {
+        let secs = |d: Option<Duration>| d.map(|d| d.as_secs_f64());
+        let total = self.latencies.len() + self.errors;
+        let hash = ruby.hash_new();
+        hash.aset(ruby.to_symbol("requests"), total)?;
+        hash.aset(ruby.to_symbol("errors"), self.errors)?;
+        hash.aset(ruby.to_symbol("elapsed"), self.elapsed.as_secs_f64())?;
+        let rate = if self.elapsed.is_zero() { 0.0 } else { total as f64 / self.elapsed.as_secs_f64() };
+        hash.aset(ruby.to_symbol("requests_per_sec"), rate)?;
+        hash.aset(ruby.to_symbol("min"), secs(self.latencies.first().copied()))?;
+        hash.aset(ruby.to_symbol("mean"), secs(self.mean()))?;
+        hash.aset(ruby.to_symbol("p50"), secs(self.percentile(0.50)))?;
+        hash.aset(ruby.to_symbol("p95"), secs(self.percentile(0.95)))?;
+        hash.aset(ruby.to_symbol("max"), secs(self.latencies.last().copied()))?;
+        let statuses = ruby.hash_new();
+        for (status, n) in &self.statuses {
+            statuses.aset(*status, *n)?;
+        }
+        hash.aset(ruby.to_symbol("statuses"), statuses)?;
+        Ok(hash)
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::{with_kind, wreq_error};
+use crate::pool::origin;
+
+/// `circuit_breaker:` defaults.
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Per-origin circuit breaker. After `failure_threshold` consecutive
+/// failures a host's circuit opens and its requests fail fast until
+/// `cooldown` has passed; then one probe request is let through, which
+/// closes the circuit on success or reopens it for another cooldown.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    hosts: Mutex<HashMap<String, HostState>>,
+}
+
+#[derive(Default)]
+struct HostState {
+    /// Consecutive failures since the last success.
+    failures: u32,
+    /// When the circuit last opened; None while closed.
+    opened: Option<Instant>,
+    /// Whether the half-open probe is in flight.
+    probing: bool,
+}
+
+/// Leave for one request to a host, reporting how it went with `record`.
+/// Dropped unrecorded (the request failed for reasons that say nothing
+/// about the host), a probe frees the half-open slot for the next request.
+pub struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    host: String,
+    probe: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker { failure_threshold, cooldown, hosts: Mutex::new(HashMap::new()) }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, HostState>> {
+        self.hosts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Let a request to `uri`'s origin through, or raise `Wreq::Error`
+    /// (`error_kind` `:circuit_open`) while its circuit is open.
+    pub fn admit(&self, uri: &http::Uri) -> Result<Permit<'_>, magnus::Error> {
+        let host = origin(uri);
+        let mut hosts = self.lock();
+        // Hosts without failures have no entry, so the map only holds
+        // origins that are currently failing.
+        let Some(state) = hosts.get_mut(&host) else {
+            return Ok(Permit { breaker: self, host, probe: false });
+        };
+        let Some(opened) = state.opened else {
+            return Ok(Permit { breaker: self, host, probe: false });
+        };
+        let elapsed = opened.elapsed();
+        if elapsed < self.cooldown || state.probing {
+            let wait = self.cooldown.saturating_sub(elapsed);
+            let msg = format!(
+                "circuit open for {} after {} consecutive failures (retry in {:.1}s)",
+                host,
+                state.failures,
+                wait.as_secs_f64()
+            );
+            return Err(with_kind(wreq_error(), msg, "circuit_open"));
+        }
+        state.probing = true;
+        Ok(Permit { breaker: self, host, probe: true })
+    }
+}
+
+impl Permit<'_> {
+    /// Count the request as a success or a failure of its host.
+    pub fn record(mut self, success: bool) {
+        let breaker = self.breaker;
+        let mut hosts = breaker.lock();
+        let host = std::mem::take(&mut self.host);
+        if success {
+            hosts.remove(&host);
+            return;
+        }
+        let state = hosts.entry(host).or_default();
+        state.failures = state.failures.saturating_add(1);
+        if self.probe {
+            state.probing = false;
+        }
+        if self.probe || state.failures >= breaker.failure_threshold {
+            state.opened = Some(Instant::now());
+        }
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.probe && !self.host.is_empty() {
+            if let Some(state) = self.breaker.lock().get_mut(&self.host) {
+                state.probing = false;
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use http::HeaderMap;
+use magnus::{RHash, Ruby};
+
+use crate::response::Response;
+
+/// `cache:` defaults.
+pub const DEFAULT_MAX_ENTRIES: usize = 100;
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+/// In-process cache of GET responses keyed by full URL, evicting the least
+/// recently used entry when full. Entries expire `ttl` after being stored.
+/// An entry only answers requests that agree with the one that stored it
+/// on every header the response's `Vary` names.
+pub struct ResponseCache {
+    max_entries: usize,
+    ttl: Duration,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<String, Entry>,
+    /// Bumped on every store and hit; the entry with the lowest `used`
+    /// is the least recently used.
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+struct Entry {
+    response: Response,
+    /// The storing request's value of each header named by `Vary`.
+    vary: Vec<(String, Option<String>)>,
+    stored: Instant,
+    used: u64,
+}
+
+impl Entry {
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        self.vary.iter().all(|(name, value)| header_str(headers, name) == *value)
+    }
+}
+
+/// Every value of `name` in `headers`, joined with ", ".
+fn header_str(headers: &HeaderMap, name: &str) -> Option<String> {
+    let values: Vec<&str> = headers.get_all(name).iter().filter_map(|v| v.to_str().ok()).collect();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+/// Whether a request with these headers may be answered from, or stored
+/// in, the cache: credentials and ranges make the response the caller's own.
+pub fn admits(headers: &HeaderMap) -> bool {
+    ![http::header::AUTHORIZATION, http::header::COOKIE, http::header::RANGE]
+        .iter()
+        .any(|name| headers.contains_key(name))
+}
+
+impl ResponseCache {
+    pub fn new(max_entries: usize, ttl: Duration) -> Self {
+        ResponseCache { max_entries, ttl, inner: Mutex::new(Inner::default()) }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A copy of the fresh response cached for `url` that a request with
+    /// `headers` may be served, counting a hit or miss.
+    pub fn get(&self, url: &str, headers: &HeaderMap) -> Option<Response> {
+        let mut inner = self.lock();
+        inner.clock += 1;
+        let now = inner.clock;
+        let fresh = match inner.entries.get_mut(url) {
+            Some(entry) if entry.stored.elapsed() < self.ttl => {
+                if entry.matches(headers) {
+                    entry.used = now;
+                    entry.response.snapshot()
+                } else {
+                    None
+                }
+            }
+            Some(_) => {
+                inner.entries.remove(url);
+                None
+            }
+            None => None,
+        };
+        match fresh {
+            Some(_) => inner.hits += 1,
+            None => inner.misses += 1,
+        }
+        fresh
+    }
+
+    /// Store a copy of `response` to the request for `url` with `headers`,
+    /// unless it is a streamed, unsuccessful, partial, `Vary: *`, or
+    /// `Cache-Control: no-store` response.
+    pub fn store(&self, url: String, headers: &HeaderMap, response: &Response) {
+        if response.status() != 200 || response.is_no_store() {
+            return;
+        }
+        let names: Vec<String> = response
+            .header_values("vary")
+            .flat_map(|v| v.split(','))
+            .map(|name| name.trim().to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect();
+        if names.iter().any(|name| name == "*") {
+            return;
+        }
+        let vary = names
+            .into_iter()
+            .map(|name| {
+                let value = header_str(headers, &name);
+                (name, value)
+            })
+            .collect();
+        let Some(copy) = response.snapshot().filter(|r| !r.is_partial()) else {
+            return;
+        };
+        let mut inner = self.lock();
+        inner.clock += 1;
+        let used = inner.clock;
+        if !inner.entries.contains_key(&url) && inner.entries.len() >= self.max_entries {
+            let ttl = self.ttl;
+            inner.entries.retain(|_, e| e.stored.elapsed() < ttl);
+            if inner.entries.len() >= self.max_entries {
+                let oldest = inner.entries.iter().min_by_key(|(_, e)| e.used).map(|(k, _)| k.clone());
+                if let Some(oldest) = oldest {
+                    inner.entries.remove(&oldest);
+                }
+            }
+        }
+        inner.entries.insert(url, Entry { response: copy, vary, stored: Instant::now(), used });
+    }
+
+    pub fn clear(&self) {
+        self.lock().entries.clear();
+    }
+
+    /// `{ hits:, misses:, entries:, max_entries:, ttl: }`
+    pub fn stats(&self, ruby: &Ruby) -> Result<RHash, magnus::Error> {
+        let inner = self.lock();
+        let hash = ruby.hash_new();
+        hash.aset(ruby.to_symbol("hits"), inner.hits)?;
+        hash.aset(ruby.to_symbol("misses"), inner.misses)?;
+        hash.aset(ruby.to_symbol("entries"), inner.entries.len())?;
+        hash.aset(ruby.to_symbol("max_entries"), self.max_entries)?;
+        hash.aset(ruby.to_symbol("ttl"), self.ttl.as_secs_f64())?;
+        Ok(hash)
+    }
+}
@@ -2,35 +2,77 @@ use std::ffi::c_void;
 use std::panic::{self, AssertUnwindSafe};
 use std::ptr;
 use std::any::Any;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
 use magnus::{
-    function, method, prelude::*, Module, RArray, RHash, Ruby,
-    try_convert::TryConvert, Value,
+    encoding::EncodingCapable, function, method, prelude::*, typed_data::Obj, DataTypeFunctions,
+    Module, RArray, RHash, RString, Ruby, Symbol, try_convert::TryConvert, Value,
 };
+use bytes::Bytes;
+use tokio::io::AsyncWriteExt;
 use tokio::runtime::Runtime;
+use tokio_util::io::ReaderStream;
 use tokio_util::sync::CancellationToken;
+use url::Url;
 use std::net::IpAddr;
+use wreq::cookie::Jar;
 use wreq::header::{HeaderMap, HeaderName, HeaderValue, OrigHeaderMap};
+use wreq::multipart::{Form, Part};
 use wreq::tls::TlsVersion;
+use wreq::EmulationFactory;
 use wreq_util::{Emulation as BrowserEmulation, EmulationOS, EmulationOption};
 
-use crate::error::{generic_error, to_magnus_error};
+use crate::bench;
+use crate::config;
+use crate::cookie_jar::{self, CookieJar};
+use crate::dns::CachingResolver;
+use crate::decode::{self, Decoders};
+use crate::hooks::{self, Hooks};
+use crate::error::{
+    decode_error, generic_error, interrupted, interrupted_error, invalid_header, timeout_error, tls_error,
+    to_magnus_error, with_cause, with_kind, wreq_error, CrossOriginRedirect, TooManyRedirects,
+};
+use crate::pin::PinSet;
+use crate::breaker::{self, CircuitBreaker};
+use crate::cache::{self, ResponseCache};
+use crate::options::request_opts;
+use crate::pool::{self, PoolStats};
 use crate::response::Response;
+use crate::request::Request;
+use crate::result::RequestResult;
+use crate::stream::BodyStream;
+use crate::upload::ChunkProducer;
 
 // --------------------------------------------------------------------------
 // Shared Tokio runtime
 // --------------------------------------------------------------------------
 
-fn runtime() -> &'static Runtime {
+/// The runtime every request runs on, built on first use. A failure to
+/// build it (e.g. the process may not spawn threads) is remembered and
+/// raised as `Wreq::Error` from each request instead of aborting.
+pub(crate) fn runtime() -> Result<&'static Runtime, magnus::Error> {
     use std::sync::OnceLock;
-    static RT: OnceLock<Runtime> = OnceLock::new();
-    RT.get_or_init(|| {
-        tokio::runtime::Builder::new_multi_thread()
-            .enable_all()
-            .build()
-            .expect("failed to build tokio runtime")
-    })
+    static RT: OnceLock<Result<Runtime, String>> = OnceLock::new();
+    RT.get_or_init(build_runtime)
+        .as_ref()
+        .map_err(|e| generic_error(format!("failed to start the async runtime: {}", e)))
+}
+
+/// `WREQ_RUNTIME_THREADS` sets the number of worker threads (default: one
+/// per CPU core).
+fn build_runtime() -> Result<Runtime, String> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    if let Ok(value) = std::env::var("WREQ_RUNTIME_THREADS") {
+        match value.trim().parse::<usize>() {
+            Ok(n) if n > 0 => {
+                builder.worker_threads(n);
+            }
+            _ => return Err(format!("invalid WREQ_RUNTIME_THREADS '{}' (expected a positive integer)", value)),
+        }
+    }
+    builder.enable_all().build().map_err(|e| e.to_string())
 }
 
 // --------------------------------------------------------------------------
@@ -44,7 +86,7 @@ fn runtime() -> &'static Runtime {
 /// # Safety
 /// The closure must NOT access any Ruby objects or call any Ruby C API.
 /// Extract all data from Ruby before calling this, convert results after.
-unsafe fn without_gvl<F, R>(f: F) -> R
+pub(crate) unsafe fn without_gvl<F, R>(f: F) -> R
 where
     F: FnOnce(CancellationToken) -> R,
 {
@@ -110,18 +152,193 @@ struct ResponseData {
     version: String,
     content_length: Option<u64>,
     transfer_size: Option<u64>,
+    /// The body was cut short at the `max_body_size:` limit.
+    truncated: bool,
+    /// The connection ended before `Content-Length` bytes arrived
+    /// (kept only with `allow_incomplete: true`).
+    incomplete: bool,
+    /// The response itself, with its body unread, for a `Stream` sink.
+    stream: Option<wreq::Response>,
+    /// Bytes written to disk by a `File` sink.
+    written: Option<u64>,
 }
 
 /// Outcome of the network call performed outside the GVL.
 enum RequestOutcome {
     Ok(ResponseData),
     Err(wreq::Error),
+    Io(std::io::Error),
+    DecodeFailed(String),
+    /// The body exceeded `max_body_size:` (the limit in bytes).
+    TooLarge(usize),
+    /// The headers exceeded `max_header_size:`: (their size, the limit).
+    HeadersTooLarge(usize, usize),
+    /// The body ended early: (bytes received, declared `Content-Length`).
+    Incomplete(u64, u64),
+    /// The server's key matched none of the `pin_sha256:` pins.
+    PinMismatch(String),
     Interrupted,
+    /// `total_timeout:` ran out first.
+    DeadlineExceeded(Deadline),
+}
+
+/// Failure while fetching: either from wreq or from writing the body out.
+enum FetchError {
+    Http(wreq::Error),
+    Io(std::io::Error),
+    TooLarge(usize),
+    HeadersTooLarge(usize, usize),
+    Incomplete(u64, u64),
+    PinMismatch(String),
+}
+
+impl From<wreq::Error> for FetchError {
+    fn from(e: wreq::Error) -> Self {
+        FetchError::Http(e)
+    }
+}
+
+impl From<std::io::Error> for FetchError {
+    fn from(e: std::io::Error) -> Self {
+        FetchError::Io(e)
+    }
+}
+
+/// How an `each_event` connection ended without an error.
+enum SseEnd {
+    /// The stream closed; reconnect.
+    Eof,
+    /// The block returned `:stop` or the server sent 204; do not reconnect.
+    Stopped,
+}
+
+/// Where the response body goes once headers have arrived.
+enum BodySink {
+    /// Collect the whole body in memory.
+    Buffer,
+    /// Stream the body into a file through a write buffer of the given size.
+    /// With `resume: Some(offset)`, a 206 whose `Content-Range` starts at
+    /// `offset` is appended to the existing file, a 200 replaces it, and any
+    /// other response leaves it untouched.
+    File { path: std::path::PathBuf, buffer_size: usize, resume: Option<u64> },
+    /// Leave the body unread for the caller to pull (`stream: true`).
+    Stream,
+}
+
+/// Cap on how much of a buffered response body is read.
+#[derive(Clone, Copy)]
+struct BodyLimit {
+    max: usize,
+    /// Keep the first `max` bytes instead of failing.
+    truncate: bool,
+}
+
+/// A request ready to send, plus the Ruby producer feeding its body when
+/// `body:` is an Enumerator.
+struct Prepared {
+    req: wreq::RequestBuilder,
+    producer: Option<ChunkProducer>,
+    body_limit: Option<BodyLimit>,
+    /// The `meta:` option, handed to hooks only.
+    meta: Option<Value>,
+    /// `stream: true`: return before reading the body.
+    stream: bool,
+    /// Decoders for bodies decoded after download (`send_accept_encoding:
+    /// false`), with per-request flags applied.
+    manual_decoders: Option<Decoders>,
+    /// When `total_timeout:` runs out.
+    deadline: Option<Deadline>,
+    /// `allow_incomplete: true`: keep a body cut short of its Content-Length.
+    allow_incomplete: bool,
+    /// `extensions:`, inserted into the built request's extensions.
+    extensions: Option<RequestExtensions>,
+}
+
+/// The `extensions:` request option as stored in the wreq request's
+/// extensions, where `before_request` and custom transport layers read it.
+/// Keys and values are converted with `to_s`: Ruby objects cannot travel
+/// with the request.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestExtensions(pub std::collections::BTreeMap<String, String>);
+
+/// A `total_timeout:` budget and the instant it expires.
+#[derive(Clone, Copy)]
+struct Deadline {
+    at: tokio::time::Instant,
+    budget: Duration,
+}
+
+impl Deadline {
+    /// Resolves when the deadline passes; never, without one.
+    async fn expired(deadline: Option<Deadline>) -> Deadline {
+        match deadline {
+            Some(d) => {
+                tokio::time::sleep_until(d.at).await;
+                d
+            }
+            None => std::future::pending().await,
+        }
+    }
+
+    fn error(&self) -> magnus::Error {
+        let msg = format!("request exceeded total_timeout of {}s", self.budget.as_secs_f64());
+        with_kind(timeout_error(), msg, "timeout")
+    }
+}
+
+/// Undo compression listed in `Transfer-Encoding`, which wreq leaves to
+/// us, then the `Content-Encoding` when the client decodes bodies itself
+/// (`decoders`). Only whole buffered bodies are decoded; truncated,
+/// incomplete, streamed, and downloaded ones are returned as received.
+fn decode_response(mut data: ResponseData, decoders: Option<Decoders>) -> RequestOutcome {
+    if data.truncated || data.incomplete || data.stream.is_some() || data.written.is_some() {
+        return RequestOutcome::Ok(data);
+    }
+    let header = |name: &str| {
+        let values: Vec<&str> = data.headers.iter().filter(|(k, _)| k == name).map(|(_, v)| v.as_str()).collect();
+        (!values.is_empty()).then(|| values.join(","))
+    };
+    let transfer_encoding = header("transfer-encoding");
+    let content_encoding = header("content-encoding");
+    let mut body = std::mem::take(&mut data.body);
+    // Transfer codings were applied over any content coding, so go first.
+    if let Some(te) = transfer_encoding {
+        body = match decode::decode_transfer(&te, body) {
+            Ok(body) => body,
+            Err(msg) => return RequestOutcome::DecodeFailed(msg),
+        };
+    }
+    if let (Some(decoders), Some(ce)) = (decoders, content_encoding.as_deref()) {
+        if decode::decodable(ce, decoders) {
+            body = match decode::decode_content(Some(ce), body, decoders) {
+                Ok(body) => body,
+                Err(msg) => return RequestOutcome::DecodeFailed(msg),
+            };
+            // As wreq does for bodies it decodes: neither the coding nor the
+            // length it was sent with describes `body` any more.
+            data.headers.retain(|(k, _)| k != "content-encoding" && k != "content-length");
+        } else if decode::identity(ce) {
+            data.headers.retain(|(k, _)| k != "content-encoding");
+        }
+        // Otherwise the header stays: the body is still encoded (e.g. `dcb`,
+        // or a disabled coding).
+    }
+    data.body = body;
+    RequestOutcome::Ok(data)
 }
 
 /// Execute a request and collect the full response as pure Rust types.
-async fn execute_request(req: wreq::RequestBuilder) -> Result<ResponseData, wreq::Error> {
-    let resp = req.send().await?;
+/// With a `File` sink the body is written to disk and `body` stays empty;
+/// `limit` only applies to buffered bodies. A body shorter than its
+/// `Content-Length` fails unless `allow_incomplete` is set.
+async fn execute_request(
+    pending: impl std::future::Future<Output = Result<wreq::Response, FetchError>>,
+    sink: BodySink,
+    limit: Option<BodyLimit>,
+    allow_incomplete: bool,
+    max_header_size: usize,
+) -> Result<ResponseData, FetchError> {
+    let mut resp = pending.await?;
     let status = resp.status().as_u16();
     let url = resp.uri().to_string();
     let version = format!("{:?}", resp.version());
@@ -129,21 +346,244 @@ async fn execute_request(req: wreq::RequestBuilder) -> Result<ResponseData, wreq
     let headers: Vec<(String, String)> = resp
         .headers()
         .iter()
-        .map(|(k, v)| (k.as_str().to_owned(), v.to_str().unwrap_or("").to_owned()))
+        .map(|(k, v)| {
+            // Content-Disposition commonly carries a raw UTF-8 filename, which
+            // `to_str` rejects; `Response#filename` needs it kept.
+            let value = if k == http::header::CONTENT_DISPOSITION {
+                String::from_utf8_lossy(v.as_bytes()).into_owned()
+            } else {
+                v.to_str().unwrap_or("").to_owned()
+            };
+            (k.as_str().to_owned(), value)
+        })
         .collect();
+    let header_size = resp.headers().iter().map(|(k, v)| header_line_size(k.as_str(), v.as_bytes())).sum();
+    if header_size > max_header_size {
+        return Err(FetchError::HeadersTooLarge(header_size, max_header_size));
+    }
     let transfer_size_handle = resp.transfer_size_handle().cloned();
-    let body = resp.bytes().await?.to_vec();
+    let mut truncated = false;
+    let mut incomplete = false;
+    let mut stream = None;
+    let mut written = None;
+    let mut body_reader = BodyReader { expected: content_length, received: 0, allow_incomplete };
+    // An error body never replaces a downloaded file; it is buffered so the
+    // response still carries it.
+    let sink = match sink {
+        BodySink::File { resume: None, .. } if status >= 400 => BodySink::Buffer,
+        sink => sink,
+    };
+    let body = match (sink, limit) {
+        (BodySink::Stream, _) => {
+            stream = Some(resp);
+            Vec::new()
+        }
+        (BodySink::Buffer, limit) => {
+            if let Some(limit) = limit {
+                if !limit.truncate && content_length.is_some_and(|len| len > limit.max as u64) {
+                    return Err(FetchError::TooLarge(limit.max));
+                }
+            }
+            let mut buf = Vec::new();
+            while let Some(chunk) = body_reader.next(&mut resp, &mut incomplete).await? {
+                if let Some(limit) = limit {
+                    let room = limit.max - buf.len();
+                    if chunk.len() > room {
+                        if !limit.truncate {
+                            return Err(FetchError::TooLarge(limit.max));
+                        }
+                        // Stop reading; the rest of the body is never downloaded.
+                        buf.extend_from_slice(&chunk[..room]);
+                        truncated = true;
+                        break;
+                    }
+                }
+                buf.extend_from_slice(&chunk);
+            }
+            buf
+        }
+        (BodySink::File { resume: Some(offset), .. }, _)
+            if status != 200
+                && (status != 206
+                    || resp
+                        .headers()
+                        .get(http::header::CONTENT_RANGE)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(content_range_start)
+                        != Some(offset)) =>
+        {
+            Vec::new()
+        }
+        (BodySink::File { path, buffer_size, resume }, _) => {
+            let file = match resume {
+                Some(offset) if offset > 0 && status == 206 => {
+                    tokio::fs::OpenOptions::new().append(true).open(&path).await?
+                }
+                _ => tokio::fs::File::create(&path).await?,
+            };
+            let mut writer = tokio::io::BufWriter::with_capacity(buffer_size, file);
+            while let Some(chunk) = body_reader.next(&mut resp, &mut incomplete).await? {
+                writer.write_all(&chunk).await?;
+            }
+            writer.flush().await?;
+            written = Some(body_reader.received);
+            Vec::new()
+        }
+    };
     let transfer_size = transfer_size_handle.map(|h| h.get());
-    Ok(ResponseData { status, headers, body, url, version, content_length, transfer_size })
+    Ok(ResponseData {
+        status,
+        headers,
+        body,
+        url,
+        version,
+        content_length,
+        transfer_size,
+        truncated,
+        incomplete,
+        stream,
+        written,
+    })
+}
+
+/// The first byte position of a `Content-Range: bytes <start>-<end>/<size>`.
+fn content_range_start(value: &str) -> Option<u64> {
+    let (unit, range) = value.trim().split_once(' ')?;
+    if !unit.eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+    range.split_once('-')?.0.trim().parse().ok()
+}
+
+/// Reads a response body while checking it against its `Content-Length`.
+struct BodyReader {
+    expected: Option<u64>,
+    received: u64,
+    allow_incomplete: bool,
+}
+
+impl BodyReader {
+    /// Next chunk, or None at the end. If the body stops short of
+    /// `Content-Length` (a clean close or a connection error), that is an
+    /// `Incomplete` error, or with `allow_incomplete` the end of the body
+    /// with `incomplete` set. Timeouts are reported as such.
+    async fn next(&mut self, resp: &mut wreq::Response, incomplete: &mut bool) -> Result<Option<Bytes>, FetchError> {
+        match resp.chunk().await {
+            Ok(Some(chunk)) => {
+                self.received += chunk.len() as u64;
+                return Ok(Some(chunk));
+            }
+            Ok(None) => {}
+            Err(e) if e.is_timeout() || self.missing().is_none() => return Err(e.into()),
+            // The connection dropped mid-body.
+            Err(_) => {}
+        }
+        match self.missing() {
+            None => Ok(None),
+            Some(_) if self.allow_incomplete => {
+                *incomplete = true;
+                Ok(None)
+            }
+            Some(len) => Err(FetchError::Incomplete(self.received, len)),
+        }
+    }
+
+    /// The declared length, if fewer bytes than that have arrived.
+    fn missing(&self) -> Option<u64> {
+        self.expected.filter(|&len| self.received < len)
+    }
+}
+
+/// Bytes a header takes on the wire as `name: value\r\n`.
+fn header_line_size(name: &str, value: &[u8]) -> usize {
+    name.len() + value.len() + 4
+}
+
+/// Wait for `fetch` with the GVL released, giving up if the calling thread
+/// or the client is interrupted, then decode the body (see `decode_response`).
+///
+/// # Safety
+/// Same as `without_gvl`: `fetch` must not touch Ruby objects.
+unsafe fn wait_for_response(
+    rt: &'static Runtime,
+    fetch: impl std::future::Future<Output = Result<ResponseData, FetchError>>,
+    client_token: &CancellationToken,
+    decoders: Option<Decoders>,
+    deadline: Option<Deadline>,
+) -> RequestOutcome {
+    without_gvl(|thread_token| {
+        let outcome = rt.block_on(async {
+            tokio::select! {
+                biased;
+                _ = thread_token.cancelled() => RequestOutcome::Interrupted,
+                _ = client_token.cancelled() => RequestOutcome::Interrupted,
+                d = Deadline::expired(deadline) => RequestOutcome::DeadlineExceeded(d),
+                res = fetch => match res {
+                    Ok(data) => RequestOutcome::Ok(data),
+                    Err(FetchError::Http(e)) => RequestOutcome::Err(e),
+                    Err(FetchError::Io(e)) => RequestOutcome::Io(e),
+                    Err(FetchError::TooLarge(max)) => RequestOutcome::TooLarge(max),
+                    Err(FetchError::HeadersTooLarge(size, max)) => RequestOutcome::HeadersTooLarge(size, max),
+                    Err(FetchError::Incomplete(got, len)) => RequestOutcome::Incomplete(got, len),
+                    Err(FetchError::PinMismatch(msg)) => RequestOutcome::PinMismatch(msg),
+                },
+            }
+        });
+        match outcome {
+            RequestOutcome::Ok(data) => decode_response(data, decoders),
+            outcome => outcome,
+        }
+    })
 }
 
 // --------------------------------------------------------------------------
 // Emulation helpers
 // --------------------------------------------------------------------------
 
+/// Bodies above this many bytes are streamed unless `stream_body_over:` says otherwise.
+const DEFAULT_STREAM_BODY_OVER: usize = 8 * 1024 * 1024;
+
+/// Response header bytes allowed when `max_header_size:` is not given.
+const DEFAULT_MAX_HEADER_SIZE: usize = 256 * 1024;
+
+/// `Client#each_event` reconnection delay until the server sends `retry:`.
+const DEFAULT_SSE_RECONNECT_DELAY: Duration = Duration::from_secs(3);
+
+/// Redirects followed by `redirect: true`, and by pinned clients without `redirect:`.
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+/// Default write buffer for `Client#download`.
+const DEFAULT_DOWNLOAD_BUFFER_SIZE: usize = 64 * 1024;
+
+/// `Wreq.benchmark` defaults for `count:` and `concurrency:`.
+const DEFAULT_BENCHMARK_COUNT: usize = 100;
+const DEFAULT_BENCHMARK_CONCURRENCY: usize = 10;
+
+/// Body bytes `Client#peek` reads when `bytes:` is not given.
+const DEFAULT_PEEK_BYTES: usize = 4096;
+
+/// Chunk size used when streaming an in-memory body.
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
 /// The default emulation to apply when none is specified.
 const DEFAULT_EMULATION: BrowserEmulation = BrowserEmulation::Chrome145;
 
+/// Set by `Wreq.default_emulation=`; replaces `DEFAULT_EMULATION` for
+/// clients built afterwards.
+static DEFAULT_EMULATION_OVERRIDE: std::sync::Mutex<Option<BrowserEmulation>> = std::sync::Mutex::new(None);
+
+/// The emulation applied when none is specified.
+fn default_emulation() -> BrowserEmulation {
+    DEFAULT_EMULATION_OVERRIDE.lock().unwrap_or_else(|e| e.into_inner()).unwrap_or(DEFAULT_EMULATION)
+}
+
+/// The name `parse_emulation` accepts for `emu`, e.g. "chrome_145".
+fn emulation_name(emu: BrowserEmulation) -> String {
+    match serde_json::to_value(emu) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => format!("{:?}", emu),
+    }
+}
+
 /// Parse a Ruby string like "chrome_143" into a BrowserEmulation variant.
 fn parse_emulation(name: &str) -> Result<BrowserEmulation, magnus::Error> {
     let json_val = serde_json::Value::String(name.to_string());
@@ -158,6 +598,93 @@ fn parse_emulation_os(name: &str) -> Result<EmulationOS, magnus::Error> {
         .map_err(|_| generic_error("unknown emulation_os. Use: 'windows', 'macos', 'linux', 'android', 'ios'"))
 }
 
+/// Resolve an emulation profile for the client builder, dropping the
+/// profile's `Accept-Encoding` header when `send_accept_encoding` is false
+/// and replacing its `Accept-Language` in place (keeping its wire position).
+fn client_emulation(
+    opt: EmulationOption,
+    send_accept_encoding: bool,
+    accept_language: Option<&HeaderValue>,
+) -> wreq::Emulation {
+    let mut emulation = opt.emulation();
+    if !send_accept_encoding {
+        emulation.headers_mut().remove(wreq::header::ACCEPT_ENCODING);
+    }
+    if let Some(lang) = accept_language {
+        emulation.headers_mut().insert(wreq::header::ACCEPT_LANGUAGE, lang.clone());
+    }
+    emulation
+}
+
+/// Read `accept_language:` (sent verbatim) or `locale:` (expanded to a
+/// browser-style value such as "fr-FR,fr;q=0.9"). Either may also be an
+/// Array of tags, weighted by position, or of `[tag, q]` pairs.
+fn accept_language_option(opts: &RHash) -> Result<Option<HeaderValue>, magnus::Error> {
+    let value = match hash_get_value(opts, "accept_language")? {
+        Some(v) => match RArray::from_value(v) {
+            Some(ary) => weighted_languages(ary, "accept_language")?,
+            None => TryConvert::try_convert(v)?,
+        },
+        None => match hash_get_value(opts, "locale")? {
+            Some(v) => match RArray::from_value(v) {
+                Some(ary) => weighted_languages(ary, "locale")?,
+                None => locale_to_accept_language(&String::try_convert(v)?)?,
+            },
+            None => return Ok(None),
+        },
+    };
+    HeaderValue::from_str(&value)
+        .map(Some)
+        .map_err(|e| invalid_header(format!("invalid Accept-Language: '{}'", value), &e))
+}
+
+/// Join language tags into an Accept-Language value. Bare tags get
+/// q-values falling by 0.1 from 1.0 (floored at 0.1), as browsers send;
+/// `[tag, q]` pairs keep their own weight. A weight of 1 is left implicit.
+/// `option` names the option the array came from, for error messages.
+fn weighted_languages(ary: RArray, option: &str) -> Result<String, magnus::Error> {
+    if ary.is_empty() {
+        return Err(generic_error(format!("{}: array must not be empty", option)));
+    }
+    let mut parts = Vec::with_capacity(ary.len());
+    for (i, entry) in ary.into_iter().enumerate() {
+        let (tag, q) = match RArray::from_value(entry) {
+            Some(pair) => {
+                let (tag, q): (String, f64) = TryConvert::try_convert(pair.as_value())?;
+                if !(0.0..=1.0).contains(&q) {
+                    return Err(generic_error(format!("{} weight for '{}' must be between 0 and 1", option, tag)));
+                }
+                (tag, q)
+            }
+            None => (String::try_convert(entry)?, (10 - i.min(9)) as f64 / 10.0),
+        };
+        let tag = tag.trim().replace('_', "-");
+        if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '*') {
+            return Err(generic_error(format!("invalid {} tag: '{}'", option, tag)));
+        }
+        if q >= 1.0 {
+            parts.push(tag);
+        } else {
+            let q = format!("{:.3}", q);
+            parts.push(format!("{};q={}", tag, q.trim_end_matches('0').trim_end_matches('.')));
+        }
+    }
+    Ok(parts.join(","))
+}
+
+/// Expand a locale tag like "fr-FR" into "fr-FR,fr;q=0.9", the shape
+/// browsers send for a single preferred language.
+fn locale_to_accept_language(locale: &str) -> Result<String, magnus::Error> {
+    let locale = locale.trim().replace('_', "-");
+    if locale.is_empty() || !locale.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(generic_error(format!("invalid locale: '{}'", locale)));
+    }
+    match locale.split_once('-') {
+        Some((lang, _)) => Ok(format!("{},{};q=0.9", locale, lang)),
+        None => Ok(locale),
+    }
+}
+
 /// Build an EmulationOption from an Emulation and an optional OS from the opts hash.
 fn build_emulation_option(
     emu: BrowserEmulation,
@@ -177,23 +704,106 @@ fn build_emulation_option(
 // Ruby Client
 // --------------------------------------------------------------------------
 
-#[magnus::wrap(class = "Wreq::Client", free_immediately)]
+#[magnus::wrap(class = "Wreq::Client", free_immediately, mark)]
 struct Client {
     inner: wreq::Client,
     cancel_token: std::sync::Mutex<CancellationToken>,
+    cookie_jar: Option<Arc<Jar>>,
+    cookie_jar_path: Option<String>,
+    stream_body_over: usize,
+    /// Set when wreq's own decoders are off (`send_accept_encoding: false`)
+    /// and response bodies are decoded after download instead.
+    manual_decoders: Option<Decoders>,
+    download_buffer_size: usize,
+    body_limit: Option<BodyLimit>,
+    /// `max_header_size:` cap on a response's total header bytes.
+    max_header_size: usize,
+    hooks: Hooks,
+    /// `pin_sha256:` pins checked against every response's server key.
+    pins: Option<Arc<PinSet>>,
+    default_headers: HeaderMap,
+    /// `base_url:` that relative request URLs are resolved against.
+    base_url: Option<Url>,
+    /// `default_scheme:` prepended to URLs given without one.
+    default_scheme: &'static str,
+    /// `total_timeout:` budget for a whole request, redirects and body included.
+    total_timeout: Option<Duration>,
+    /// `allow_incomplete:` default for requests.
+    allow_incomplete: bool,
+    /// `content_length: :decoded`: report the decoded body size as
+    /// `Response#content_length` instead of the header.
+    decoded_content_length: bool,
+    /// `proxies:` pool, one of which is used for each request.
+    proxies: Vec<wreq::Proxy>,
+    proxy_random: bool,
+    next_proxy: AtomicUsize,
+    /// Set by `close`; every later request raises.
+    closed: AtomicBool,
+    /// Per-origin request counts for `pool_stats`.
+    pool_stats: PoolStats,
+    /// `cache:` store of GET responses.
+    cache: Option<ResponseCache>,
+    /// `circuit_breaker:` per-host failure tracking.
+    breaker: Option<CircuitBreaker>,
+    /// The resolver, when `dns_cache_ttl:` makes it cache.
+    dns_cache: Option<Arc<CachingResolver>>,
+    bytes_sent: AtomicU64,
+    /// Shared with the client's `stream: true` bodies, which count their
+    /// chunks as they are read.
+    bytes_received: Arc<AtomicU64>,
+}
+
+impl DataTypeFunctions for Client {
+    fn mark(&self, marker: &magnus::gc::Marker) {
+        self.hooks.mark(marker);
+    }
 }
 
 impl Client {
     /// Wreq::Client.new or Wreq::Client.new(options_hash)
+    ///
+    /// Options not given here fall back to those set with `Wreq.configure`.
     fn rb_new(args: &[Value]) -> Result<Self, magnus::Error> {
         let opts: Option<RHash> = if args.is_empty() {
             None
         } else {
             Some(RHash::try_convert(args[0])?)
         };
+        let opts = config::with_defaults(opts)?;
 
         let mut builder = wreq::Client::builder()
             .retry(wreq::retry::Policy::never());
+        let mut cookie_jar: Option<Arc<Jar>> = None;
+        let mut cookie_jar_path: Option<String> = None;
+        let mut manual_decoders: Option<Decoders> = None;
+        let mut stream_body_over = DEFAULT_STREAM_BODY_OVER;
+        let mut download_buffer_size = DEFAULT_DOWNLOAD_BUFFER_SIZE;
+        let mut body_limit: Option<BodyLimit> = None;
+        let mut max_header_size = DEFAULT_MAX_HEADER_SIZE;
+        let mut cache: Option<ResponseCache> = None;
+        let mut breaker: Option<CircuitBreaker> = None;
+        let mut hooks = Hooks::default();
+        let mut pins: Option<Arc<PinSet>> = None;
+        // What the client will send by default, for `Client#default_headers`.
+        let mut default_headers = HeaderMap::new();
+        let mut base_url: Option<Url> = None;
+        let mut default_scheme = "https";
+        let mut proxies: Vec<wreq::Proxy> = Vec::new();
+        let mut proxy_random = false;
+        let mut total_timeout: Option<Duration> = None;
+        let mut allow_incomplete = false;
+        let mut decoded_content_length = false;
+
+        // Always ours, with or without options, so lookup failures can be
+        // classified by type; the default TTL of 0 caches nothing, like
+        // wreq's own resolver.
+        let dns_ttl = match opts.as_ref().map(|o| hash_get_value(o, "dns_cache_ttl")).transpose()?.flatten() {
+            Some(val) => value_to_duration(val, "dns_cache_ttl")?,
+            None => Duration::ZERO,
+        };
+        let resolver = Arc::new(CachingResolver::new(dns_ttl));
+        builder = builder.dns_resolver(resolver.clone());
+        let dns_cache = (!dns_ttl.is_zero()).then_some(resolver);
 
         if let Some(opts) = opts {
             // Apply header_order BEFORE emulation so the user's ordering takes precedence
@@ -202,88 +812,230 @@ impl Client {
                 builder = builder.orig_headers(orig);
             }
 
-            if let Some(val) = hash_get_value(&opts, "emulation")? {
+            let send_accept_encoding = hash_get_bool(&opts, "send_accept_encoding")?.unwrap_or(true);
+            let accept_language = accept_language_option(&opts)?;
+
+            let emulation = if let Some(val) = hash_get_value(&opts, "emulation")? {
                 let ruby = unsafe { Ruby::get_unchecked() };
                 if val.is_kind_of(ruby.class_false_class()) {
                     // emulation: false — skip emulation entirely
+                    None
                 } else if val.is_kind_of(ruby.class_true_class()) {
-                    let opt = build_emulation_option(DEFAULT_EMULATION, &opts)?;
-                    builder = builder.emulation(opt);
+                    let opt = build_emulation_option(default_emulation(), &opts)?;
+                    Some(client_emulation(opt, send_accept_encoding, accept_language.as_ref()))
                 } else {
                     let name: String = TryConvert::try_convert(val)?;
                     let emu = parse_emulation(&name)?;
                     let opt = build_emulation_option(emu, &opts)?;
-                    builder = builder.emulation(opt);
+                    Some(client_emulation(opt, send_accept_encoding, accept_language.as_ref()))
                 }
             } else {
-                let opt = build_emulation_option(DEFAULT_EMULATION, &opts)?;
-                builder = builder.emulation(opt);
+                let opt = build_emulation_option(default_emulation(), &opts)?;
+                Some(client_emulation(opt, send_accept_encoding, accept_language.as_ref()))
+            };
+
+            match emulation {
+                Some(emulation) => {
+                    default_headers.extend(emulation.headers().clone());
+                    builder = builder.emulation(emulation);
+                }
+                None => {
+                    if let Some(lang) = &accept_language {
+                        let mut hmap = HeaderMap::new();
+                        hmap.insert(wreq::header::ACCEPT_LANGUAGE, lang.clone());
+                        default_headers.extend(hmap.clone());
+                        builder = builder.default_headers(hmap);
+                    }
+                }
             }
 
             if let Some(ua) = hash_get_string(&opts, "user_agent")? {
+                if let Ok(value) = HeaderValue::from_str(&ua) {
+                    default_headers.insert(wreq::header::USER_AGENT, value);
+                }
                 builder = builder.user_agent(ua);
             }
 
             if let Some(hdr_hash) = hash_get_hash(&opts, "headers")? {
                 let hmap = hash_to_header_map(&hdr_hash)?;
+                default_headers.extend(hmap.clone());
                 builder = builder.default_headers(hmap);
             }
 
-            if let Some(t) = hash_get_float(&opts, "timeout")? {
-                builder = builder.timeout(Duration::from_secs_f64(t));
+            if let Some(t) = hash_get_duration(&opts, "timeout")? {
+                builder = builder.timeout(t);
+            }
+            total_timeout = hash_get_duration(&opts, "total_timeout")?;
+            allow_incomplete = hash_get_bool(&opts, "allow_incomplete")?.unwrap_or(false);
+            if let Some(val) = hash_get_value(&opts, "content_length")? {
+                let mode: String = val.funcall("to_s", ())?;
+                decoded_content_length = match mode.as_str() {
+                    "raw" => false,
+                    "decoded" => true,
+                    _ => return Err(generic_error(format!("content_length must be :raw or :decoded, got '{}'", mode))),
+                };
+            }
+
+            // wreq times TCP connect and the TLS handshake as one phase and
+            // has no deadline for the handshake alone.
+            if hash_get_value(&opts, "tls_handshake_timeout")?.is_some() {
+                return Err(generic_error(
+                    "tls_handshake_timeout: is not supported: the TLS handshake cannot be timed on its own; \
+                     connect_timeout: bounds connecting and the handshake together",
+                ));
             }
 
-            if let Some(t) = hash_get_float(&opts, "connect_timeout")? {
-                builder = builder.connect_timeout(Duration::from_secs_f64(t));
+            if let Some(t) = hash_get_duration(&opts, "connect_timeout")? {
+                builder = builder.connect_timeout(t);
             }
 
-            if let Some(t) = hash_get_float(&opts, "read_timeout")? {
-                builder = builder.read_timeout(Duration::from_secs_f64(t));
+            if let Some(t) = hash_get_duration(&opts, "read_timeout")? {
+                builder = builder.read_timeout(t);
             }
 
+            // A pinned client never follows a redirect to another origin,
+            // whose connection the pins would not cover.
+            let pinned = hash_get_value(&opts, "pin_sha256")?.is_some();
             if let Some(val) = hash_get_value(&opts, "redirect")? {
                 let ruby = unsafe { Ruby::get_unchecked() };
                 if val.is_kind_of(ruby.class_false_class()) {
                     builder = builder.redirect(wreq::redirect::Policy::none());
                 } else if val.is_kind_of(ruby.class_true_class()) {
-                    builder = builder.redirect(wreq::redirect::Policy::limited(10));
+                    builder = builder.redirect(redirect_limit(DEFAULT_MAX_REDIRECTS, pinned));
                 } else {
                     let n: usize = TryConvert::try_convert(val)?;
-                    builder = builder.redirect(wreq::redirect::Policy::limited(n));
+                    builder = builder.redirect(redirect_limit(n, pinned));
                 }
+            } else if pinned {
+                builder = builder.redirect(redirect_limit(DEFAULT_MAX_REDIRECTS, true));
             }
 
-            if let Some(enabled) = hash_get_bool(&opts, "cookie_store")? {
-                builder = builder.cookie_store(enabled);
+            if let Some(val) = hash_get_value(&opts, "cookie_jar")? {
+                let jar: &CookieJar = TryConvert::try_convert(val)?;
+                cookie_jar = Some(jar.inner.clone());
+            } else if let Some(true) = hash_get_bool(&opts, "cookie_store")? {
+                cookie_jar = Some(Arc::new(Jar::default()));
+            }
+            if let Some(path) = hash_get_string(&opts, "cookie_jar_path")? {
+                let jar = cookie_jar.get_or_insert_with(|| Arc::new(Jar::default())).clone();
+                load_cookie_file(&CookieJar::from_jar(jar), &path)?;
+                cookie_jar_path = Some(path);
+            }
+            if let Some(jar) = &cookie_jar {
+                builder = builder.cookie_provider(jar.clone());
             }
 
             if let Some(proxy_url) = hash_get_string(&opts, "proxy")? {
-                let mut proxy = wreq::Proxy::all(&proxy_url).map_err(to_magnus_error)?;
-                if let (Some(user), Some(pass)) = (
+                let proxy = build_proxy(
+                    &proxy_url,
                     hash_get_string(&opts, "proxy_user")?,
                     hash_get_string(&opts, "proxy_pass")?,
-                ) {
-                    proxy = proxy.basic_auth(&user, &pass);
-                }
+                )?;
                 builder = builder.proxy(proxy);
             }
 
+            if let Some(ary) = hash_get_array(&opts, "proxy_chain")? {
+                // First entry is the outermost hop: the proxy this client
+                // connects to, which tunnels on to the next.
+                let chain = ary.to_vec::<String>()?;
+                match chain.as_slice() {
+                    [] => return Err(generic_error("proxy_chain: must not be empty")),
+                    [only] => builder = builder.proxy(build_proxy(only, None, None)?),
+                    _ => {
+                        for url in &chain {
+                            build_proxy(url, None, None)?;
+                        }
+                        return Err(generic_error(format!(
+                            "proxy_chain: wreq cannot tunnel through more than one proxy (got {}); \
+                             configure the chain on the first proxy ({}) instead",
+                            chain.len(),
+                            chain[0]
+                        )));
+                    }
+                }
+            }
+
+            if let Some(ary) = hash_get_array(&opts, "proxies")? {
+                // Credentials, if any, come from each URL's userinfo.
+                for url in ary.to_vec::<String>()? {
+                    proxies.push(build_proxy(&url, None, None)?);
+                }
+                if proxies.is_empty() {
+                    return Err(generic_error("proxies must not be empty"));
+                }
+            }
+            if let Some(val) = hash_get_value(&opts, "proxy_strategy")? {
+                // Accepts a Symbol (`:random`) or a String.
+                let strategy: String = val.funcall("to_s", ())?;
+                proxy_random = match strategy.as_str() {
+                    "round_robin" => false,
+                    "random" => true,
+                    other => {
+                        return Err(generic_error(format!(
+                            "unknown proxy_strategy '{}' (expected round_robin or random)",
+                            other
+                        )))
+                    }
+                };
+            }
+
             if let Some(true) = hash_get_bool(&opts, "no_proxy")? {
                 builder = builder.no_proxy();
             }
 
-            if let Some(enabled) = hash_get_bool(&opts, "https_only")? {
+            // wreq opens every connection itself and offers no hook for a
+            // caller-supplied stream; a Ruby IO could not be read from the
+            // runtime's threads anyway, as they never hold the GVL.
+            if hash_get_value(&opts, "socket")?.is_some() {
+                return Err(generic_error(
+                    "socket: is not supported: requests cannot be sent over a pre-connected IO; \
+                     tunnel through a SOCKS proxy (proxy: \"socks5h://...\") instead",
+                ));
+            }
+
+            let https_only = hash_get_bool(&opts, "https_only")?;
+            if let Some(enabled) = https_only {
                 builder = builder.https_only(enabled);
             }
 
-            if let Some(v) = hash_get_bool(&opts, "verify_host")? {
+            if let Some(val) = hash_get_value(&opts, "default_scheme")? {
+                let scheme: String = val.funcall("to_s", ())?;
+                default_scheme = match scheme.to_ascii_lowercase().as_str() {
+                    "https" => "https",
+                    "http" if https_only == Some(true) => {
+                        return Err(generic_error("default_scheme: \"http\" conflicts with https_only: true"))
+                    }
+                    "http" => "http",
+                    _ => return Err(generic_error(format!("default_scheme must be \"http\" or \"https\", got '{}'", scheme))),
+                };
+            }
+
+            // `verify_hostname:` is the descriptive spelling of `verify_host:`.
+            // Turning it off skips only the name check; the chain is still
+            // verified unless `verify_cert: false` is also given.
+            let verify_hostname = match hash_get_bool(&opts, "verify_hostname")? {
+                Some(v) => Some(v),
+                None => hash_get_bool(&opts, "verify_host")?,
+            };
+            if let Some(v) = verify_hostname {
                 builder = builder.verify_hostname(v);
             }
 
+            if let Some(v) = hash_get_value(&opts, "sni_hostname")? {
+                return Err(sni_hostname_error(v));
+            }
+
             if let Some(v) = hash_get_bool(&opts, "verify_cert")? {
                 builder = builder.cert_verification(v);
             }
 
+            // BoringSSL never fetches OCSP responses or CRLs while verifying,
+            // so there is no revocation check to turn off; `ocsp:` is only
+            // validated. Stapled responses requested by the emulation profile
+            // arrive inside the handshake and are left alone to keep the
+            // fingerprint intact.
+            let _ = hash_get_bool(&opts, "ocsp")?;
+
             if let Some(true) = hash_get_bool(&opts, "http1_only")? {
                 builder = builder.http1_only();
             }
@@ -291,24 +1043,48 @@ impl Client {
                 builder = builder.http2_only();
             }
 
-            if let Some(v) = hash_get_bool(&opts, "gzip")? {
-                builder = builder.gzip(v);
-            }
-            if let Some(v) = hash_get_bool(&opts, "brotli")? {
-                builder = builder.brotli(v);
-            }
-            if let Some(v) = hash_get_bool(&opts, "deflate")? {
-                builder = builder.deflate(v);
-            }
-            if let Some(v) = hash_get_bool(&opts, "zstd")? {
-                builder = builder.zstd(v);
+            if send_accept_encoding {
+                if let Some(v) = hash_get_bool(&opts, "gzip")? {
+                    builder = builder.gzip(v);
+                }
+                if let Some(v) = hash_get_bool(&opts, "brotli")? {
+                    builder = builder.brotli(v);
+                }
+                if let Some(v) = hash_get_bool(&opts, "deflate")? {
+                    builder = builder.deflate(v);
+                }
+                if let Some(v) = hash_get_bool(&opts, "zstd")? {
+                    builder = builder.zstd(v);
+                }
+            } else {
+                // wreq advertises Accept-Encoding whenever one of its decoders
+                // is on, so turn them all off and decode bodies ourselves.
+                let defaults = Decoders::default();
+                manual_decoders = Some(Decoders {
+                    gzip: hash_get_bool(&opts, "gzip")?.unwrap_or(defaults.gzip),
+                    brotli: hash_get_bool(&opts, "brotli")?.unwrap_or(defaults.brotli),
+                    deflate: hash_get_bool(&opts, "deflate")?.unwrap_or(defaults.deflate),
+                    zstd: hash_get_bool(&opts, "zstd")?.unwrap_or(defaults.zstd),
+                });
+                builder = builder.gzip(false).brotli(false).deflate(false).zstd(false);
             }
 
-            if let Some(v) = hash_get_bool(&opts, "referer")? {
+            // `auto_referer:` is the clearer spelling; `referer:` is kept for compatibility.
+            let auto_referer = match hash_get_bool(&opts, "auto_referer")? {
+                Some(v) => Some(v),
+                None => hash_get_bool(&opts, "referer")?,
+            };
+            if let Some(v) = auto_referer {
                 builder = builder.referer(v);
             }
 
-            if let Some(n) = hash_get_usize(&opts, "pool_max_idle_per_host")? {
+            // `reuse_connections: false` keeps no idle connections, so every
+            // request opens a new one; it wins over `pool_max_idle_per_host:`.
+            let idle_per_host = match hash_get_bool(&opts, "reuse_connections")? {
+                Some(false) => Some(0),
+                _ => hash_get_usize(&opts, "pool_max_idle_per_host")?,
+            };
+            if let Some(n) = idle_per_host {
                 builder = builder.pool_max_idle_per_host(n);
             }
 
@@ -320,16 +1096,95 @@ impl Client {
                 builder = builder.tcp_nodelay(v);
             }
 
-            if let Some(t) = hash_get_float(&opts, "tcp_keepalive")? {
-                builder = builder.tcp_keepalive(Duration::from_secs_f64(t));
+            if let Some(t) = hash_get_duration(&opts, "tcp_keepalive")? {
+                builder = builder.tcp_keepalive(t);
+            }
+
+            if let Some(s) = hash_get_string(&opts, "base_url")? {
+                let url = Url::parse(&s)
+                    .ok()
+                    .filter(|u| matches!(u.scheme(), "http" | "https"))
+                    .ok_or_else(|| generic_error(format!("invalid base_url: '{}'", s)))?;
+                base_url = Some(url);
+            }
+
+            if let Some(n) = hash_get_usize(&opts, "stream_body_over")? {
+                stream_body_over = n;
+            }
+
+            if let Some(n) = hash_get_usize(&opts, "download_buffer_size")? {
+                if n == 0 {
+                    return Err(generic_error("download_buffer_size must be positive"));
+                }
+                download_buffer_size = n;
+            }
+
+            let truncate = hash_get_bool(&opts, "truncate_body")?.unwrap_or(false);
+            if let Some(max) = hash_get_usize(&opts, "max_body_size")? {
+                body_limit = Some(BodyLimit { max, truncate });
+            }
+
+            if let Some(max) = hash_get_usize(&opts, "max_header_size")? {
+                if max == 0 {
+                    return Err(generic_error("max_header_size must be positive"));
+                }
+                max_header_size = max;
+            }
+
+            // `cache: true` or `cache: { max_entries:, ttl: }`.
+            if let Some(val) = hash_get_value(&opts, "cache")? {
+                if let Some(spec) = RHash::from_value(val) {
+                    let max_entries = hash_get_usize(&spec, "max_entries")?.unwrap_or(cache::DEFAULT_MAX_ENTRIES);
+                    if max_entries == 0 {
+                        return Err(generic_error("cache max_entries must be positive"));
+                    }
+                    let ttl = hash_get_duration(&spec, "ttl")?.unwrap_or(cache::DEFAULT_TTL);
+                    cache = Some(ResponseCache::new(max_entries, ttl));
+                } else if val.to_bool() {
+                    cache = Some(ResponseCache::new(cache::DEFAULT_MAX_ENTRIES, cache::DEFAULT_TTL));
+                }
+            }
+
+            // `circuit_breaker: true` or `circuit_breaker: { failure_threshold:, cooldown: }`.
+            if let Some(val) = hash_get_value(&opts, "circuit_breaker")? {
+                if let Some(spec) = RHash::from_value(val) {
+                    let threshold = hash_get_usize(&spec, "failure_threshold")?
+                        .map(|n| u32::try_from(n).unwrap_or(u32::MAX))
+                        .unwrap_or(breaker::DEFAULT_FAILURE_THRESHOLD);
+                    if threshold == 0 {
+                        return Err(generic_error("circuit_breaker failure_threshold must be positive"));
+                    }
+                    let cooldown = hash_get_duration(&spec, "cooldown")?.unwrap_or(breaker::DEFAULT_COOLDOWN);
+                    breaker = Some(CircuitBreaker::new(threshold, cooldown));
+                } else if val.to_bool() {
+                    breaker = Some(CircuitBreaker::new(breaker::DEFAULT_FAILURE_THRESHOLD, breaker::DEFAULT_COOLDOWN));
+                }
             }
 
+            hooks = Hooks::new(
+                hash_get_value(&opts, "before_request")?,
+                hash_get_value(&opts, "on_request")?,
+                hash_get_value(&opts, "on_response")?,
+                hash_get_value(&opts, "on_informational")?,
+            )?;
+
             if let Some(addr_str) = hash_get_string(&opts, "local_address")? {
                 let addr: IpAddr = addr_str.parse()
                     .map_err(|_| generic_error(format!("invalid IP address: '{}'", addr_str)))?;
                 builder = builder.local_address(addr);
             }
 
+            if let Some(val) = hash_get_value(&opts, "pin_sha256")? {
+                let list: Vec<String> = match RArray::from_value(val) {
+                    Some(ary) => ary.to_vec()?,
+                    None => vec![TryConvert::try_convert(val)?],
+                };
+                let set = PinSet::parse(&list).map_err(generic_error)?;
+                // The peer certificate is only exposed with tls_info enabled.
+                builder = builder.tls_info(true);
+                pins = Some(Arc::new(set));
+            }
+
             if let Some(v) = hash_get_bool(&opts, "tls_sni")? {
                 builder = builder.tls_sni(v);
             }
@@ -342,169 +1197,1597 @@ impl Client {
                 builder = builder.max_tls_version(parse_tls_version(&s)?);
             }
         } else {
-            builder = builder.emulation(DEFAULT_EMULATION);
+            let emulation = default_emulation().emulation();
+            default_headers = emulation.headers().clone();
+            builder = builder.emulation(emulation);
         }
 
         let client = builder.build().map_err(to_magnus_error)?;
-        Ok(Client { inner: client, cancel_token: std::sync::Mutex::new(CancellationToken::new()) })
+        Ok(Client {
+            inner: client,
+            cancel_token: std::sync::Mutex::new(CancellationToken::new()),
+            cookie_jar,
+            cookie_jar_path,
+            stream_body_over,
+            manual_decoders,
+            download_buffer_size,
+            body_limit,
+            max_header_size,
+            hooks,
+            pins,
+            default_headers,
+            base_url,
+            default_scheme,
+            total_timeout,
+            allow_incomplete,
+            decoded_content_length,
+            proxies,
+            proxy_random,
+            next_proxy: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            pool_stats: PoolStats::default(),
+            cache,
+            breaker,
+            dns_cache,
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: Arc::new(AtomicU64::new(0)),
+        })
     }
 
     /// client.get(url) or client.get(url, opts)
-    fn get(&self, args: &[Value]) -> Result<Response, magnus::Error> {
+    fn get(&self, args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
         self.execute_method("GET", args)
     }
 
-    fn post(&self, args: &[Value]) -> Result<Response, magnus::Error> {
+    fn post(&self, args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
         self.execute_method("POST", args)
     }
 
-    fn put(&self, args: &[Value]) -> Result<Response, magnus::Error> {
+    fn put(&self, args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
         self.execute_method("PUT", args)
     }
 
-    fn patch(&self, args: &[Value]) -> Result<Response, magnus::Error> {
+    fn patch(&self, args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
         self.execute_method("PATCH", args)
     }
 
-    fn delete(&self, args: &[Value]) -> Result<Response, magnus::Error> {
+    fn delete(&self, args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
         self.execute_method("DELETE", args)
     }
 
-    fn head(&self, args: &[Value]) -> Result<Response, magnus::Error> {
+    fn head(&self, args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
         self.execute_method("HEAD", args)
     }
 
-    fn options(&self, args: &[Value]) -> Result<Response, magnus::Error> {
+    fn options(&self, args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
         self.execute_method("OPTIONS", args)
     }
 
-    fn cancel(&self) {
-        // Replace the cancel token first so new requests use a fresh token,
-        // then cancel the old one to unblock all current in-flight select!s.
-        let old_token = {
-            let mut guard = self.cancel_token.lock().unwrap_or_else(|e| e.into_inner());
-            let old = guard.clone();
-            *guard = CancellationToken::new();
-            old
-        };
-        old_token.cancel();
+    /// client.allowed_methods(url) or client.allowed_methods(url, opts)
+    ///
+    /// Sends OPTIONS and returns the methods listed in `Allow`, uppercased
+    /// and without duplicates; empty when the header is absent.
+    fn allowed_methods(&self, args: &[Value]) -> Result<Vec<String>, magnus::Error> {
+        let resp = self.execute_method("OPTIONS", args)?;
+        let mut methods: Vec<String> = Vec::new();
+        for value in resp.header_values("allow") {
+            for method in value.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+                let method = method.to_ascii_uppercase();
+                if !methods.contains(&method) {
+                    methods.push(method);
+                }
+            }
+        }
+        Ok(methods)
     }
 
-    fn execute_method(&self, method_str: &str, args: &[Value]) -> Result<Response, magnus::Error> {
-        let url: String = if args.is_empty() {
-            return Err(generic_error("url is required"));
-        } else {
-            TryConvert::try_convert(args[0])?
-        };
+    /// client.try_get(url) or client.try_get(url, opts)
+    ///
+    /// Like `get`, but returns a `Wreq::Result` holding either the response
+    /// or the `Wreq::Error` instead of raising it.
+    fn try_get(&self, args: &[Value]) -> Result<RequestResult, magnus::Error> {
+        RequestResult::capture(self.execute_method("GET", args))
+    }
 
-        let opts: Option<RHash> = if args.len() > 1 {
-            Some(RHash::try_convert(args[1])?)
-        } else {
-            None
-        };
+    fn try_post(&self, args: &[Value]) -> Result<RequestResult, magnus::Error> {
+        RequestResult::capture(self.execute_method("POST", args))
+    }
 
-        let method: wreq::Method = method_str
-            .parse()
-            .map_err(|_| generic_error(format!("invalid HTTP method: {}", method_str)))?;
+    fn try_put(&self, args: &[Value]) -> Result<RequestResult, magnus::Error> {
+        RequestResult::capture(self.execute_method("PUT", args))
+    }
 
-        let mut req = self.inner.request(method, &url);
+    fn try_patch(&self, args: &[Value]) -> Result<RequestResult, magnus::Error> {
+        RequestResult::capture(self.execute_method("PATCH", args))
+    }
 
-        if let Some(opts) = opts {
-            req = apply_request_options(req, &opts)?;
-        }
+    fn try_delete(&self, args: &[Value]) -> Result<RequestResult, magnus::Error> {
+        RequestResult::capture(self.execute_method("DELETE", args))
+    }
 
-        let client_token = self.cancel_token.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    fn try_head(&self, args: &[Value]) -> Result<RequestResult, magnus::Error> {
+        RequestResult::capture(self.execute_method("HEAD", args))
+    }
 
-        // Release the GVL so other Ruby threads can run during I/O.
-        let outcome: RequestOutcome = unsafe {
-            without_gvl(|thread_token| {
-                runtime().block_on(async {
-                    tokio::select! {
-                        biased;
-                        _ = thread_token.cancelled() => RequestOutcome::Interrupted,
-                        _ = client_token.cancelled() => RequestOutcome::Interrupted,
-                        res = execute_request(req) => match res {
-                            Ok(data) => RequestOutcome::Ok(data),
-                            Err(e) => RequestOutcome::Err(e),
-                        },
-                    }
-                })
-            })
-        };
+    fn try_options(&self, args: &[Value]) -> Result<RequestResult, magnus::Error> {
+        RequestResult::capture(self.execute_method("OPTIONS", args))
+    }
 
-        let data = match outcome {
-            RequestOutcome::Ok(d) => d,
-            RequestOutcome::Err(e) => return Err(to_magnus_error(e)),
-            RequestOutcome::Interrupted => return Err(generic_error("request interrupted")),
-        };
-        Ok(Response::new(data.status, data.headers, data.body, data.url, data.version, data.content_length, data.transfer_size))
+    /// client.send_request(request)
+    ///
+    /// Send a `Wreq::Request` spec. The spec is read when the request is
+    /// built, so it can be changed and sent again.
+    fn send_request(&self, request: &Request) -> Result<Obj<Response>, magnus::Error> {
+        let (method, url, opts) = request.parts()?;
+        let prepared = self.build_request(&method, &url, Some(&opts))?;
+        self.dispatch(prepared)
     }
-}
 
-fn apply_request_options(
-    mut req: wreq::RequestBuilder,
-    opts: &RHash,
-) -> Result<wreq::RequestBuilder, magnus::Error> {
-    if let Some(hdr_hash) = hash_get_hash(opts, "headers")? {
-        let hmap = hash_to_header_map(&hdr_hash)?;
-        req = req.headers(hmap);
+    /// client.default_headers
+    ///
+    /// Headers sent with every request: the emulation's headers overlaid
+    /// with `user_agent:` and `headers:`. Multiple values are joined with
+    /// ", "; Authorization values are redacted.
+    fn default_headers(&self) -> Result<RHash, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let hash = ruby.hash_new();
+        for name in self.default_headers.keys() {
+            let value = if name == wreq::header::AUTHORIZATION || name == wreq::header::PROXY_AUTHORIZATION {
+                "[REDACTED]".to_owned()
+            } else {
+                self.default_headers
+                    .get_all(name)
+                    .iter()
+                    .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            hash.aset(name.as_str(), value)?;
+        }
+        Ok(hash)
     }
 
-    if let Some(body_str) = hash_get_string(opts, "body")? {
-        req = req.body(body_str);
+    /// The client's `base_url:`, or nil.
+    fn base_uri(&self) -> Option<String> {
+        self.base_url.as_ref().map(|u| u.to_string())
     }
 
-    if let Some(json_val) = hash_get_value(opts, "json")? {
+    /// The client's cookie jar, or nil if cookies are not stored.
+    fn cookie_jar(&self) -> Option<CookieJar> {
+        self.cookie_jar.clone().map(CookieJar::from_jar)
+    }
+
+    /// client.pool_stats => { idle: nil, active:, per_host: { ... } }
+    ///
+    /// Approximate pool usage from this client's own bookkeeping: requests
+    /// in flight overall and, per `host:port`, in flight now (`active`), at
+    /// most at once (`peak`), and in total (`requests`). wreq does not
+    /// expose its pool, so idle connections are unknown and `idle` is nil.
+    fn pool_stats(&self) -> Result<RHash, magnus::Error> {
         let ruby = unsafe { Ruby::get_unchecked() };
-        let json_module: Value = ruby.class_object().const_get("JSON")?;
-        let json_str: String = json_module.funcall("generate", (json_val,))?;
-        req = req
-            .header("content-type", "application/json")
-            .body(json_str);
+        self.pool_stats.to_hash(&ruby)
     }
 
-    if let Some(form_hash) = hash_get_hash(opts, "form")? {
-        let pairs = hash_to_pairs(&form_hash)?;
-        req = req.form(&pairs);
+    /// client.cache_stats => { hits:, misses:, entries:, max_entries:, ttl: },
+    /// or nil without `cache:`.
+    fn cache_stats(&self) -> Result<Option<RHash>, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        self.cache.as_ref().map(|c| c.stats(&ruby)).transpose()
+    }
+
+    /// client.dns_cache_stats => { hits:, misses:, entries:, ttl: }, or nil
+    /// without `dns_cache_ttl:`.
+    fn dns_cache_stats(&self) -> Result<Option<RHash>, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        self.dns_cache.as_ref().map(|c| c.stats(&ruby)).transpose()
     }
 
-    if let Some(query_hash) = hash_get_hash(opts, "query")? {
-        let pairs = hash_to_pairs(&query_hash)?;
-        req = req.query(&pairs);
+    /// client.cache_clear: forget every cached response.
+    fn cache_clear(&self) {
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
     }
 
-    if let Some(t) = hash_get_float(opts, "timeout")? {
-        req = req.timeout(Duration::from_secs_f64(t));
+    /// Total request body bytes sent by this client.
+    fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
     }
 
-    if let Some(token) = hash_get_string(opts, "auth")? {
-        req = req.auth(token);
+    /// Total response body bytes received by this client, as transferred on
+    /// the wire (before decompression) when known.
+    fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
     }
 
-    if let Some(token) = hash_get_string(opts, "bearer")? {
-        req = req.bearer_auth(token);
+    fn reset_counters(&self) {
+        self.bytes_sent.store(0, Ordering::Relaxed);
+        self.bytes_received.store(0, Ordering::Relaxed);
     }
 
-    if let Some(basic_val) = hash_get_value(opts, "basic")? {
-        let ary = RArray::try_convert(basic_val)?;
-        if ary.len() >= 2 {
-            let user: String = TryConvert::try_convert(ary.entry::<Value>(0)?)?;
-            let pass: String = TryConvert::try_convert(ary.entry::<Value>(1)?)?;
-            req = req.basic_auth(user, Some(pass));
+    /// Write the cookie jar to `cookie_jar_path` in Netscape format.
+    /// A no-op when the client has no `cookie_jar_path`.
+    fn save_cookies(&self) -> Result<(), magnus::Error> {
+        if let (Some(jar), Some(path)) = (&self.cookie_jar, &self.cookie_jar_path) {
+            let cookies = CookieJar::from_jar(jar.clone()).stored_cookies();
+            let data = cookie_jar::to_netscape(&cookies);
+            // Write to a sibling file first so a crash never leaves a
+            // half-written jar behind.
+            let tmp = format!("{}.tmp", path);
+            std::fs::write(&tmp, data)
+                .and_then(|_| std::fs::rename(&tmp, path))
+                .map_err(|e| generic_error(format!("cannot save cookies to '{}': {}", path, e)))?;
         }
+        Ok(())
     }
 
-    if let Some(proxy_url) = hash_get_string(opts, "proxy")? {
-        let proxy = wreq::Proxy::all(&proxy_url).map_err(to_magnus_error)?;
-        req = req.proxy(proxy);
+    /// Cancel in-flight requests and persist cookies to `cookie_jar_path`.
+    /// Requests made after this raise `Wreq::Error`.
+    fn close(&self) -> Result<(), magnus::Error> {
+        self.closed.store(true, Ordering::Release);
+        self.cancel();
+        self.save_cookies()
     }
 
-    if let Some(val) = hash_get_value(opts, "emulation")? {
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// client.with_timeout(seconds) { ... }
+    ///
+    /// Applies `seconds` as the timeout of every request this client makes
+    /// from the current fiber inside the block. Scopes nest; the previous
+    /// timeout is restored however the block exits. A per-request
+    /// `timeout:` still takes precedence.
+    fn with_timeout(rb_self: Obj<Self>, seconds: Value) -> Result<Value, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let timeout = positive_duration(seconds, "timeout")?;
+        if !ruby.block_given() {
+            return Err(generic_error("with_timeout requires a block"));
+        }
+        let id = rb_self.scope_id();
+        let nanos = u64::try_from(timeout.as_nanos()).unwrap_or(u64::MAX);
+        let stack = scoped_timeouts()?;
+        stack.push((id, nanos))?;
+        let result = ruby.yield_value(rb_self);
+        let popped = (|| {
+            for pos in (0..stack.len()).rev() {
+                let owner: usize = RArray::try_convert(stack.entry::<Value>(pos as isize)?)?.entry(0)?;
+                if owner == id {
+                    let _: Value = stack.funcall("delete_at", (pos,))?;
+                    break;
+                }
+            }
+            Ok::<_, magnus::Error>(())
+        })();
+        let value = result?;
+        popped.map(|_| value)
+    }
+
+    /// client.with_cookies({ "session" => "abc" }) { ... } or
+    /// client.with_cookies(cookies, url) { ... }
+    ///
+    /// Sets the cookies in the client's jar for the duration of the block,
+    /// as if `url` (default: the client's `base_url`) had sent them: host-
+    /// only with `Path=/`. When the block exits, however it exits, each
+    /// name gets back the value the jar held for it before, or is removed.
+    /// Nested scopes stack and unwind the same way.
+    fn with_cookies(rb_self: Obj<Self>, args: &[Value]) -> Result<Value, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        if !ruby.block_given() {
+            return Err(generic_error("with_cookies requires a block"));
+        }
+        let cookies = match args {
+            [cookies] | [cookies, _] => RHash::try_convert(*cookies)?,
+            _ => return Err(generic_error("with_cookies takes a Hash of cookies and an optional url")),
+        };
+        let jar = match &rb_self.cookie_jar {
+            Some(jar) => CookieJar::from_jar(jar.clone()),
+            None => return Err(generic_error("with_cookies requires a cookie jar (cookie_store: true or cookie_jar:)")),
+        };
+        let url = match args.get(1).filter(|v| !v.is_nil()) {
+            Some(v) => with_default_scheme(&String::try_convert(*v)?, rb_self.default_scheme),
+            None => match &rb_self.base_url {
+                Some(base) => base.to_string(),
+                None => return Err(generic_error("with_cookies needs a url when the client has no base_url")),
+            },
+        };
+        let uri: http::Uri = url.parse().map_err(|_| generic_error(format!("invalid URL: '{}'", url)))?;
+        let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+
+        let mut pairs = Vec::new();
+        cookies.foreach(|k: Value, v: Value| {
+            let name: String = k.funcall("to_s", ())?;
+            let value: String = v.funcall("to_s", ())?;
+            if !cookie_jar::is_valid_cookie(&name, &value) {
+                return Err(generic_error(format!("invalid cookie: {}={}", name, value)));
+            }
+            pairs.push((name, value));
+            Ok(magnus::r_hash::ForEach::Continue)
+        })?;
+
+        // The cookie each pair replaces: same name, host-only, `Path=/`.
+        let stored = jar.stored_cookies();
+        let saved: Vec<(String, Option<cookie_jar::StoredCookie>)> = pairs
+            .iter()
+            .map(|(name, _)| {
+                let index = stored.iter().position(|c| {
+                    c.name == *name && c.host_only && c.path == "/" && c.domain.eq_ignore_ascii_case(&host)
+                });
+                (name.clone(), index.map(|i| stored[i].clone()))
+            })
+            .collect();
+        for (name, value) in &pairs {
+            jar.inner.add_cookie_str(&format!("{}={}; Path=/", name, value), &uri);
+        }
+        let result = ruby.yield_value(rb_self);
+        let mut restored = Ok(());
+        for (name, previous) in saved {
+            match previous {
+                Some(cookie) => restored = restored.and(jar.insert(&cookie)),
+                None => jar.inner.add_cookie_str(&format!("{}=; Path=/; Max-Age=0", name), &uri),
+            }
+        }
+        let value = result?;
+        restored.map(|_| value)
+    }
+
+    /// The next proxy from `proxies:`, in turn or at random.
+    fn pick_proxy(&self) -> Option<wreq::Proxy> {
+        if self.proxies.is_empty() {
+            return None;
+        }
+        let n = self.next_proxy.fetch_add(1, Ordering::Relaxed);
+        let index = if self.proxy_random {
+            random_index(n, self.proxies.len())
+        } else {
+            n % self.proxies.len()
+        };
+        Some(self.proxies[index].clone())
+    }
+
+    /// Identifies this client in `with_timeout` scopes; wrapped clients never move.
+    fn scope_id(&self) -> usize {
+        self as *const Client as usize
+    }
+
+    /// The innermost `with_timeout` in effect for this client in the
+    /// current fiber.
+    fn scoped_timeout(&self) -> Result<Option<Duration>, magnus::Error> {
+        let stack = scoped_timeouts()?;
+        let id = self.scope_id();
+        for pos in (0..stack.len()).rev() {
+            let scope = RArray::try_convert(stack.entry::<Value>(pos as isize)?)?;
+            if scope.entry::<usize>(0)? == id {
+                return Ok(Some(Duration::from_nanos(scope.entry(1)?)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// client.cancel / client.abort_all
+    ///
+    /// Interrupt every in-flight request with `Wreq::InterruptedError`.
+    /// The client stays usable.
+    fn cancel(&self) {
+        // Replace the cancel token first so new requests use a fresh token,
+        // then cancel the old one to unblock all current in-flight select!s.
+        let old_token = {
+            let mut guard = self.cancel_token.lock().unwrap_or_else(|e| e.into_inner());
+            let old = guard.clone();
+            *guard = CancellationToken::new();
+            old
+        };
+        old_token.cancel();
+    }
+
+    fn execute_method(&self, method_str: &str, args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
+        let (url, opts) = parse_url_and_opts(args)?;
+        let prepared = self.build_request(method_str, &url, opts.as_ref())?;
+        self.dispatch(prepared)
+    }
+
+    /// Build a request for `method_str` with per-request options applied.
+    fn build_request(
+        &self,
+        method_str: &str,
+        url: &str,
+        opts: Option<&RHash>,
+    ) -> Result<Prepared, magnus::Error> {
+        if self.is_closed() {
+            return Err(generic_error("client is closed"));
+        }
+        let method: wreq::Method = method_str
+            .parse()
+            .map_err(|_| generic_error(format!("invalid HTTP method: {}", method_str)))?;
+
+        let url = match &self.base_url {
+            Some(base) => resolve_url(base, url).map_err(generic_error)?,
+            None => with_default_scheme(url, self.default_scheme),
+        };
+        let url = match opts.map(raw_query).transpose()?.flatten() {
+            Some(query) => append_query(&url, &query),
+            None => url,
+        };
+        let mut req = self.inner.request(method, url.as_str());
+        if let Some(t) = self.scoped_timeout()? {
+            req = req.timeout(t);
+        }
+        // A per-request `proxy:` replaces the rotation for that request.
+        let explicit_proxy = match opts {
+            Some(o) => hash_get_value(o, "proxy")?.is_some(),
+            None => false,
+        };
+        if !explicit_proxy {
+            if let Some(proxy) = self.pick_proxy() {
+                req = req.proxy(proxy);
+            }
+        }
+        let mut producer = None;
+        let mut body_limit = self.body_limit;
+        let mut meta = None;
+        let mut stream = false;
+        let mut manual_decoders = self.manual_decoders;
+        let mut total_timeout = self.total_timeout;
+        let mut allow_incomplete = self.allow_incomplete;
+        let mut extensions = None;
+
+        if let Some(opts) = opts {
+            if let Some(t) = hash_get_duration(opts, "total_timeout")? {
+                total_timeout = Some(t);
+            }
+            if let Some(v) = hash_get_bool(opts, "allow_incomplete")? {
+                allow_incomplete = v;
+            }
+            req = apply_request_options(self, req, opts, &mut producer)?;
+            body_limit = request_body_limit(body_limit, opts)?;
+            meta = hash_get_value(opts, "meta")?;
+            if let Some(hash) = hash_get_hash(opts, "extensions")? {
+                let mut map = std::collections::BTreeMap::new();
+                hash.foreach(|k: Value, v: Value| {
+                    map.insert(k.funcall("to_s", ())?, v.funcall("to_s", ())?);
+                    Ok(magnus::r_hash::ForEach::Continue)
+                })?;
+                extensions = Some(RequestExtensions(map));
+            }
+            stream = hash_get_bool(opts, "stream")?.unwrap_or(false);
+            (req, manual_decoders) = apply_request_decoders(req, opts, manual_decoders)?;
+        }
+        // The clock starts here, so building the body counts against it too.
+        let deadline = total_timeout.map(|t| Deadline { at: tokio::time::Instant::now() + t, budget: t });
+        Ok(Prepared { req, producer, body_limit, meta, stream, manual_decoders, deadline, allow_incomplete, extensions })
+    }
+
+    /// Send a built request with the GVL released and wrap the result.
+    fn dispatch(&self, prepared: Prepared) -> Result<Obj<Response>, magnus::Error> {
+        let sink = if prepared.stream { BodySink::Stream } else { BodySink::Buffer };
+        self.dispatch_into(prepared, sink)
+    }
+
+    /// Like `dispatch`, but with control over where the body is written.
+    fn dispatch_into(&self, prepared: Prepared, sink: BodySink) -> Result<Obj<Response>, magnus::Error> {
+        let result = self.send_prepared(prepared, sink);
+        // Back on the calling thread: hand log lines to `Wreq.log_sink`.
+        crate::log::deliver();
+        result
+    }
+
+    /// The part of a request that runs on the runtime: send it, check the
+    /// server key against `pin_sha256:`, and read the response into `sink`.
+    fn fetch(
+        &self,
+        http: &wreq::Client,
+        request: wreq::Request,
+        sink: BodySink,
+        body_limit: Option<BodyLimit>,
+        allow_incomplete: bool,
+    ) -> impl std::future::Future<Output = Result<ResponseData, FetchError>> + Send + 'static {
+        let pending = http.execute(request);
+        let pins = self.pins.clone();
+        let pending = async move {
+            let resp = pending.await?;
+            if let Some(pins) = pins {
+                pins.verify(&resp).map_err(FetchError::PinMismatch)?;
+            }
+            Ok::<_, FetchError>(resp)
+        };
+        execute_request(pending, sink, body_limit, allow_incomplete, self.max_header_size)
+    }
+
+    fn send_prepared(&self, prepared: Prepared, sink: BodySink) -> Result<Obj<Response>, magnus::Error> {
+        let rt = runtime()?;
+        let Prepared { req, producer, body_limit, meta, manual_decoders, deadline, allow_incomplete, extensions, .. } =
+            prepared;
+        let (http, request) = req.build_split();
+        let mut request = request.map_err(to_magnus_error)?;
+        if let Some(extensions) = extensions {
+            request.extensions_mut().insert(extensions);
+        }
+        // Headers added by `before_request` (signatures, tokens) decide
+        // caching too, so it runs before the cache is consulted.
+        self.hooks.before_send(&mut request)?;
+        // Only plain GETs whose body is buffered, and that carry no
+        // credentials or ranges, are cached. A hit skips the network,
+        // the other hooks, and byte counters.
+        let cache_key = match &self.cache {
+            Some(_)
+                if request.method() == wreq::Method::GET
+                    && producer.is_none()
+                    && matches!(sink, BodySink::Buffer)
+                    && cache::admits(request.headers())
+                    && !self.cookie_jar.as_ref().is_some_and(|jar| CookieJar::sends_to(jar, request.uri())) =>
+            {
+                Some((request.uri().to_string(), request.headers().clone()))
+            }
+            _ => None,
+        };
+        if let (Some(cache), Some((key, headers))) = (&self.cache, &cache_key) {
+            if let Some(hit) = cache.get(key, headers) {
+                let ruby = unsafe { Ruby::get_unchecked() };
+                return Ok(ruby.obj_wrap(hit));
+            }
+        }
+        let permit = match &self.breaker {
+            Some(breaker) => Some(breaker.admit(request.uri())?),
+            None => None,
+        };
+        let _in_flight = self.pool_stats.begin(request.uri());
+        let mut sent = request_body_size(&request);
+
+        let info = if self.hooks.is_empty() {
+            None
+        } else {
+            let info = hooks::request_info(request.method().as_str(), &request.uri().to_string(), meta)?;
+            self.hooks.before(info)?;
+            Some(info)
+        };
+        // wreq stores a response's cookies before its pin is checked, so
+        // they are rolled back if it fails.
+        let saved_cookies = match (&self.pins, &self.cookie_jar) {
+            (Some(_), Some(jar)) => Some(CookieJar::from_jar(jar.clone()).stored_cookies()),
+            _ => None,
+        };
+        let started = std::time::Instant::now();
+        let client_token = self.cancel_token.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        // Bodies streamed to disk or to the caller are passed on as received.
+        let decoders = match sink {
+            BodySink::Buffer => manual_decoders,
+            BodySink::File { .. } | BodySink::Stream => None,
+        };
+
+        let fetch = self.fetch(&http, request, sink, body_limit, allow_incomplete);
+        let outcome = match producer {
+            // Release the GVL so other Ruby threads can run during I/O.
+            None => unsafe { wait_for_response(rt, fetch, &client_token, decoders, deadline) },
+            Some(mut producer) => {
+                // The request runs on the runtime while this thread, holding
+                // the GVL, pulls chunks from Ruby and feeds them to its body.
+                let handle = rt.spawn(fetch);
+                let abort = handle.abort_handle();
+                sent = 0;
+                loop {
+                    let chunk = match producer.next_chunk() {
+                        Ok(Some(chunk)) => chunk,
+                        Ok(None) => break,
+                        Err(e) => {
+                            abort.abort();
+                            return Err(e);
+                        }
+                    };
+                    // Time spent in the Ruby producer counts against the budget.
+                    if let Some(d) = deadline.filter(|d| tokio::time::Instant::now() >= d.at) {
+                        abort.abort();
+                        return Err(d.error());
+                    }
+                    let len = chunk.len() as u64;
+                    let tx = producer.sender().clone();
+                    let send = unsafe {
+                        without_gvl(|thread_token| {
+                            rt.block_on(async {
+                                tokio::select! {
+                                    biased;
+                                    _ = thread_token.cancelled() => None,
+                                    _ = client_token.cancelled() => None,
+                                    d = Deadline::expired(deadline) => Some(Err(d)),
+                                    res = tx.send(Ok(chunk)) => Some(Ok(res.is_ok())),
+                                }
+                            })
+                        })
+                    };
+                    match send {
+                        Some(Ok(true)) => sent += len,
+                        // The request stopped reading its body (it failed or
+                        // the server answered early); collect its outcome.
+                        Some(Ok(false)) => break,
+                        Some(Err(d)) => {
+                            abort.abort();
+                            return Err(d.error());
+                        }
+                        None => {
+                            abort.abort();
+                            return Err(interrupted());
+                        }
+                    }
+                }
+                // Dropping the sender ends the body stream.
+                drop(producer);
+                let fetch = async move {
+                    handle
+                        .await
+                        .unwrap_or_else(|e| Err(FetchError::Io(std::io::Error::other(e))))
+                };
+                let outcome = unsafe { wait_for_response(rt, fetch, &client_token, decoders, deadline) };
+                if matches!(outcome, RequestOutcome::Interrupted | RequestOutcome::DeadlineExceeded(_)) {
+                    abort.abort();
+                }
+                outcome
+            }
+        };
+
+        if let Some(permit) = permit {
+            // Only signs of a struggling host count: transport failures,
+            // timeouts, truncated bodies, and 5xx statuses.
+            match &outcome {
+                RequestOutcome::Ok(d) => permit.record(d.status < 500),
+                RequestOutcome::Err(e) if !e.is_redirect() && !e.is_builder() => permit.record(false),
+                RequestOutcome::DeadlineExceeded(_) | RequestOutcome::Incomplete(..) => permit.record(false),
+                _ => {}
+            }
+        }
+        let data = match outcome {
+            RequestOutcome::Ok(d) => d,
+            RequestOutcome::Err(e) => return Err(to_magnus_error(e)),
+            RequestOutcome::Io(e) => return Err(generic_error(e)),
+            RequestOutcome::DecodeFailed(msg) => return Err(magnus::Error::new(decode_error(), msg)),
+            RequestOutcome::TooLarge(max) => {
+                let msg = format!("response body exceeds max_body_size ({} bytes)", max);
+                return Err(with_kind(wreq_error(), msg, "body"));
+            }
+            RequestOutcome::HeadersTooLarge(size, max) => {
+                let msg = format!("response headers are {} bytes, over max_header_size ({} bytes)", size, max);
+                return Err(with_kind(wreq_error(), msg, "headers"));
+            }
+            RequestOutcome::Incomplete(got, len) => {
+                let msg = format!("response body ended after {} of {} bytes (Content-Length)", got, len);
+                return Err(with_kind(wreq_error(), msg, "incomplete_body"));
+            }
+            RequestOutcome::PinMismatch(msg) => {
+                if let (Some(jar), Some(saved)) = (&self.cookie_jar, saved_cookies) {
+                    CookieJar::from_jar(jar.clone()).replace_all(&saved)?;
+                }
+                return Err(with_kind(tls_error(), msg, "tls"));
+            }
+            RequestOutcome::Interrupted => return Err(interrupted()),
+            RequestOutcome::DeadlineExceeded(d) => return Err(d.error()),
+        };
+        self.bytes_sent.fetch_add(sent, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(data.transfer_size.unwrap_or(data.body.len() as u64), Ordering::Relaxed);
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let mut resp =
+            Response::new(data.status, data.headers, data.body, data.url, data.version, data.content_length, data.transfer_size)
+                .with_body_truncated(data.truncated)
+                .with_incomplete(data.incomplete)
+                .with_request_body_size(sent);
+        if let Some(stream) = data.stream {
+            resp = resp.with_stream(BodyStream::new(stream, client_token, self.bytes_received.clone()));
+        }
+        if let Some(n) = data.written {
+            resp = resp.with_body_size(Some(n));
+        }
+        if self.decoded_content_length {
+            resp = resp.with_decoded_content_length();
+        }
+        if let (Some(cache), Some((key, headers))) = (&self.cache, cache_key) {
+            cache.store(key, &headers, &resp);
+        }
+        let resp = ruby.obj_wrap(resp);
+        if let Some(info) = info {
+            self.hooks.after(resp, info, started.elapsed())?;
+        }
+        Ok(resp)
+    }
+
+    /// client.get_json(url, opts = {})
+    fn get_json(&self, args: &[Value]) -> Result<Value, magnus::Error> {
+        let (url, opts) = parse_url_and_opts(args)?;
+        self.json_call("GET", &url, None, opts)
+    }
+
+    /// client.post_json(url, payload, opts = {})
+    fn post_json(&self, args: &[Value]) -> Result<Value, magnus::Error> {
+        let (url, payload, opts) = parse_url_payload_and_opts(args)?;
+        self.json_call("POST", &url, Some(payload), opts)
+    }
+
+    fn put_json(&self, args: &[Value]) -> Result<Value, magnus::Error> {
+        let (url, payload, opts) = parse_url_payload_and_opts(args)?;
+        self.json_call("PUT", &url, Some(payload), opts)
+    }
+
+    fn patch_json(&self, args: &[Value]) -> Result<Value, magnus::Error> {
+        let (url, payload, opts) = parse_url_payload_and_opts(args)?;
+        self.json_call("PATCH", &url, Some(payload), opts)
+    }
+
+    /// client.graphql(url, query, variables: {}, operation_name: nil, **opts)
+    ///
+    /// POST `{ query:, variables:, operationName: }` as JSON and return the
+    /// parsed response (`data` and `errors`), through the same path as
+    /// `post_json`. With `raise_errors: true`, a response carrying a
+    /// non-empty `errors` array raises `Wreq::Error` (`error_kind`
+    /// `:graphql`) listing their messages.
+    fn graphql(&self, args: &[Value]) -> Result<Value, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let (url, query, opts) = parse_url_payload_and_opts(args)?;
+        let envelope = ruby.hash_new();
+        envelope.aset("query", query)?;
+        let (variables, operation_name, raise_errors) = match &opts {
+            Some(o) => (
+                hash_get_value(o, "variables")?,
+                hash_get_value(o, "operation_name")?,
+                hash_get_bool(o, "raise_errors")?.unwrap_or(false),
+            ),
+            None => (None, None, false),
+        };
+        envelope.aset("variables", variables.unwrap_or_else(|| ruby.hash_new().as_value()))?;
+        if let Some(name) = operation_name {
+            envelope.aset("operationName", name)?;
+        }
+        let result = self.json_call("POST", &url, Some(envelope.as_value()), opts)?;
+        if raise_errors {
+            if let Some(hash) = RHash::from_value(result) {
+                let errors = match hash.get("errors") {
+                    Some(errors) => errors,
+                    None => hash.aref(ruby.to_symbol("errors"))?,
+                };
+                if let Some(errors) = RArray::from_value(errors).filter(|e| !e.is_empty()) {
+                    let mut messages = Vec::new();
+                    for error in errors.into_iter() {
+                        let message = match RHash::from_value(error) {
+                            Some(e) => e.get("message").or_else(|| e.get(ruby.to_symbol("message"))),
+                            None => None,
+                        };
+                        messages.push(match message {
+                            Some(m) => m.funcall::<_, _, String>("to_s", ())?,
+                            None => error.funcall::<_, _, String>("inspect", ())?,
+                        });
+                    }
+                    let msg = format!("GraphQL request to {} returned errors: {}", url, messages.join("; "));
+                    return Err(with_kind(wreq_error(), msg, "graphql"));
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// client.post_form(url, fields) or client.post_form(url, fields, opts)
+    ///
+    /// POST with `fields` as an urlencoded `form:` body.
+    fn post_form(&self, args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
+        let (url, fields, opts) = parse_url_payload_and_opts(args)?;
+        let opts = with_option(opts.as_ref(), "form", fields)?;
+        let prepared = self.build_request("POST", &url, Some(&opts))?;
+        self.dispatch(prepared)
+    }
+
+    /// client.post_multipart(url, parts) or client.post_multipart(url, parts, opts)
+    ///
+    /// POST with `parts` as a `multipart:` form body.
+    fn post_multipart(&self, args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
+        let (url, parts, opts) = parse_url_payload_and_opts(args)?;
+        let opts = with_option(opts.as_ref(), "multipart", parts)?;
+        let prepared = self.build_request("POST", &url, Some(&opts))?;
+        self.dispatch(prepared)
+    }
+
+    /// client.upload(url, path, method: :put, content_type: nil, **opts)
+    ///
+    /// Send the file at `path` as a streamed `body_file:`, e.g. to a
+    /// presigned URL. `Content-Type` is `content_type:`, or guessed from
+    /// the extension; a Content-Type in `headers:` still wins.
+    fn upload(&self, args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let (url, path_val, opts) = parse_url_payload_and_opts(args)?;
+        let path: String = TryConvert::try_convert(path_val)?;
+        let opts = with_option(opts.as_ref(), "body_file", path_val)?;
+        let method = match hash_get_value(&opts, "method")? {
+            Some(v) => v.funcall::<_, _, String>("to_s", ())?.to_ascii_uppercase(),
+            None => "PUT".to_owned(),
+        };
+        let content_type = match hash_get_string(&opts, "content_type")? {
+            Some(ct) => ct,
+            None => content_type_for_path(&path).to_owned(),
+        };
+        let headers = ruby.hash_new();
+        let mut has_type = false;
+        if let Some(given) = hash_get_hash(&opts, "headers")? {
+            given.foreach(|k: Value, v: Value| {
+                let name: String = k.funcall("to_s", ())?;
+                has_type |= name.eq_ignore_ascii_case("content-type");
+                headers.aset(k, v)?;
+                Ok(magnus::r_hash::ForEach::Continue)
+            })?;
+        }
+        if !has_type {
+            headers.aset("content-type", content_type)?;
+        }
+        let opts = with_option(Some(&opts), "headers", headers.as_value())?;
+        let prepared = self.build_request(&method, &url, Some(&opts))?;
+        self.dispatch(prepared)
+    }
+
+    /// Send `payload` (if any) as JSON and return the parsed response body.
+    /// Non-2xx statuses raise with the start of the body in the message.
+    fn json_call(
+        &self,
+        method: &str,
+        url: &str,
+        payload: Option<Value>,
+        opts: Option<RHash>,
+    ) -> Result<Value, magnus::Error> {
+        let opts = match payload {
+            Some(payload) => Some(with_option(opts.as_ref(), "json", payload)?),
+            None => opts,
+        };
+        let symbolize = match &opts {
+            Some(o) => hash_get_bool(o, "symbolize_keys")?.unwrap_or(false),
+            None => false,
+        };
+        let resp = self.dispatch(self.build_request(method, url, opts.as_ref())?)?;
+        if !(200..300).contains(&resp.status()) {
+            let msg = format!("{} {} returned {}: {}", method, url, resp.status(), resp.body_excerpt(200));
+            return Err(with_kind(wreq_error(), msg, "status"));
+        }
+        resp.parse_json(symbolize).map_err(|e| {
+            with_cause(decode_error(), format!("{} {} returned a body that is not JSON", method, url), e)
+        })
+    }
+
+    /// client.each_event(url, opts = {}) { |event| ... }
+    ///
+    /// GETs a `text/event-stream` and yields each event as
+    /// `{ event:, data:, id: }`. When the stream ends or drops, reconnects
+    /// after the server's `retry:` interval (else `reconnect_delay:`, 3s)
+    /// with `Last-Event-ID` set, like a browser's EventSource, up to
+    /// `max_reconnects:` times (default: no limit). Stops when the block
+    /// returns `:stop` or raises, on a 204, or on any other failed
+    /// response; returns the last event id.
+    fn each_event(&self, args: &[Value]) -> Result<Option<String>, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        if !ruby.block_given() {
+            return Err(generic_error("each_event requires a block"));
+        }
+        let (url, opts) = parse_url_and_opts(args)?;
+        let opts = with_option(opts.as_ref(), "stream", ruby.qtrue().as_value())?;
+        let max_reconnects = hash_get_usize(&opts, "max_reconnects")?;
+        let reconnect_delay = hash_get_duration(&opts, "reconnect_delay")?.unwrap_or(DEFAULT_SSE_RECONNECT_DELAY);
+        let explicit_accept = match hash_get_hash(&opts, "headers")? {
+            Some(h) => {
+                let names: Vec<Value> = h.funcall("keys", ())?;
+                names.iter().any(|k| {
+                    k.funcall::<_, _, String>("to_s", ()).is_ok_and(|name| name.eq_ignore_ascii_case("accept"))
+                })
+            }
+            None => false,
+        };
+        let mut parser = crate::sse::Parser::default();
+        if let Some(id) = hash_get_string(&opts, "last_event_id")? {
+            parser.set_last_event_id(id);
+        }
+        let mut reconnects = 0;
+        loop {
+            let mut prepared = self.build_request("GET", &url, Some(&opts))?;
+            if !explicit_accept {
+                prepared.req = prepared.req.header(wreq::header::ACCEPT, "text/event-stream");
+            }
+            if let Some(id) = parser.last_event_id() {
+                let value = HeaderValue::from_str(id)
+                    .map_err(|e| invalid_header(format!("invalid Last-Event-ID: '{}'", id), &e))?;
+                prepared.req = prepared.req.header("last-event-id", value);
+            }
+            // Transport failures reconnect; anything else ends the stream.
+            let dropped = match self.read_events(prepared, &mut parser) {
+                Ok(SseEnd::Stopped) => return Ok(parser.last_event_id().map(str::to_owned)),
+                Ok(SseEnd::Eof) => None,
+                Err(e) if is_transport_error(&e) => Some(e),
+                Err(e) => return Err(e),
+            };
+            if max_reconnects.is_some_and(|max| reconnects >= max) {
+                return match dropped {
+                    Some(e) => Err(e),
+                    None => Ok(parser.last_event_id().map(str::to_owned)),
+                };
+            }
+            reconnects += 1;
+            parser.reconnected();
+            let delay = parser.retry_ms().map(Duration::from_millis).unwrap_or(reconnect_delay);
+            self.pause(delay)?;
+        }
+    }
+
+    /// One `each_event` connection: how it ended, or why it failed.
+    fn read_events(&self, prepared: Prepared, parser: &mut crate::sse::Parser) -> Result<SseEnd, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let resp = self.dispatch(prepared)?;
+        if resp.status() == 204 {
+            return Ok(SseEnd::Stopped);
+        }
+        let _: Value = resp.funcall("raise_for_status", ())?;
+        let media_type = resp.header_value("content-type").and_then(|ct| ct.split(';').next()).map(str::trim);
+        if !media_type.is_some_and(|mt| mt.eq_ignore_ascii_case("text/event-stream")) {
+            return Err(magnus::Error::new(
+                decode_error(),
+                format!("expected content type 'text/event-stream', got '{}'", media_type.unwrap_or("none")),
+            ));
+        }
+        let stream = resp.body_stream().expect("each_event requests are streamed");
+        let stop = ruby.to_symbol("stop");
+        while let Some(chunk) = stream.next_chunk()? {
+            for event in parser.feed(&chunk) {
+                let hash = ruby.hash_new();
+                hash.aset(ruby.to_symbol("event"), event.event)?;
+                hash.aset(ruby.to_symbol("data"), event.data)?;
+                hash.aset(ruby.to_symbol("id"), event.id)?;
+                let ret: Value = ruby.yield_value(hash)?;
+                if ret.eql(stop)? {
+                    stream.close();
+                    return Ok(SseEnd::Stopped);
+                }
+            }
+        }
+        Ok(SseEnd::Eof)
+    }
+
+    /// Sleep with the GVL released; a Ruby interrupt or `cancel` cuts it
+    /// short with `Wreq::InterruptedError`.
+    fn pause(&self, delay: Duration) -> Result<(), magnus::Error> {
+        let rt = runtime()?;
+        let client_token = self.cancel_token.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let slept = unsafe {
+            without_gvl(|thread_token| {
+                rt.block_on(async {
+                    tokio::select! {
+                        _ = thread_token.cancelled() => false,
+                        _ = client_token.cancelled() => false,
+                        _ = tokio::time::sleep(delay) => true,
+                    }
+                })
+            })
+        };
+        if slept {
+            Ok(())
+        } else {
+            Err(interrupted())
+        }
+    }
+
+    /// client.download(url, path) or client.download(url, path, opts)
+    ///
+    /// GETs `url` and streams the body straight into the file at `path`
+    /// without holding it in memory. Returns the Response (with an empty
+    /// body) so callers can check the status and headers. A 4xx or 5xx
+    /// leaves `path` untouched; its body is kept in the Response instead.
+    fn download(&self, args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
+        if args.len() < 2 {
+            return Err(generic_error("url and path are required"));
+        }
+        let url: String = TryConvert::try_convert(args[0])?;
+        let path: String = TryConvert::try_convert(args[1])?;
+        let opts: Option<RHash> = match args.get(2) {
+            Some(v) => Some(request_opts(*v)?),
+            None => None,
+        };
+        let buffer_size = match &opts {
+            Some(o) => hash_get_usize(o, "download_buffer_size")?,
+            None => None,
+        }
+        .unwrap_or(self.download_buffer_size);
+        if buffer_size == 0 {
+            return Err(generic_error("download_buffer_size must be positive"));
+        }
+        let prepared = self.build_request("GET", &url, opts.as_ref())?;
+        self.dispatch_into(prepared, BodySink::File { path: path.into(), buffer_size, resume: None })
+    }
+
+    /// client.download_resumable(url, path) or
+    /// client.download_resumable(url, path, opts)
+    ///
+    /// A `download` that continues a partial file. A HEAD checks for
+    /// `Accept-Ranges: bytes`; if the server has it and `path` already holds
+    /// part of the body, a `Range` request fetches the rest and appends it.
+    /// `If-Range` (the HEAD's ETag or Last-Modified) makes a server whose
+    /// copy changed send the whole body instead, which replaces the file, as
+    /// does a server without range support, or one answering 416 (the local
+    /// file is not a prefix of its copy). Returns
+    /// `{ bytes: <file size>, resumed: <appended?> }`; other statuses, and
+    /// a 206 that does not start where the file ends, raise without
+    /// touching the file.
+    fn download_resumable(&self, args: &[Value]) -> Result<RHash, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        if args.len() < 2 {
+            return Err(generic_error("url and path are required"));
+        }
+        let url: String = TryConvert::try_convert(args[0])?;
+        let path = std::path::PathBuf::from(String::try_convert(args[1])?);
+        let opts: Option<RHash> = match args.get(2) {
+            Some(v) => Some(request_opts(*v)?),
+            None => None,
+        };
+        let buffer_size = match &opts {
+            Some(o) => hash_get_usize(o, "download_buffer_size")?,
+            None => None,
+        }
+        .unwrap_or(self.download_buffer_size);
+        if buffer_size == 0 {
+            return Err(generic_error("download_buffer_size must be positive"));
+        }
+        let result = |bytes: u64, resumed: bool| -> Result<RHash, magnus::Error> {
+            let hash = ruby.hash_new();
+            hash.aset(ruby.to_symbol("bytes"), bytes)?;
+            hash.aset(ruby.to_symbol("resumed"), resumed)?;
+            Ok(hash)
+        };
+
+        let offset = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let mut validator = None;
+        let mut resume = false;
+        if offset > 0 {
+            let head = self.dispatch(self.build_request("HEAD", &url, opts.as_ref())?)?;
+            let ranges = head.status() == 200
+                && head
+                    .header_value("accept-ranges")
+                    .is_some_and(|v| v.split(',').any(|unit| unit.trim().eq_ignore_ascii_case("bytes")));
+            if ranges {
+                let total = head.header_value("content-length").and_then(|v| v.trim().parse::<u64>().ok());
+                match total {
+                    Some(total) if total == offset => return result(offset, true),
+                    // A local file longer than the resource is not a prefix of it.
+                    Some(total) if total < offset => {}
+                    _ => resume = true,
+                }
+                // Weak ETags cannot be used with If-Range.
+                validator = head
+                    .header_value("etag")
+                    .filter(|etag| !etag.starts_with("W/"))
+                    .or_else(|| head.header_value("last-modified"))
+                    .map(str::to_owned);
+            }
+        }
+
+        let resp = loop {
+            let mut prepared = self.build_request("GET", &url, opts.as_ref())?;
+            if resume {
+                prepared.req = prepared.req.header("range", format!("bytes={}-", offset));
+                if let Some(validator) = &validator {
+                    let value = HeaderValue::from_str(validator)
+                        .map_err(|e| invalid_header(format!("invalid If-Range value: '{}'", validator), &e))?;
+                    prepared.req = prepared.req.header("if-range", value);
+                }
+            }
+            let start = if resume { offset } else { 0 };
+            let sink = BodySink::File { path: path.clone(), buffer_size, resume: Some(start) };
+            let resp = self.dispatch_into(prepared, sink)?;
+            if resp.status() == 416 && resume {
+                resume = false;
+                continue;
+            }
+            if resp.status() == 206 {
+                let range = resp.header_value("content-range").unwrap_or("");
+                if content_range_start(range) != Some(start) {
+                    return Err(generic_error(format!(
+                        "{} answered with Content-Range '{}', expected one starting at byte {}",
+                        url, range, start
+                    )));
+                }
+            }
+            break resp;
+        };
+        match resp.status() {
+            200 | 206 => {}
+            status if status >= 400 => {
+                let _: Value = resp.funcall("raise_for_status", ())?;
+            }
+            status => return Err(generic_error(format!("unexpected status {} for {}", status, url))),
+        }
+        let bytes = std::fs::metadata(&path)
+            .map(|m| m.len())
+            .map_err(|e| generic_error(format!("failed to save {}: {}", path.display(), e)))?;
+        result(bytes, resp.status() == 206)
+    }
+
+    /// client.download_if_changed(url, path) or
+    /// client.download_if_changed(url, path, etag_path: nil, **opts)
+    ///
+    /// A conditional `download`: sends `If-Modified-Since` with the mtime
+    /// of an existing file at `path` (and `If-None-Match` with the ETag in
+    /// `etag_path`, if given and present). The body goes to a temporary
+    /// file that replaces `path` only on a 200, whose `Last-Modified`
+    /// becomes the file's mtime and whose ETag is saved to `etag_path`.
+    /// Returns `:downloaded`, `:unchanged` for a 304, or `:error` for any
+    /// other status, leaving `path` untouched. Transport errors raise.
+    fn download_if_changed(&self, args: &[Value]) -> Result<Symbol, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        if args.len() < 2 {
+            return Err(generic_error("url and path are required"));
+        }
+        let url: String = TryConvert::try_convert(args[0])?;
+        let path = std::path::PathBuf::from(String::try_convert(args[1])?);
+        let opts: Option<RHash> = match args.get(2) {
+            Some(v) => Some(request_opts(*v)?),
+            None => None,
+        };
+        let etag_path = match &opts {
+            Some(o) => hash_get_string(o, "etag_path")?.map(std::path::PathBuf::from),
+            None => None,
+        };
+
+        let mut prepared = self.build_request("GET", &url, opts.as_ref())?;
+        if let Ok(modified) = std::fs::metadata(&path).and_then(|m| m.modified()) {
+            prepared.req = prepared.req.header("if-modified-since", httpdate::fmt_http_date(modified));
+        }
+        if let Some(etag) = etag_path.as_ref().and_then(|p| std::fs::read_to_string(p).ok()) {
+            let etag = etag.trim();
+            if !etag.is_empty() {
+                let value = HeaderValue::from_str(etag)
+                    .map_err(|e| invalid_header(format!("invalid ETag in etag_path: '{}'", etag), &e))?;
+                prepared.req = prepared.req.header("if-none-match", value);
+            }
+        }
+
+        let mut part = path.clone().into_os_string();
+        part.push(".part");
+        let part = std::path::PathBuf::from(part);
+        let sink = BodySink::File { path: part.clone(), buffer_size: self.download_buffer_size, resume: None };
+        let resp = match self.dispatch_into(prepared, sink) {
+            Ok(resp) => resp,
+            Err(e) => {
+                let _ = std::fs::remove_file(&part);
+                return Err(e);
+            }
+        };
+        if resp.status() != 200 {
+            let _ = std::fs::remove_file(&part);
+            let outcome = if resp.status() == 304 { "unchanged" } else { "error" };
+            return Ok(ruby.to_symbol(outcome));
+        }
+
+        let io_error = |e: std::io::Error| generic_error(format!("failed to save {}: {}", path.display(), e));
+        if let Some(modified) = resp.header_value("last-modified").and_then(|v| httpdate::parse_http_date(v).ok()) {
+            std::fs::File::options().write(true).open(&part).and_then(|f| f.set_modified(modified)).map_err(io_error)?;
+        }
+        std::fs::rename(&part, &path).map_err(io_error)?;
+        if let (Some(etag_path), Some(etag)) = (&etag_path, resp.header_value("etag")) {
+            std::fs::write(etag_path, etag).map_err(io_error)?;
+        }
+        Ok(ruby.to_symbol("downloaded"))
+    }
+
+    /// client.stream_to_io(url, io) or client.stream_to_io(url, io, opts)
+    ///
+    /// GETs `url` and writes the body to `io` chunk by chunk as it arrives,
+    /// returning the number of bytes written. `io` only needs `write`; it is
+    /// never closed or flushed. An interrupt or a failing `write` stops the
+    /// transfer and propagates. A 4xx or 5xx raises `Wreq::HTTPStatusError`
+    /// before anything is written.
+    fn stream_to_io(&self, args: &[Value]) -> Result<u64, magnus::Error> {
+        if args.len() < 2 {
+            return Err(generic_error("url and io are required"));
+        }
+        let url: String = TryConvert::try_convert(args[0])?;
+        let io = args[1];
+        if !io.respond_to("write", false)? {
+            return Err(generic_error("io must respond to #write"));
+        }
+        let opts: Option<RHash> = match args.get(2) {
+            Some(v) => Some(request_opts(*v)?),
+            None => None,
+        };
+        let mut prepared = self.build_request("GET", &url, opts.as_ref())?;
+        prepared.stream = true;
+        let resp = self.dispatch(prepared)?;
+        let stream = resp.body_stream().ok_or_else(|| generic_error("response body is not streaming"))?;
+        let ruby = unsafe { Ruby::get_unchecked() };
+        if resp.status() >= 400 {
+            // Keep the start of the error body for the exception message.
+            let mut excerpt = stream.read(Some(DEFAULT_PEEK_BYTES + 1))?.unwrap_or_default();
+            stream.close();
+            let more = excerpt.len() > DEFAULT_PEEK_BYTES;
+            excerpt.truncate(DEFAULT_PEEK_BYTES);
+            let failed = ruby.obj_wrap(resp.with_prefix(excerpt, more));
+            let _: Value = failed.funcall("raise_for_status", ())?;
+        }
+        let mut written = 0u64;
+        while let Some(chunk) = stream.next_chunk()? {
+            let _: Value = io.funcall("write", (ruby.str_from_slice(&chunk),))?;
+            written += chunk.len() as u64;
+        }
+        Ok(written)
+    }
+
+    /// client.peek(url) or client.peek(url, bytes: 4096, **opts)
+    ///
+    /// GETs `url`, reads at most `bytes` of the body, and drops the
+    /// connection. Returns a response whose body is that prefix;
+    /// `body_truncated?` is true when the server had more to send.
+    fn peek(&self, args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
+        let (url, opts) = parse_url_and_opts(args)?;
+        let limit = match &opts {
+            Some(o) => hash_get_usize(o, "bytes")?.unwrap_or(DEFAULT_PEEK_BYTES),
+            None => DEFAULT_PEEK_BYTES,
+        };
+        let mut prepared = self.build_request("GET", &url, opts.as_ref())?;
+        prepared.stream = true;
+        let resp = self.dispatch(prepared)?;
+        let stream = resp.body_stream().ok_or_else(|| generic_error("response body is not streaming"))?;
+        // One byte past the limit tells a body of exactly `bytes` from a longer one.
+        let mut prefix = stream.read(Some(limit.saturating_add(1)))?.unwrap_or_default();
+        stream.close();
+        let more = prefix.len() > limit;
+        prefix.truncate(limit);
+        let ruby = unsafe { Ruby::get_unchecked() };
+        Ok(ruby.obj_wrap(resp.with_prefix(prefix, more)))
+    }
+
+    /// client.exists?(url) or client.exists?(url, opts)
+    ///
+    /// Issues a HEAD (falling back to a one-byte ranged GET when the server
+    /// answers 405) and returns true for a 2xx status. Connection, DNS, TLS,
+    /// and timeout errors return false unless `raise: true` is passed; any
+    /// other error raises.
+    fn exists(&self, args: &[Value]) -> Result<bool, magnus::Error> {
+        let (url, opts) = parse_url_and_opts(args)?;
+        let raise = match &opts {
+            Some(o) => hash_get_bool(o, "raise")?.unwrap_or(false),
+            None => false,
+        };
+        let result = self.build_request("HEAD", &url, opts.as_ref()).and_then(|p| self.dispatch(p));
+        let result = match result {
+            Ok(resp) if resp.status() == 405 => self
+                .build_request("GET", &url, opts.as_ref())
+                .and_then(|mut p| {
+                    p.req = p.req.header("range", "bytes=0-0");
+                    // A server that ignores Range would send the whole body.
+                    p.stream = true;
+                    self.dispatch(p)
+                })
+                .inspect(|resp| {
+                    if let Some(stream) = resp.body_stream() {
+                        stream.close();
+                    }
+                }),
+            other => other,
+        };
+        match result {
+            Ok(resp) => Ok((200..300).contains(&resp.status())),
+            Err(e) if !raise && is_connection_error(&e) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// client.head_size(url) or client.head_size(url, opts)
+    ///
+    /// Sends HEAD and returns the `Content-Length` of a 2xx response, or
+    /// nil when the header is missing or the status is not 2xx. The header
+    /// is read directly, since a HEAD response's own body is always empty.
+    /// Connection errors raise.
+    fn head_size(&self, args: &[Value]) -> Result<Option<u64>, magnus::Error> {
+        let resp = self.execute_method("HEAD", args)?;
+        if !(200..300).contains(&resp.status()) {
+            return Ok(None);
+        }
+        Ok(resp.header_value("content-length").and_then(|v| v.trim().parse().ok()))
+    }
+
+    /// client.healthy?(url) or client.healthy?(url, timeout: 1)
+    ///
+    /// Reachability probe: sends a HEAD with a short timeout (2s unless
+    /// `timeout:` is given). Any HTTP response, including 4xx/5xx, counts
+    /// as healthy; only connection, TLS, or timeout failures return false.
+    fn healthy(&self, args: &[Value]) -> Result<bool, magnus::Error> {
+        let (url, opts) = parse_url_and_opts(args)?;
+        let timeout = match &opts {
+            Some(o) => hash_get_duration(o, "timeout")?,
+            None => None,
+        }
+        .unwrap_or(HEALTH_CHECK_TIMEOUT);
+        let mut prepared = self.build_request("HEAD", &url, opts.as_ref())?;
+        prepared.req = prepared.req.timeout(timeout);
+        match self.dispatch(prepared) {
+            Ok(_) => Ok(true),
+            Err(e) if is_connection_error(&e) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Load a Netscape cookie file into `jar`. A missing file is treated as an
+/// empty jar and malformed lines are skipped.
+fn load_cookie_file(jar: &CookieJar, path: &str) -> Result<(), magnus::Error> {
+    let data = match std::fs::read(path) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(generic_error(format!("cannot read cookies from '{}': {}", path, e))),
+    };
+    for cookie in cookie_jar::parse_netscape(&data) {
+        // A single bad entry should not prevent the rest of the session loading.
+        let _ = jar.insert(&cookie);
+    }
+    Ok(())
+}
+
+/// Size of a request's body: the buffered length, or the declared
+/// `Content-Length` for streamed bodies. Zero for bodyless requests.
+fn request_body_size(request: &wreq::Request) -> u64 {
+    match request.body() {
+        None => 0,
+        Some(body) => match body.as_bytes() {
+            Some(bytes) => bytes.len() as u64,
+            None => request
+                .headers()
+                .get("content-length")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+        },
+    }
+}
+
+/// Per-request `max_body_size:` / `truncate_body:` layered over the client's
+/// limit. `max_body_size: nil` leaves the client's limit in place.
+fn request_body_limit(
+    limit: Option<BodyLimit>,
+    opts: &RHash,
+) -> Result<Option<BodyLimit>, magnus::Error> {
+    let max = hash_get_usize(opts, "max_body_size")?.or(limit.map(|l| l.max));
+    let truncate = match hash_get_bool(opts, "truncate_body")? {
+        Some(t) => t,
+        None => limit.is_some_and(|l| l.truncate),
+    };
+    Ok(max.map(|max| BodyLimit { max, truncate }))
+}
+
+/// `Thread.current` key of the `with_timeout` scopes, innermost last, as
+/// `[scope_id, nanoseconds]` pairs. `Thread#[]` is fiber-local, so fibers
+/// sharing a thread (Enumerators, fiber schedulers) keep separate scopes.
+const SCOPED_TIMEOUTS_KEY: &str = "__wreq_scoped_timeouts";
+
+/// The current fiber's scope stack, created on first use.
+fn scoped_timeouts() -> Result<RArray, magnus::Error> {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    let current: Value = ruby.class_thread().funcall("current", ())?;
+    let key = ruby.to_symbol(SCOPED_TIMEOUTS_KEY);
+    if let Some(stack) = RArray::from_value(current.funcall("[]", (key,))?) {
+        return Ok(stack);
+    }
+    let stack = ruby.ary_new();
+    let _: Value = current.funcall("[]=", (key, stack))?;
+    Ok(stack)
+}
+
+/// Split `(url, payload, opts = nil)` method arguments.
+fn parse_url_payload_and_opts(args: &[Value]) -> Result<(String, Value, Option<RHash>), magnus::Error> {
+    if args.len() < 2 {
+        return Err(generic_error("url and payload are required"));
+    }
+    let url: String = TryConvert::try_convert(args[0])?;
+    let opts: Option<RHash> = match args.get(2) {
+        Some(v) => Some(request_opts(*v)?),
+        None => None,
+    };
+    Ok((url, args[1], opts))
+}
+
+/// A copy of `opts` (or a new Hash) with `key` set to `value`, for helpers
+/// that fill in a body option. The caller's Hash is left untouched.
+fn with_option(opts: Option<&RHash>, key: &str, value: Value) -> Result<RHash, magnus::Error> {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    let merged: RHash = match opts {
+        Some(o) => o.funcall("dup", ())?,
+        None => ruby.hash_new(),
+    };
+    // Drop a String spelling, which option lookups would otherwise prefer.
+    let _: Value = merged.delete(key)?;
+    merged.aset(ruby.to_symbol(key), value)?;
+    Ok(merged)
+}
+
+/// Default timeout for `Client#healthy?` probes.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Split `(url, opts = nil)` method arguments.
+fn parse_url_and_opts(args: &[Value]) -> Result<(String, Option<RHash>), magnus::Error> {
+    let url: String = if args.is_empty() {
+        return Err(generic_error("url is required"));
+    } else {
+        TryConvert::try_convert(args[0])?
+    };
+
+    let opts: Option<RHash> = if args.len() > 1 {
+        Some(request_opts(args[1])?)
+    } else {
+        None
+    };
+    Ok((url, opts))
+}
+
+fn apply_request_options(
+    client: &Client,
+    mut req: wreq::RequestBuilder,
+    opts: &RHash,
+    producer: &mut Option<ChunkProducer>,
+) -> Result<wreq::RequestBuilder, magnus::Error> {
+    // Set before `headers:` so an explicit Referer header replaces it.
+    if let Some(referer) = hash_get_string(opts, "referer")? {
+        let value = HeaderValue::from_str(&referer)
+            .map_err(|e| invalid_header(format!("invalid referer: '{}'", referer), &e))?;
+        req = req.header(wreq::header::REFERER, value);
+    }
+    // Also before `headers:`; replaces the client's or emulation's value.
+    if let Some(lang) = accept_language_option(opts)? {
+        req = req.header(wreq::header::ACCEPT_LANGUAGE, lang);
+    }
+
+    if let Some(hdr_hash) = hash_get_hash(opts, "headers")? {
+        let hmap = hash_to_header_map(&hdr_hash)?;
+        req = req.headers(hmap);
+    }
+
+    // `body_enum:` streams whatever an `each` yields, like an Enumerator
+    // `body:`, but a failure mid-stream surfaces as `Wreq::Error`.
+    if let Some(source) = hash_get_value(opts, "body_enum")? {
+        if hash_get_value(opts, "body")?.is_some() {
+            return Err(generic_error("body_enum: cannot be combined with body:"));
+        }
+        if !source.respond_to("each", false)? {
+            return Err(generic_error("body_enum: must respond to each"));
+        }
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let enumerator: Value = source.funcall("to_enum", (ruby.to_symbol("each"),))?;
+        let (mut chunks, body) = ChunkProducer::new(enumerator);
+        chunks = chunks.wrapping_errors();
+        let content_length = hash_get_u64(opts, "content_length")?;
+        if let Some(len) = content_length {
+            req = req.header(wreq::header::CONTENT_LENGTH, len);
+            chunks = chunks.with_declared_length(len);
+        }
+        *producer = Some(chunks);
+        req = req.body(body);
+    }
+
+    // `body_file:` streams a file from disk with its size as Content-Length.
+    if let Some(path) = hash_get_string(opts, "body_file")? {
+        if hash_get_value(opts, "body")?.is_some() || hash_get_value(opts, "body_enum")?.is_some() {
+            return Err(generic_error("body_file: cannot be combined with body: or body_enum:"));
+        }
+        let (body, len) = file_body(&path)?;
+        req = req.header(wreq::header::CONTENT_LENGTH, len).body(body);
+    }
+
+    if let Some(mut body_val) = hash_get_value(opts, "body")? {
+        // A callable body is materialized now, just before the request is
+        // sent, so signatures and nonces are computed at send time.
+        let ruby = unsafe { Ruby::get_unchecked() };
+        if let Some(blk) = magnus::block::Proc::from_value(body_val).filter(|p| p.arity() == 1) {
+            // A one-argument block is a producer: `proc { |out| out << chunk }`.
+            body_val = ruby.class_enumerator().funcall_with_block("new", (), blk)?;
+        } else if body_val.is_kind_of(ruby.class_proc()) || body_val.is_kind_of(ruby.class_method()) {
+            body_val = body_val.funcall("call", ())?;
+        }
+        let content_length = hash_get_u64(opts, "content_length")?;
+        if let Some(len) = content_length {
+            req = req.header(wreq::header::CONTENT_LENGTH, len);
+        }
+        if body_val.is_kind_of(ruby.class_enumerator()) {
+            // Chunks are pulled from Ruby while the request is in flight. The
+            // body is sent chunked unless its length was given up front.
+            let (mut chunks, body) = ChunkProducer::new(body_val);
+            if let Some(len) = content_length {
+                chunks = chunks.with_declared_length(len);
+            }
+            *producer = Some(chunks);
+            req = req.body(body);
+        } else {
+            let string = RString::try_convert(body_val)?;
+            if let Some(len) = content_length.filter(|&len| len != string.len() as u64) {
+                return Err(generic_error(format!(
+                    "body is {} bytes but content_length is {}",
+                    string.len(),
+                    len
+                )));
+            }
+            let threshold = match hash_get_usize(opts, "stream_body_over")? {
+                Some(n) => n,
+                None => client.stream_body_over,
+            };
+            if string.len() <= threshold {
+                req = req.body(rstring_to_bytes(string));
+            } else {
+                // Sliced from the String as the connection takes it, so the
+                // body is never copied whole.
+                let (chunks, body) = ChunkProducer::from_string(string, STREAM_CHUNK_SIZE)?;
+                *producer = Some(chunks);
+                if content_length.is_none() {
+                    req = req.header(wreq::header::CONTENT_LENGTH, string.len());
+                }
+                req = req.body(body);
+            }
+        }
+    } else if hash_get_value(opts, "content_length")?.is_some() && hash_get_value(opts, "body_enum")?.is_none() {
+        return Err(generic_error("content_length requires body: or body_enum:"));
+    }
+
+    if let Some(json_val) = hash_get_value(opts, "json")? {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let json_module: Value = ruby.class_object().const_get("JSON")?;
+        let json_str: String = json_module
+            .funcall("generate", (json_val,))
+            .map_err(|e| with_cause(decode_error(), "json: payload cannot be encoded as JSON", e))?;
+        req = req
+            .header("content-type", "application/json")
+            .body(json_str);
+    }
+
+    let nested = hash_get_bool(opts, "nested")?.unwrap_or(false);
+
+    if let Some(form_hash) = hash_get_hash(opts, "form")? {
+        let pairs = hash_to_pairs(&form_hash, nested)?;
+        req = req.form(&pairs);
+    }
+
+    if let Some(mp_hash) = hash_get_hash(opts, "multipart")? {
+        let form = hash_to_multipart_form(&mp_hash)?;
+        req = req.multipart(form);
+    }
+
+    // String and `raw_query: true` queries are already in the URL.
+    if let Some(query) = hash_get_value(opts, "query")? {
+        if RString::from_value(query).is_none() && !hash_get_bool(opts, "raw_query")?.unwrap_or(false) {
+            let pairs = hash_to_pairs(&RHash::try_convert(query)?, nested)?;
+            req = req.query(&pairs);
+        }
+    }
+
+    if let Some(n) = hash_get_usize(opts, "max_redirects")? {
+        req = req.redirect(redirect_limit(n, client.pins.is_some()));
+    }
+
+    if let Some(t) = hash_get_duration(opts, "timeout")? {
+        req = req.timeout(t);
+    }
+
+    if let Some(token) = hash_get_string(opts, "auth")? {
+        req = req.auth(token);
+    }
+
+    if let Some(token) = hash_get_string(opts, "bearer")? {
+        req = req.bearer_auth(token);
+    }
+
+    if let Some(basic_val) = hash_get_value(opts, "basic")? {
+        let ary = RArray::try_convert(basic_val)?;
+        if ary.len() >= 2 {
+            let user: String = TryConvert::try_convert(ary.entry::<Value>(0)?)?;
+            let pass: String = TryConvert::try_convert(ary.entry::<Value>(1)?)?;
+            req = req.basic_auth(user, Some(pass));
+        }
+    }
+
+    if let Some(proxy_url) = hash_get_string(opts, "proxy")? {
+        let proxy = build_proxy(&proxy_url, None, None)?;
+        req = req.proxy(proxy);
+    }
+
+    if let Some(v) = hash_get_value(opts, "sni_hostname")? {
+        return Err(sni_hostname_error(v));
+    }
+
+    if let Some(val) = hash_get_value(opts, "emulation")? {
         let ruby = unsafe { Ruby::get_unchecked() };
         if val.is_kind_of(ruby.class_false_class()) {
             // emulation: false — no per-request emulation override
         } else if val.is_kind_of(ruby.class_true_class()) {
-            let opt = build_emulation_option(DEFAULT_EMULATION, opts)?;
+            let opt = build_emulation_option(default_emulation(), opts)?;
             req = req.emulation(opt);
         } else {
             let name: String = TryConvert::try_convert(val)?;
@@ -517,45 +2800,271 @@ fn apply_request_options(
     Ok(req)
 }
 
+/// A pseudo-random index below `len`, good enough to spread requests over
+/// proxies; `seed` keeps calls in the same instant apart.
+fn random_index(seed: usize, len: usize) -> usize {
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_usize(seed);
+    (hasher.finish() % len as u64) as usize
+}
+
+/// `sni_hostname:` would override the name sent as SNI, but wreq always
+/// takes it from the URL host and offers no hook to send a different one,
+/// so the option raises rather than being silently ignored. Whether SNI is
+/// sent at all is `tls_sni:`.
+fn sni_hostname_error(value: Value) -> magnus::Error {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    if !value.is_kind_of(ruby.class_string()) {
+        return generic_error("sni_hostname: takes a host name; use tls_sni: false to omit SNI");
+    }
+    generic_error(format!(
+        "sni_hostname: {} is not supported: wreq sends the URL host as SNI and cannot send a different name \
+         (use tls_sni: false to omit SNI)",
+        value.inspect()
+    ))
+}
+
+/// Follow at most `max` redirects, then fail with `TooManyRedirects`, so
+/// an exceeded limit can be told apart from other redirect errors by type.
+/// With `same_origin`, a redirect to another origin fails with
+/// `CrossOriginRedirect` instead of being followed.
+fn redirect_limit(max: usize, same_origin: bool) -> wreq::redirect::Policy {
+    wreq::redirect::Policy::custom(move |attempt| {
+        if same_origin {
+            if let Some(from) = attempt.previous().last() {
+                let to = attempt.uri();
+                if from.scheme_str() != to.scheme_str() || pool::origin(from) != pool::origin(to) {
+                    let err = CrossOriginRedirect { from: from.to_string(), to: to.to_string() };
+                    return attempt.error(err);
+                }
+            }
+        }
+        // `previous` starts with the original URL, which is not a redirect.
+        if attempt.previous().len() > max {
+            attempt.error(TooManyRedirects(max))
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+/// Build a proxy for `url`, branching on its scheme:
+/// - `http`/`https` (or no scheme): credentials go in Proxy-Authorization;
+/// - `socks5`/`socks5h`: credentials are passed in the SOCKS5 handshake;
+/// - `socks4`/`socks4a`: no password authentication exists.
+fn build_proxy(url: &str, user: Option<String>, pass: Option<String>) -> Result<wreq::Proxy, magnus::Error> {
+    let scheme = url.split_once("://").map(|(s, _)| s.to_ascii_lowercase());
+    let proxy = match scheme.as_deref() {
+        None | Some("http") | Some("https") => {
+            let proxy = wreq::Proxy::all(url).map_err(to_magnus_error)?;
+            // Passed as-is to Proxy-Authorization, never through the URL, so
+            // `@`, `:`, and non-ASCII characters need no escaping. Either half
+            // may be given alone; the other is then empty.
+            match (user, pass) {
+                (None, None) => proxy,
+                (user, pass) => proxy.basic_auth(&user.unwrap_or_default(), &pass.unwrap_or_default()),
+            }
+        }
+        Some("socks5") | Some("socks5h") => {
+            let url = match (user, pass) {
+                (None, None) => url.to_owned(),
+                (user, pass) => with_userinfo(url, &user.unwrap_or_default(), &pass.unwrap_or_default()),
+            };
+            wreq::Proxy::all(&url).map_err(to_magnus_error)?
+        }
+        Some(scheme @ ("socks4" | "socks4a")) => {
+            if pass.is_some() {
+                return Err(generic_error(format!("{} proxies do not support password authentication", scheme)));
+            }
+            wreq::Proxy::all(url).map_err(to_magnus_error)?
+        }
+        Some(other) => {
+            return Err(generic_error(format!(
+                "unsupported proxy scheme '{}' (expected http, https, socks4, socks4a, socks5, or socks5h)",
+                other
+            )))
+        }
+    };
+    Ok(proxy)
+}
+
+/// Put percent-encoded credentials into a proxy URL, unless it already
+/// carries its own.
+fn with_userinfo(url: &str, user: &str, pass: &str) -> String {
+    use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_owned();
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or("");
+    if authority.contains('@') {
+        return url.to_owned();
+    }
+    format!(
+        "{}://{}:{}@{}",
+        scheme,
+        utf8_percent_encode(user, NON_ALPHANUMERIC),
+        utf8_percent_encode(pass, NON_ALPHANUMERIC),
+        rest
+    )
+}
+
 // --------------------------------------------------------------------------
 // Module-level convenience methods
 // --------------------------------------------------------------------------
 
-fn wreq_get(args: &[Value]) -> Result<Response, magnus::Error> {
+/// Run a module-level request on the `client:` given in opts, or on a
+/// throwaway client when none is passed.
+fn module_request(method: &str, args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
+    if let Some(opts) = args.get(1).and_then(|v| request_opts(*v).ok()) {
+        if let Some(val) = hash_get_value(&opts, "client")? {
+            let client: &Client = TryConvert::try_convert(val)?;
+            return client.execute_method(method, args);
+        }
+    }
     let client = Client::rb_new(&[])?;
-    client.execute_method("GET", args)
+    client.execute_method(method, args)
 }
 
-fn wreq_post(args: &[Value]) -> Result<Response, magnus::Error> {
-    let client = Client::rb_new(&[])?;
-    client.execute_method("POST", args)
+/// Options of `Wreq.benchmark` itself, removed before the rest are used as
+/// per-request options.
+const BENCHMARK_KEYS: &[&str] = &["count", "concurrency", "client", "method"];
+
+/// Wreq.benchmark(url, count: 100, concurrency: 10, **opts)
+///
+/// Sends `count` requests to `url` with up to `concurrency` in flight,
+/// all on one client (the `client:` option, or a new one) and with the GVL
+/// released throughout, and returns a summary Hash. The remaining options
+/// are per-request options; `method:` picks the HTTP method (GET).
+fn wreq_benchmark(args: &[Value]) -> Result<RHash, magnus::Error> {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    let (url, opts) = parse_url_and_opts(args)?;
+    let opts = opts.unwrap_or_else(|| ruby.hash_new());
+    let request_opts: RHash = opts.funcall("dup", ())?;
+    for key in BENCHMARK_KEYS {
+        let _: Value = request_opts.funcall("delete", (*key,))?;
+        let _: Value = request_opts.funcall("delete", (ruby.to_symbol(key),))?;
+    }
+    let count = hash_get_usize(&opts, "count")?.unwrap_or(DEFAULT_BENCHMARK_COUNT);
+    let concurrency = hash_get_usize(&opts, "concurrency")?.unwrap_or(DEFAULT_BENCHMARK_CONCURRENCY);
+    if count == 0 || concurrency == 0 {
+        return Err(generic_error("benchmark count: and concurrency: must be positive"));
+    }
+    let method = match hash_get_value(&opts, "method")? {
+        Some(v) => v.funcall::<_, _, String>("to_s", ())?.to_ascii_uppercase(),
+        None => "GET".to_owned(),
+    };
+    let owned;
+    let client: &Client = match hash_get_value(&opts, "client")? {
+        Some(val) => TryConvert::try_convert(val)?,
+        None => {
+            owned = Client::rb_new(&[])?;
+            &owned
+        }
+    };
+
+    // Build the request once, while Ruby values (bodies, headers) can
+    // still be read, and send a copy of it each time. Each goes through the
+    // same fetch as a single request; only its `total_timeout:` starts
+    // when it is sent.
+    let Prepared { req, producer, body_limit, deadline, allow_incomplete, extensions, .. } =
+        client.build_request(&method, &url, Some(&request_opts))?;
+    if producer.is_some() {
+        return Err(generic_error("benchmark does not support streaming (Enumerator) bodies"));
+    }
+    let (http, request) = req.build_split();
+    let mut request = request.map_err(to_magnus_error)?;
+    if let Some(extensions) = extensions {
+        request.extensions_mut().insert(extensions);
+    }
+    if request.try_clone().is_none() {
+        return Err(generic_error("benchmark needs a body that can be sent again (not a stream or file)"));
+    }
+    let budget = deadline.map(|d| d.budget);
+    let requests = (0..count).map(|_| {
+        // Checked above: a request that cloned once always clones.
+        let fetch = request
+            .try_clone()
+            .map(|request| client.fetch(&http, request, BodySink::Buffer, body_limit, allow_incomplete));
+        async move {
+            let fetch = fetch?;
+            let result = match budget {
+                Some(budget) => tokio::time::timeout(budget, fetch).await.ok()?,
+                None => fetch.await,
+            };
+            result.ok().map(|data| data.status)
+        }
+    });
+
+    let rt = runtime()?;
+    let client_token = client.cancel_token.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let summary = unsafe {
+        without_gvl(|thread_token| {
+            rt.block_on(async {
+                tokio::select! {
+                    biased;
+                    _ = thread_token.cancelled() => None,
+                    _ = client_token.cancelled() => None,
+                    summary = bench::run(requests, concurrency) => Some(summary),
+                }
+            })
+        })
+    };
+    match summary {
+        Some(summary) => summary.to_hash(&ruby),
+        None => Err(interrupted()),
+    }
 }
 
-fn wreq_put(args: &[Value]) -> Result<Response, magnus::Error> {
-    let client = Client::rb_new(&[])?;
-    client.execute_method("PUT", args)
+/// Wreq.default_emulation => "chrome_145"
+fn wreq_default_emulation() -> String {
+    emulation_name(default_emulation())
 }
 
-fn wreq_patch(args: &[Value]) -> Result<Response, magnus::Error> {
-    let client = Client::rb_new(&[])?;
-    client.execute_method("PATCH", args)
+/// Wreq.default_emulation = "firefox_146" (nil restores the built-in default)
+///
+/// Changes the emulation used by clients built afterwards without an
+/// `emulation:` option. Existing clients keep theirs.
+fn wreq_set_default_emulation(val: Value) -> Result<Value, magnus::Error> {
+    let emu = if val.is_nil() {
+        None
+    } else {
+        let name: String = val.funcall("to_s", ())?;
+        Some(parse_emulation(&name)?)
+    };
+    *DEFAULT_EMULATION_OVERRIDE.lock().unwrap_or_else(|e| e.into_inner()) = emu;
+    Ok(val)
 }
 
-fn wreq_delete(args: &[Value]) -> Result<Response, magnus::Error> {
-    let client = Client::rb_new(&[])?;
-    client.execute_method("DELETE", args)
+fn wreq_get(args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
+    module_request("GET", args)
 }
 
-fn wreq_head(args: &[Value]) -> Result<Response, magnus::Error> {
-    let client = Client::rb_new(&[])?;
-    client.execute_method("HEAD", args)
+fn wreq_post(args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
+    module_request("POST", args)
+}
+
+fn wreq_put(args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
+    module_request("PUT", args)
+}
+
+fn wreq_patch(args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
+    module_request("PATCH", args)
+}
+
+fn wreq_delete(args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
+    module_request("DELETE", args)
+}
+
+fn wreq_head(args: &[Value]) -> Result<Obj<Response>, magnus::Error> {
+    module_request("HEAD", args)
 }
 
 // --------------------------------------------------------------------------
 // Hash helpers
 // --------------------------------------------------------------------------
 
-fn hash_get_value(hash: &RHash, key: &str) -> Result<Option<Value>, magnus::Error> {
+pub(crate) fn hash_get_value(hash: &RHash, key: &str) -> Result<Option<Value>, magnus::Error> {
     // Try string key
     let val: Value = hash.aref(key)?;
     if !val.is_nil() {
@@ -571,21 +3080,70 @@ fn hash_get_value(hash: &RHash, key: &str) -> Result<Option<Value>, magnus::Erro
     Ok(None)
 }
 
-fn hash_get_string(hash: &RHash, key: &str) -> Result<Option<String>, magnus::Error> {
+pub(crate) fn hash_get_string(hash: &RHash, key: &str) -> Result<Option<String>, magnus::Error> {
     match hash_get_value(hash, key)? {
         Some(v) => Ok(Some(TryConvert::try_convert(v)?)),
         None => Ok(None),
     }
 }
 
-fn hash_get_float(hash: &RHash, key: &str) -> Result<Option<f64>, magnus::Error> {
+/// Read a time option: Integer or Float seconds, or a String with a unit
+/// ("500ms", "30s", "5m", "1h"). Zero or negative values raise.
+pub(crate) fn hash_get_duration(hash: &RHash, key: &str) -> Result<Option<Duration>, magnus::Error> {
     match hash_get_value(hash, key)? {
-        Some(v) => Ok(Some(TryConvert::try_convert(v)?)),
+        Some(v) => Ok(Some(positive_duration(v, key)?)),
         None => Ok(None),
     }
 }
 
-fn hash_get_bool(hash: &RHash, key: &str) -> Result<Option<bool>, magnus::Error> {
+fn positive_duration(v: Value, key: &str) -> Result<Duration, magnus::Error> {
+    let d = value_to_duration(v, key)?;
+    if d.is_zero() {
+        return Err(generic_error(format!("{} must be a positive duration", key)));
+    }
+    Ok(d)
+}
+
+/// Convert seconds or a duration String to a Duration; zero is allowed.
+fn value_to_duration(v: Value, key: &str) -> Result<Duration, magnus::Error> {
+    let seconds = match RString::from_value(v) {
+        Some(s) => {
+            let text = s.to_string()?;
+            parse_duration_str(&text)
+                .ok_or_else(|| generic_error(format!("invalid {}: '{}' (expected e.g. 30, 1.5, \"500ms\", \"30s\")", key, text)))?
+        }
+        None => {
+            let ruby = unsafe { Ruby::get_unchecked() };
+            if !v.is_kind_of(ruby.class_numeric()) {
+                return Err(generic_error(format!("{} must be a number of seconds or a duration String", key)));
+            }
+            TryConvert::try_convert(v)?
+        }
+    };
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(generic_error(format!("{} must be a positive duration", key)));
+    }
+    Duration::try_from_secs_f64(seconds).map_err(|_| generic_error(format!("{} is too large", key)))
+}
+
+/// Seconds in a "<number><unit>" String, where the unit is ms, s, m, or h
+/// (a bare number is seconds).
+fn parse_duration_str(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let split = text.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let n: f64 = number.trim().parse().ok()?;
+    let scale = match unit.trim() {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return None,
+    };
+    Some(n * scale)
+}
+
+pub(crate) fn hash_get_bool(hash: &RHash, key: &str) -> Result<Option<bool>, magnus::Error> {
     match hash_get_value(hash, key)? {
         Some(v) => Ok(Some(TryConvert::try_convert(v)?)),
         None => Ok(None),
@@ -606,6 +3164,13 @@ fn hash_get_u32(hash: &RHash, key: &str) -> Result<Option<u32>, magnus::Error> {
     }
 }
 
+fn hash_get_u64(hash: &RHash, key: &str) -> Result<Option<u64>, magnus::Error> {
+    match hash_get_value(hash, key)? {
+        Some(v) => Ok(Some(TryConvert::try_convert(v)?)),
+        None => Ok(None),
+    }
+}
+
 fn parse_tls_version(s: &str) -> Result<TlsVersion, magnus::Error> {
     match s {
         "tls1.0" | "tls_1_0" | "1.0" => Ok(TlsVersion::TLS_1_0),
@@ -659,17 +3224,167 @@ fn hash_to_header_map(hash: &RHash) -> Result<HeaderMap, magnus::Error> {
             TryConvert::try_convert(k)?
         };
         let vs: String = v.funcall("to_s", ())?;
-        let name =
-            HeaderName::from_bytes(ks.as_bytes()).map_err(|e| generic_error(e))?;
-        let value = HeaderValue::from_str(&vs).map_err(|e| generic_error(e))?;
+        let name = HeaderName::from_bytes(ks.as_bytes())
+            .map_err(|e| invalid_header(format!("invalid header name: '{}'", ks), &e))?;
+        let value = HeaderValue::from_str(&vs)
+            .map_err(|e| invalid_header(format!("invalid value for header '{}'", ks), &e))?;
         hmap.insert(name, value);
         Ok(magnus::r_hash::ForEach::Continue)
     })?;
     Ok(hmap)
 }
 
-fn hash_to_pairs(hash: &RHash) -> Result<Vec<(String, String)>, magnus::Error> {
+/// Resolve a request URL against `base_url:`. Absolute URLs are used as
+/// given and other references are joined per RFC 3986, except that the
+/// base path always acts as a directory (`/v1` + `users` is `/v1/users`)
+/// and a query-only reference (`?page=2`) is appended to the base's query.
+/// Fragments are never sent.
+fn resolve_url(base: &Url, input: &str) -> Result<String, String> {
+    let mut url = if let Some(query) = input.strip_prefix('?') {
+        let query = query.split('#').next().unwrap_or("");
+        let merged = match base.query() {
+            Some(q) if !q.is_empty() && !query.is_empty() => format!("{}&{}", q, query),
+            Some(q) if query.is_empty() => q.to_owned(),
+            _ => query.to_owned(),
+        };
+        let mut url = base.clone();
+        url.set_query((!merged.is_empty()).then_some(merged.as_str()));
+        url
+    } else {
+        let mut dir = base.clone();
+        if !dir.path().ends_with('/') {
+            let path = format!("{}/", dir.path());
+            dir.set_path(&path);
+        }
+        dir.join(input).map_err(|e| format!("invalid url '{}': {}", input, e))?
+    };
+    url.set_fragment(None);
+    Ok(url.to_string())
+}
+
+/// `input` with `scheme` prepended when it has none (`example.com/x`,
+/// `localhost:8080`, or a scheme-relative `//example.com`).
+fn with_default_scheme(input: &str, scheme: &str) -> String {
+    if input.contains("://") {
+        input.to_owned()
+    } else if let Some(rest) = input.strip_prefix("//") {
+        format!("{}://{}", scheme, rest)
+    } else {
+        format!("{}://{}", scheme, input)
+    }
+}
+
+/// Apply per-request `gzip:`/`brotli:`/`deflate:`/`zstd:` flags, which
+/// override the client's. Clients that decode after download keep wreq's
+/// decoders off and take the flags into `manual` instead.
+fn apply_request_decoders(
+    mut req: wreq::RequestBuilder,
+    opts: &RHash,
+    manual: Option<Decoders>,
+) -> Result<(wreq::RequestBuilder, Option<Decoders>), magnus::Error> {
+    let gzip = hash_get_bool(opts, "gzip")?;
+    let brotli = hash_get_bool(opts, "brotli")?;
+    let deflate = hash_get_bool(opts, "deflate")?;
+    let zstd = hash_get_bool(opts, "zstd")?;
+    if let Some(mut d) = manual {
+        d.gzip = gzip.unwrap_or(d.gzip);
+        d.brotli = brotli.unwrap_or(d.brotli);
+        d.deflate = deflate.unwrap_or(d.deflate);
+        d.zstd = zstd.unwrap_or(d.zstd);
+        return Ok((req, Some(d)));
+    }
+    if let Some(v) = gzip {
+        req = req.gzip(v);
+    }
+    if let Some(v) = brotli {
+        req = req.brotli(v);
+    }
+    if let Some(v) = deflate {
+        req = req.deflate(v);
+    }
+    if let Some(v) = zstd {
+        req = req.zstd(v);
+    }
+    Ok((req, None))
+}
+
+/// The `query:` to append to the URL as written: a String query (a leading
+/// `?` dropped), or a Hash with `raw_query: true` joined as `k=v&...`
+/// without percent-encoding. None when the query is to be encoded.
+fn raw_query(opts: &RHash) -> Result<Option<String>, magnus::Error> {
+    let Some(query) = hash_get_value(opts, "query")? else {
+        return Ok(None);
+    };
+    if let Some(s) = RString::from_value(query) {
+        return Ok(Some(s.to_string()?.trim_start_matches('?').to_owned()));
+    }
+    if !hash_get_bool(opts, "raw_query")?.unwrap_or(false) {
+        return Ok(None);
+    }
+    let nested = hash_get_bool(opts, "nested")?.unwrap_or(false);
+    let pairs = hash_to_pairs(&RHash::try_convert(query)?, nested)?;
+    Ok(Some(pairs.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&")))
+}
+
+/// True for `Wreq::Error`s raised by the transport (they carry an
+/// `error_kind`), other than cancellation and status errors.
+fn is_transport_error(e: &magnus::Error) -> bool {
+    if !e.is_kind_of(wreq_error()) || e.is_kind_of(interrupted_error()) {
+        return false;
+    }
+    let Some(exc) = e.value() else { return false };
+    let ruby = unsafe { Ruby::get_unchecked() };
+    match exc.funcall::<_, _, Value>("error_kind", ()) {
+        Ok(kind) => !kind.is_nil() && !kind.eql(ruby.to_symbol("status")).unwrap_or(false),
+        Err(_) => false,
+    }
+}
+
+/// True for failures to reach the server at all: connection, DNS, TLS,
+/// and timeout errors. Everything else (bad options, cancellation, an
+/// open circuit, hook exceptions) is a caller problem, not a host one.
+fn is_connection_error(e: &magnus::Error) -> bool {
+    if e.is_kind_of(timeout_error()) || e.is_kind_of(tls_error()) {
+        return true;
+    }
+    if !e.is_kind_of(wreq_error()) || e.is_kind_of(interrupted_error()) {
+        return false;
+    }
+    let Some(exc) = e.value() else { return false };
+    let Ok(kind) = exc.funcall::<_, _, Option<Symbol>>("error_kind", ()) else { return false };
+    let Some(kind) = kind.and_then(|k| k.name().ok()) else { return false };
+    matches!(kind.as_ref(), "connect" | "connect_refused" | "connect_timeout" | "reset" | "dns" | "tls" | "timeout")
+}
+
+/// Append `query` to the URL's own query (after a `&`), before any fragment.
+fn append_query(url: &str, query: &str) -> String {
+    if query.is_empty() {
+        return url.to_owned();
+    }
+    let (base, fragment) = url.split_at(url.find('#').unwrap_or(url.len()));
+    let sep = match base.find('?') {
+        None => "?",
+        Some(_) if base.ends_with('?') || base.ends_with('&') => "",
+        Some(_) => "&",
+    };
+    format!("{}{}{}{}", base, sep, query, fragment)
+}
+
+/// Flatten a form/query Hash into key/value pairs. With `nested`, Hash
+/// values are expanded Rack-style (`filter[status]=open`) and Array values
+/// as `ids[]=1&ids[]=2`; otherwise every value is rendered with `to_s`.
+fn hash_to_pairs(hash: &RHash, nested: bool) -> Result<Vec<(String, String)>, magnus::Error> {
     let mut pairs: Vec<(String, String)> = Vec::new();
+    push_hash_pairs(&mut pairs, hash, None, nested)?;
+    Ok(pairs)
+}
+
+fn push_hash_pairs(
+    pairs: &mut Vec<(String, String)>,
+    hash: &RHash,
+    prefix: Option<&str>,
+    nested: bool,
+) -> Result<(), magnus::Error> {
     hash.foreach(|k: Value, v: Value| {
         let ruby = unsafe { Ruby::get_unchecked() };
         let ks: String = if k.is_kind_of(ruby.class_symbol()) {
@@ -677,11 +3392,154 @@ fn hash_to_pairs(hash: &RHash) -> Result<Vec<(String, String)>, magnus::Error> {
         } else {
             TryConvert::try_convert(k)?
         };
-        let vs: String = v.funcall("to_s", ())?;
-        pairs.push((ks, vs));
+        let key = match prefix {
+            Some(p) => format!("{}[{}]", p, ks),
+            None => ks,
+        };
+        push_value_pairs(pairs, key, v, nested)?;
+        Ok(magnus::r_hash::ForEach::Continue)
+    })
+}
+
+fn push_value_pairs(
+    pairs: &mut Vec<(String, String)>,
+    key: String,
+    v: Value,
+    nested: bool,
+) -> Result<(), magnus::Error> {
+    if nested {
+        if let Some(inner) = RHash::from_value(v) {
+            return push_hash_pairs(pairs, &inner, Some(&key), nested);
+        }
+        if let Some(ary) = RArray::from_value(v) {
+            let key = format!("{}[]", key);
+            for item in ary.into_iter() {
+                push_value_pairs(pairs, key.clone(), item, nested)?;
+            }
+            return Ok(());
+        }
+    }
+    let vs: String = v.funcall("to_s", ())?;
+    pairs.push((key, vs));
+    Ok(())
+}
+
+/// Build a multipart form from a Ruby hash. Values may be:
+/// - a String: sent as a text field, or as an `application/octet-stream`
+///   byte part if the String is binary (ASCII-8BIT) encoded;
+/// - a Hash with `content:` (in-memory) or `path:` (streamed from disk),
+///   plus optional `filename:` and `content_type:`;
+/// - anything else, converted with `to_s`.
+fn hash_to_multipart_form(hash: &RHash) -> Result<Form, magnus::Error> {
+    let mut parts: Vec<(String, Part)> = Vec::new();
+    hash.foreach(|k: Value, v: Value| {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let name: String = if k.is_kind_of(ruby.class_symbol()) {
+            k.funcall("to_s", ())?
+        } else {
+            TryConvert::try_convert(k)?
+        };
+        if let Some(spec) = RHash::from_value(v) {
+            let (mut part, default_mime) = if let Some(path) = hash_get_string(&spec, "path")? {
+                (file_part(&path)?, Some("application/octet-stream".to_owned()))
+            } else {
+                let content = hash_get_value(&spec, "content")?.ok_or_else(|| {
+                    generic_error(format!("multipart field '{}' requires content: or path:", name))
+                })?;
+                let content = RString::try_convert(content)?;
+                let default_mime = is_binary_string(content).then(|| "application/octet-stream".to_owned());
+                (Part::bytes(rstring_to_bytes(content)), default_mime)
+            };
+            let mime = hash_get_string(&spec, "content_type")?.or(default_mime);
+            if let Some(mime) = mime {
+                part = part.mime_str(&mime).map_err(to_magnus_error)?;
+            }
+            if let Some(filename) = hash_get_string(&spec, "filename")? {
+                part = part.file_name(filename);
+            }
+            parts.push((name, part));
+        } else if let Some(s) = RString::from_value(v).filter(|s| is_binary_string(*s)) {
+            let part = Part::bytes(rstring_to_bytes(s))
+                .mime_str("application/octet-stream")
+                .map_err(to_magnus_error)?;
+            parts.push((name, part));
+        } else {
+            let text: String = v.funcall("to_s", ())?;
+            parts.push((name, Part::text(text)));
+        }
         Ok(magnus::r_hash::ForEach::Continue)
     })?;
-    Ok(pairs)
+    Ok(parts
+        .into_iter()
+        .fold(Form::new(), |form, (name, part)| form.part(name, part)))
+}
+
+/// A body that streams the file at `path` from disk, and its length from
+/// the file's metadata.
+fn file_body(path: &str) -> Result<(wreq::Body, u64), magnus::Error> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| generic_error(format!("cannot open '{}': {}", path, e)))?;
+    let len = file
+        .metadata()
+        .map_err(|e| generic_error(format!("cannot stat '{}': {}", path, e)))?
+        .len();
+    let stream = ReaderStream::with_capacity(tokio::fs::File::from_std(file), STREAM_CHUNK_SIZE);
+    Ok((wreq::Body::wrap_stream(stream), len))
+}
+
+/// `Content-Type` for a file by its extension, for `upload`.
+fn content_type_for_path(path: &str) -> &'static str {
+    let ext = std::path::Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "txt" | "log" => "text/plain",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "js" | "mjs" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "wasm" => "application/wasm",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// A multipart part that streams the file at `path` from disk. The file
+/// name defaults to the path's basename and the part length is taken from
+/// the file's metadata, so the form keeps a known `Content-Length`.
+fn file_part(path: &str) -> Result<Part, magnus::Error> {
+    let (body, len) = file_body(path)?;
+    let mut part = Part::stream_with_length(body, len);
+    if let Some(name) = std::path::Path::new(path).file_name() {
+        part = part.file_name(name.to_string_lossy().into_owned());
+    }
+    Ok(part)
+}
+
+/// True if the Ruby String is tagged ASCII-8BIT, i.e. holds raw bytes.
+fn is_binary_string(s: RString) -> bool {
+    s.enc_get() == magnus::encoding::Index::ascii8bit()
+}
+
+fn rstring_to_bytes(s: RString) -> Vec<u8> {
+    // SAFETY: the slice is copied immediately, before any Ruby code can run.
+    unsafe { s.as_slice().to_vec() }
 }
 
 // --------------------------------------------------------------------------
@@ -699,7 +3557,49 @@ pub fn init(_ruby: &magnus::Ruby, module: &magnus::RModule) -> Result<(), magnus
     client_class.define_method("delete", method!(Client::delete, -1))?;
     client_class.define_method("head", method!(Client::head, -1))?;
     client_class.define_method("options", method!(Client::options, -1))?;
+    client_class.define_method("allowed_methods", method!(Client::allowed_methods, -1))?;
+    client_class.define_method("try_get", method!(Client::try_get, -1))?;
+    client_class.define_method("try_post", method!(Client::try_post, -1))?;
+    client_class.define_method("try_put", method!(Client::try_put, -1))?;
+    client_class.define_method("try_patch", method!(Client::try_patch, -1))?;
+    client_class.define_method("try_delete", method!(Client::try_delete, -1))?;
+    client_class.define_method("try_head", method!(Client::try_head, -1))?;
+    client_class.define_method("try_options", method!(Client::try_options, -1))?;
+    client_class.define_method("send_request", method!(Client::send_request, 1))?;
+    client_class.define_method("get_json", method!(Client::get_json, -1))?;
+    client_class.define_method("post_json", method!(Client::post_json, -1))?;
+    client_class.define_method("put_json", method!(Client::put_json, -1))?;
+    client_class.define_method("patch_json", method!(Client::patch_json, -1))?;
+    client_class.define_method("post_form", method!(Client::post_form, -1))?;
+    client_class.define_method("post_multipart", method!(Client::post_multipart, -1))?;
+    client_class.define_method("upload", method!(Client::upload, -1))?;
+    client_class.define_method("graphql", method!(Client::graphql, -1))?;
+    client_class.define_method("each_event", method!(Client::each_event, -1))?;
+    client_class.define_method("download", method!(Client::download, -1))?;
+    client_class.define_method("download_if_changed", method!(Client::download_if_changed, -1))?;
+    client_class.define_method("download_resumable", method!(Client::download_resumable, -1))?;
+    client_class.define_method("stream_to_io", method!(Client::stream_to_io, -1))?;
+    client_class.define_method("peek", method!(Client::peek, -1))?;
+    client_class.define_method("exists?", method!(Client::exists, -1))?;
+    client_class.define_method("head_size", method!(Client::head_size, -1))?;
+    client_class.define_method("healthy?", method!(Client::healthy, -1))?;
+    client_class.define_method("pool_stats", method!(Client::pool_stats, 0))?;
+    client_class.define_method("cache_stats", method!(Client::cache_stats, 0))?;
+    client_class.define_method("dns_cache_stats", method!(Client::dns_cache_stats, 0))?;
+    client_class.define_method("cache_clear", method!(Client::cache_clear, 0))?;
+    client_class.define_method("bytes_sent", method!(Client::bytes_sent, 0))?;
+    client_class.define_method("bytes_received", method!(Client::bytes_received, 0))?;
+    client_class.define_method("reset_counters", method!(Client::reset_counters, 0))?;
+    client_class.define_method("save_cookies", method!(Client::save_cookies, 0))?;
+    client_class.define_method("close", method!(Client::close, 0))?;
+    client_class.define_method("closed?", method!(Client::is_closed, 0))?;
     client_class.define_method("cancel", method!(Client::cancel, 0))?;
+    client_class.define_method("abort_all", method!(Client::cancel, 0))?;
+    client_class.define_method("with_timeout", method!(Client::with_timeout, 1))?;
+    client_class.define_method("with_cookies", method!(Client::with_cookies, -1))?;
+    client_class.define_method("cookie_jar", method!(Client::cookie_jar, 0))?;
+    client_class.define_method("base_uri", method!(Client::base_uri, 0))?;
+    client_class.define_method("default_headers", method!(Client::default_headers, 0))?;
 
     module.define_module_function("get", function!(wreq_get, -1))?;
     module.define_module_function("post", function!(wreq_post, -1))?;
@@ -707,6 +3607,9 @@ pub fn init(_ruby: &magnus::Ruby, module: &magnus::RModule) -> Result<(), magnus
     module.define_module_function("patch", function!(wreq_patch, -1))?;
     module.define_module_function("delete", function!(wreq_delete, -1))?;
     module.define_module_function("head", function!(wreq_head, -1))?;
+    module.define_module_function("benchmark", function!(wreq_benchmark, -1))?;
+    module.define_module_function("default_emulation", function!(wreq_default_emulation, 0))?;
+    module.define_module_function("default_emulation=", function!(wreq_set_default_emulation, 1))?;
 
     Ok(())
 }
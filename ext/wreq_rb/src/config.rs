@@ -0,0 +1,188 @@
+use std::sync::OnceLock;
+
+use magnus::{
+    function, method, prelude::*, value::Opaque, Module, RHash, Ruby, Symbol, Value,
+};
+
+use crate::error::generic_error;
+
+/// `Client.new` options that may be given process-wide defaults.
+const CONFIG_KEYS: &[&str] = &[
+    "base_url",
+    "default_scheme",
+    "emulation",
+    "emulation_os",
+    "user_agent",
+    "headers",
+    "header_order",
+    "locale",
+    "accept_language",
+    "send_accept_encoding",
+    "timeout",
+    "total_timeout",
+    "allow_incomplete",
+    "content_length",
+    "connect_timeout",
+    "read_timeout",
+    "redirect",
+    "cookie_store",
+    "cookie_jar",
+    "cookie_jar_path",
+    "proxy",
+    "proxy_user",
+    "proxy_pass",
+    "proxies",
+    "proxy_chain",
+    "proxy_strategy",
+    "no_proxy",
+    "https_only",
+    "verify_host",
+    "verify_hostname",
+    "sni_hostname",
+    "verify_cert",
+    "ocsp",
+    "http1_only",
+    "http2_only",
+    "gzip",
+    "brotli",
+    "deflate",
+    "zstd",
+    "referer",
+    "auto_referer",
+    "pool_max_idle_per_host",
+    "reuse_connections",
+    "pool_max_size",
+    "tcp_nodelay",
+    "tcp_keepalive",
+    "dns_cache_ttl",
+    "stream_body_over",
+    "download_buffer_size",
+    "max_body_size",
+    "max_header_size",
+    "cache",
+    "circuit_breaker",
+    "truncate_body",
+    "local_address",
+    "pin_sha256",
+    "tls_sni",
+    "min_tls_version",
+    "max_tls_version",
+    "before_request",
+    "on_request",
+    "on_response",
+    "on_informational",
+];
+
+/// Global defaults, keyed by Symbol. Only touched with the GVL held, which
+/// serializes access across Ruby threads.
+static DEFAULTS: OnceLock<Opaque<RHash>> = OnceLock::new();
+
+fn defaults() -> RHash {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    ruby.get_inner(*DEFAULTS.get().expect("Wreq config not initialized"))
+}
+
+/// Layer `opts` over the global defaults. Explicit options always win; with
+/// no defaults configured `opts` is returned as is.
+pub fn with_defaults(opts: Option<RHash>) -> Result<Option<RHash>, magnus::Error> {
+    let defaults = defaults();
+    if defaults.is_empty() {
+        return Ok(opts);
+    }
+    let merged: RHash = defaults.funcall("dup", ())?;
+    if let Some(opts) = opts {
+        merged.update(opts)?;
+    }
+    Ok(Some(merged))
+}
+
+/// `Wreq.config`: accessor object for the process-wide defaults.
+#[magnus::wrap(class = "Wreq::Config", free_immediately)]
+struct Config;
+
+impl Config {
+    /// `c.timeout = 10` / `c.timeout` for every key in `CONFIG_KEYS`.
+    fn method_missing(&self, args: &[Value]) -> Result<Value, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let name = Symbol::try_convert(args[0])?.name()?.into_owned();
+        match (name.strip_suffix('='), &args[1..]) {
+            (Some(key), [value]) if CONFIG_KEYS.contains(&key) => {
+                defaults().aset(ruby.to_symbol(key), *value)?;
+                Ok(*value)
+            }
+            (None, []) if CONFIG_KEYS.contains(&name.as_str()) => defaults().aref(ruby.to_symbol(&name)),
+            _ => Err(magnus::Error::new(
+                ruby.exception_no_method_error(),
+                format!("undefined method '{}' for Wreq::Config", name),
+            )),
+        }
+    }
+
+    fn respond_to_missing(&self, name: Symbol, _include_private: bool) -> Result<bool, magnus::Error> {
+        let name = name.name()?;
+        Ok(CONFIG_KEYS.contains(&name.trim_end_matches('=')))
+    }
+
+    /// `c[:timeout]`
+    fn aref(&self, key: Value) -> Result<Value, magnus::Error> {
+        defaults().aref(config_key(key)?)
+    }
+
+    /// `c[:timeout] = 10`
+    fn aset(&self, key: Value, value: Value) -> Result<Value, magnus::Error> {
+        defaults().aset(config_key(key)?, value)?;
+        Ok(value)
+    }
+
+    /// Snapshot of the configured defaults.
+    fn to_h(&self) -> Result<RHash, magnus::Error> {
+        defaults().funcall("dup", ())
+    }
+
+    /// Forget every configured default.
+    fn reset(&self) -> Result<(), magnus::Error> {
+        defaults().funcall::<_, _, Value>("clear", ())?;
+        Ok(())
+    }
+}
+
+/// Normalize a String or Symbol key, rejecting unknown option names.
+fn config_key(key: Value) -> Result<Symbol, magnus::Error> {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    let name: String = key.funcall("to_s", ())?;
+    if !CONFIG_KEYS.contains(&name.as_str()) {
+        return Err(generic_error(format!("unknown config option: {}", name)));
+    }
+    Ok(ruby.to_symbol(name))
+}
+
+/// Wreq.configure { |c| c.timeout = 10 }
+fn configure() -> Result<Config, magnus::Error> {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    if ruby.block_given() {
+        let _: Value = ruby.yield_value(Config)?;
+    }
+    Ok(Config)
+}
+
+fn config() -> Config {
+    Config
+}
+
+pub fn init(ruby: &Ruby, module: &magnus::RModule) -> Result<(), magnus::Error> {
+    let hash = ruby.hash_new();
+    magnus::gc::register_mark_object(hash);
+    let _ = DEFAULTS.set(hash.into());
+
+    let class = module.define_class("Config", ruby.class_object())?;
+    class.define_method("method_missing", method!(Config::method_missing, -1))?;
+    class.define_method("respond_to_missing?", method!(Config::respond_to_missing, 2))?;
+    class.define_method("[]", method!(Config::aref, 1))?;
+    class.define_method("[]=", method!(Config::aset, 2))?;
+    class.define_method("to_h", method!(Config::to_h, 0))?;
+    class.define_method("reset", method!(Config::reset, 0))?;
+
+    module.define_module_function("configure", function!(configure, 0))?;
+    module.define_module_function("config", function!(config, 0))?;
+    Ok(())
+}
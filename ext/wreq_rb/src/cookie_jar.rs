@@ -0,0 +1,305 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use magnus::{function, method, prelude::*, Module, RHash, Ruby, TryConvert, Value};
+use wreq::cookie::Jar;
+
+use crate::client::{hash_get_bool, hash_get_string, hash_get_value};
+use crate::error::generic_error;
+
+/// True if `name=value` can be sent in a Cookie header (RFC 6265 token
+/// name, cookie-octet value).
+pub(crate) fn is_valid_cookie(name: &str, value: &str) -> bool {
+    let token = |c: char| c.is_ascii_graphic() && !"()<>@,;:\\\"/[]?={}".contains(c);
+    let octet = |c: char| c.is_ascii_graphic() && !"\",;\\".contains(c);
+    !name.is_empty() && name.chars().all(token) && value.chars().all(octet)
+}
+
+/// A cookie store that can be shared between clients and persisted.
+/// It holds no state of its own: every read and write goes through the
+/// wreq `Jar`, which locks internally, so the same jar can be used from
+/// Ruby threads while requests update it off the GVL.
+#[magnus::wrap(class = "Wreq::CookieJar", free_immediately)]
+pub struct CookieJar {
+    pub(crate) inner: Arc<Jar>,
+}
+
+/// A cookie with every attribute needed to rebuild it elsewhere.
+#[derive(Clone)]
+pub(crate) struct StoredCookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    /// Set without a `Domain` attribute, so only sent to `domain` itself
+    /// and not to its subdomains.
+    pub host_only: bool,
+    pub path: String,
+    pub secure: bool,
+    pub http_only: bool,
+    /// Unix timestamp in seconds, or `None` for a session cookie.
+    pub expires: Option<u64>,
+}
+
+impl StoredCookie {
+    /// Render as a `Set-Cookie` value together with the URL it must be
+    /// stored against.
+    pub fn to_set_cookie(&self) -> (String, String) {
+        let mut s = format!("{}={}", self.name, self.value);
+        if !self.host_only {
+            s.push_str(&format!("; Domain={}", self.domain));
+        }
+        s.push_str(&format!("; Path={}", self.path));
+        if let Some(exp) = self.expires {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            s.push_str(&format!("; Max-Age={}", exp.saturating_sub(now)));
+        }
+        if self.secure {
+            s.push_str("; Secure");
+        }
+        if self.http_only {
+            s.push_str("; HttpOnly");
+        }
+        let scheme = if self.secure { "https" } else { "http" };
+        let url = format!("{}://{}{}", scheme, self.domain.trim_start_matches('.'), self.path);
+        (s, url)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "value": self.value,
+            "domain": self.domain,
+            "host_only": self.host_only,
+            "path": self.path,
+            "secure": self.secure,
+            "http_only": self.http_only,
+            "expires": self.expires,
+        })
+    }
+
+    fn from_json(v: &serde_json::Value) -> Option<Self> {
+        Some(StoredCookie {
+            name: v.get("name")?.as_str()?.to_owned(),
+            value: v.get("value")?.as_str()?.to_owned(),
+            domain: v.get("domain")?.as_str()?.to_owned(),
+            host_only: v.get("host_only").and_then(|b| b.as_bool()).unwrap_or(false),
+            path: v.get("path").and_then(|p| p.as_str()).unwrap_or("/").to_owned(),
+            secure: v.get("secure").and_then(|b| b.as_bool()).unwrap_or(false),
+            http_only: v.get("http_only").and_then(|b| b.as_bool()).unwrap_or(false),
+            expires: v.get("expires").and_then(|e| e.as_u64()),
+        })
+    }
+}
+
+/// Render cookies in the Netscape/Mozilla `cookies.txt` format used by curl.
+pub(crate) fn to_netscape(cookies: &[StoredCookie]) -> String {
+    let mut out = String::from("# Netscape HTTP Cookie File\n");
+    for c in cookies {
+        let bool_str = |b: bool| if b { "TRUE" } else { "FALSE" };
+        out.push_str(&format!(
+            "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            if c.http_only { "#HttpOnly_" } else { "" },
+            c.domain,
+            bool_str(!c.host_only),
+            c.path,
+            bool_str(c.secure),
+            c.expires.unwrap_or(0),
+            c.name,
+            c.value,
+        ));
+    }
+    out
+}
+
+/// Parse a Netscape `cookies.txt` file. Comments and malformed lines are
+/// skipped rather than failing the whole file.
+pub(crate) fn parse_netscape(data: &str) -> Vec<StoredCookie> {
+    data.lines()
+        .filter_map(|line| {
+            let (http_only, line) = match line.strip_prefix("#HttpOnly_") {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            if line.starts_with('#') || line.trim().is_empty() {
+                return None;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 7 || fields[0].is_empty() || fields[5].is_empty() {
+                return None;
+            }
+            let expires: u64 = fields[4].trim().parse().ok()?;
+            Some(StoredCookie {
+                domain: fields[0].to_owned(),
+                host_only: !fields[1].eq_ignore_ascii_case("TRUE"),
+                path: fields[2].to_owned(),
+                secure: fields[3].eq_ignore_ascii_case("TRUE"),
+                expires: (expires > 0).then_some(expires),
+                name: fields[5].to_owned(),
+                value: fields[6].to_owned(),
+                http_only,
+            })
+        })
+        .collect()
+}
+
+impl CookieJar {
+    pub fn from_jar(inner: Arc<Jar>) -> Self {
+        CookieJar { inner }
+    }
+
+    fn rb_new() -> Self {
+        CookieJar { inner: Arc::new(Jar::default()) }
+    }
+
+    /// Every cookie currently held by the jar.
+    pub(crate) fn stored_cookies(&self) -> Vec<StoredCookie> {
+        self.inner
+            .get_all()
+            .map(|c| {
+                let expires = c
+                    .expires()
+                    .or_else(|| c.max_age().map(|age| SystemTime::now() + age))
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs());
+                let domain = c.domain().unwrap_or_default().to_owned();
+                let path = c.path().unwrap_or("/").to_owned();
+                StoredCookie {
+                    host_only: !self.sent_to_subdomains(c.name(), &domain, &path, c.secure()),
+                    name: c.name().to_owned(),
+                    value: c.value().to_owned(),
+                    domain,
+                    path,
+                    secure: c.secure(),
+                    http_only: c.http_only(),
+                    expires,
+                }
+            })
+            .collect()
+    }
+
+    /// Whether the jar would send cookie `name` of `domain` to a subdomain.
+    /// The jar reports every cookie with a domain, so this tells a host-only
+    /// cookie from one set with `Domain=`.
+    fn sent_to_subdomains(&self, name: &str, domain: &str, path: &str, secure: bool) -> bool {
+        let scheme = if secure { "https" } else { "http" };
+        let probe = format!("{}://wreq-host-only-probe.{}{}", scheme, domain.trim_start_matches('.'), path);
+        match probe.parse::<http::Uri>() {
+            Ok(uri) => self.inner.get(name, &uri).is_some(),
+            Err(_) => false,
+        }
+    }
+
+    /// Replace the jar's contents with `cookies`.
+    pub(crate) fn replace_all(&self, cookies: &[StoredCookie]) -> Result<(), magnus::Error> {
+        self.inner.clear();
+        cookies.iter().try_for_each(|cookie| self.insert(cookie))
+    }
+
+    /// Whether the jar holds a cookie it would send to `uri`.
+    pub(crate) fn sends_to(jar: &Jar, uri: &http::Uri) -> bool {
+        jar.get_all().any(|c| jar.get(c.name(), uri).is_some())
+    }
+
+    pub(crate) fn insert(&self, cookie: &StoredCookie) -> Result<(), magnus::Error> {
+        let (set_cookie, url) = cookie.to_set_cookie();
+        let uri: http::Uri = url
+            .parse()
+            .map_err(|_| generic_error(format!("invalid cookie domain: '{}'", cookie.domain)))?;
+        self.inner.add_cookie_str(&set_cookie, &uri);
+        Ok(())
+    }
+
+    /// jar.add("name=value; Path=/", "https://example.com")
+    fn add(&self, cookie: String, url: String) -> Result<(), magnus::Error> {
+        let uri: http::Uri = url
+            .parse()
+            .map_err(|_| generic_error(format!("invalid URL: '{}'", url)))?;
+        self.inner.add_cookie_str(&cookie, &uri);
+        Ok(())
+    }
+
+    /// jar.set(url, name, value, path: "/", domain: nil, secure: false,
+    ///         http_only: false, expires: nil)
+    ///
+    /// Store a fully specified cookie as if `url` had set it. Without
+    /// `domain:` the cookie is host-only; `expires:` is a Time or Unix
+    /// timestamp, and nil makes a session cookie.
+    fn set(&self, args: &[Value]) -> Result<(), magnus::Error> {
+        if args.len() < 3 {
+            return Err(generic_error("url, name and value are required"));
+        }
+        let url: String = TryConvert::try_convert(args[0])?;
+        let name: String = TryConvert::try_convert(args[1])?;
+        let value: String = TryConvert::try_convert(args[2])?;
+        if !is_valid_cookie(&name, &value) {
+            return Err(generic_error(format!("invalid cookie: {}={}", name, value)));
+        }
+        let opts = match args.get(3) {
+            Some(v) => RHash::try_convert(*v)?,
+            None => unsafe { Ruby::get_unchecked() }.hash_new(),
+        };
+
+        let mut cookie = format!("{}={}", name, value);
+        let path = hash_get_string(&opts, "path")?.unwrap_or_else(|| "/".to_owned());
+        cookie.push_str(&format!("; Path={}", path));
+        if let Some(domain) = hash_get_string(&opts, "domain")? {
+            cookie.push_str(&format!("; Domain={}", domain));
+        }
+        if let Some(v) = hash_get_value(&opts, "expires")? {
+            let at: i64 = v.funcall("to_i", ())?;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+            cookie.push_str(&format!("; Max-Age={}", (at - now).max(0)));
+        }
+        if hash_get_bool(&opts, "secure")?.unwrap_or(false) {
+            cookie.push_str("; Secure");
+        }
+        if hash_get_bool(&opts, "http_only")?.unwrap_or(false) {
+            cookie.push_str("; HttpOnly");
+        }
+        self.add(cookie, url)
+    }
+
+    fn clear(&self) {
+        self.inner.clear();
+    }
+
+    /// Serialize every cookie to a JSON string.
+    fn dump(&self) -> String {
+        let cookies: Vec<serde_json::Value> =
+            self.stored_cookies().iter().map(StoredCookie::to_json).collect();
+        serde_json::Value::Array(cookies).to_string()
+    }
+
+    /// Wreq::CookieJar.load(json_string)
+    fn load(data: String) -> Result<Self, magnus::Error> {
+        let parsed: serde_json::Value = serde_json::from_str(&data)
+            .map_err(|e| generic_error(format!("invalid cookie jar data: {}", e)))?;
+        let entries = parsed
+            .as_array()
+            .ok_or_else(|| generic_error("invalid cookie jar data: expected an array"))?;
+        let jar = CookieJar::rb_new();
+        for entry in entries {
+            let cookie = StoredCookie::from_json(entry)
+                .ok_or_else(|| generic_error(format!("invalid cookie entry: {}", entry)))?;
+            jar.insert(&cookie)?;
+        }
+        Ok(jar)
+    }
+
+    fn inspect(&self) -> String {
+        format!("#<Wreq::CookieJar cookies={}>", self.inner.get_all().count())
+    }
+}
+
+pub fn init(ruby: &Ruby, module: &magnus::RModule) -> Result<(), magnus::Error> {
+    let class = module.define_class("CookieJar", ruby.class_object())?;
+    class.define_singleton_method("new", function!(CookieJar::rb_new, 0))?;
+    class.define_singleton_method("load", function!(CookieJar::load, 1))?;
+    class.define_method("add", method!(CookieJar::add, 2))?;
+    class.define_method("set", method!(CookieJar::set, -1))?;
+    class.define_method("clear", method!(CookieJar::clear, 0))?;
+    class.define_method("dump", method!(CookieJar::dump, 0))?;
+    class.define_method("to_s", method!(CookieJar::dump, 0))?;
+    class.define_method("inspect", method!(CookieJar::inspect, 0))?;
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::io::Read;
+
+/// Which content-codings the client is willing to decode.
+#[derive(Clone, Copy, Debug)]
+pub struct Decoders {
+    pub gzip: bool,
+    pub brotli: bool,
+    pub deflate: bool,
+    pub zstd: bool,
+}
+
+impl Default for Decoders {
+    fn default() -> Self {
+        Decoders { gzip: true, brotli: true, deflate: true, zstd: true }
+    }
+}
+
+impl Decoders {
+    fn allows(&self, coding: &str) -> bool {
+        match coding {
+            "gzip" | "x-gzip" => self.gzip,
+            "br" => self.brotli,
+            "deflate" => self.deflate,
+            "zstd" => self.zstd,
+            _ => false,
+        }
+    }
+}
+
+/// Decode `data` compressed with a single content-coding
+/// (`gzip`, `br`/`brotli`, `deflate`, or `zstd`).
+pub fn decode(coding: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let res = match coding {
+        // MultiGzDecoder keeps reading past the first member, so bodies made
+        // of concatenated gzip members are decoded in full.
+        "gzip" | "x-gzip" => flate2::read::MultiGzDecoder::new(data).read_to_end(&mut out),
+        "br" | "brotli" => brotli::Decompressor::new(data, 4096).read_to_end(&mut out),
+        "deflate" => {
+            // "deflate" is specified as zlib-wrapped, but some servers send
+            // raw deflate; try both.
+            match flate2::read::ZlibDecoder::new(data).read_to_end(&mut out) {
+                Ok(n) => Ok(n),
+                Err(_) => {
+                    out.clear();
+                    flate2::read::DeflateDecoder::new(data).read_to_end(&mut out)
+                }
+            }
+        }
+        "zstd" => zstd::stream::read::Decoder::new(data).and_then(|mut d| d.read_to_end(&mut out)),
+        other => return Err(format!("unsupported content encoding '{}'", other)),
+    };
+    res.map_err(|e| format!("failed to decode {} body: {}", coding, e))?;
+    Ok(out)
+}
+
+/// Undo the compression codings listed in a `Transfer-Encoding` value.
+/// `chunked` has already been removed by the HTTP layer; transfer codings
+/// are hop-by-hop, so they are always decoded when supported, whatever
+/// the client's content-coding flags. Unknown codings leave the body as is.
+pub fn decode_transfer(transfer_encoding: &str, body: Vec<u8>) -> Result<Vec<u8>, String> {
+    let codings: Vec<&str> = transfer_encoding
+        .split(',')
+        .map(str::trim)
+        .filter(|c| !c.eq_ignore_ascii_case("chunked"))
+        .collect();
+    decode_content(Some(&codings.join(",")), body, Decoders::default())
+}
+
+/// Undo the codings listed in a `Content-Encoding` value (applied in
+/// order, so they are removed in reverse). Returns the body untouched if
+/// any listed coding is not enabled in `decoders`.
+pub fn decode_content(
+    content_encoding: Option<&str>,
+    body: Vec<u8>,
+    decoders: Decoders,
+) -> Result<Vec<u8>, String> {
+    let Some(header) = content_encoding else {
+        return Ok(body);
+    };
+    if !decodable(header, decoders) {
+        return Ok(body);
+    }
+    let mut body = body;
+    for coding in codings(header).iter().rev() {
+        body = decode(coding, &body)?;
+    }
+    Ok(body)
+}
+
+/// True if `decode_content` would decode a body with this
+/// `Content-Encoding`: it lists at least one coding and every one is
+/// enabled. Shared-dictionary codings (`dcb`, `dcz`) never are, since the
+/// dictionary they were compressed against is not available here.
+pub fn decodable(content_encoding: &str, decoders: Decoders) -> bool {
+    let codings = codings(content_encoding);
+    !codings.is_empty() && codings.iter().all(|c| decoders.allows(c))
+}
+
+/// True if a `Content-Encoding` lists no coding other than `identity`,
+/// so the body as received is already the decoded one.
+pub fn identity(content_encoding: &str) -> bool {
+    codings(content_encoding).is_empty()
+}
+
+fn codings(header: &str) -> Vec<String> {
+    header
+        .split(',')
+        .map(|c| c.trim().to_ascii_lowercase())
+        .filter(|c| !c.is_empty() && c != "identity")
+        .collect()
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use magnus::{RHash, Ruby};
+use wreq::dns::{Addrs, Name, Resolve, Resolving};
+
+/// A system (getaddrinfo) resolver that caches results for a fixed TTL.
+///
+/// wreq's default resolver does not cache at all, so every new connection
+/// performs a fresh lookup; this resolver lets callers trade freshness for
+/// fewer lookups. With a zero TTL it caches nothing and behaves like
+/// wreq's, except that failed lookups are reported as `DnsError`.
+pub struct CachingResolver {
+    ttl: Duration,
+    cache: Arc<Mutex<DnsCache>>,
+}
+
+#[derive(Default)]
+struct DnsCache {
+    entries: HashMap<String, (Instant, Vec<SocketAddr>)>,
+    hits: u64,
+    misses: u64,
+}
+
+impl CachingResolver {
+    pub fn new(ttl: Duration) -> Self {
+        CachingResolver { ttl, cache: Arc::new(Mutex::new(DnsCache::default())) }
+    }
+
+    /// `{ hits:, misses:, entries:, ttl: }`; a miss is a system lookup.
+    pub fn stats(&self, ruby: &Ruby) -> Result<RHash, magnus::Error> {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        let hash = ruby.hash_new();
+        hash.aset(ruby.to_symbol("hits"), cache.hits)?;
+        hash.aset(ruby.to_symbol("misses"), cache.misses)?;
+        hash.aset(ruby.to_symbol("entries"), cache.entries.len())?;
+        hash.aset(ruby.to_symbol("ttl"), self.ttl.as_secs_f64())?;
+        Ok(hash)
+    }
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_owned();
+        let ttl = self.ttl;
+        let cache = self.cache.clone();
+        Box::pin(async move {
+            {
+                let mut guard = cache.lock().unwrap_or_else(|e| e.into_inner());
+                if let Some((at, addrs)) = guard.entries.get(&host) {
+                    if at.elapsed() < ttl {
+                        let addrs: Addrs = Box::new(addrs.clone().into_iter());
+                        guard.hits += 1;
+                        return Ok(addrs);
+                    }
+                }
+                guard.misses += 1;
+            }
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await
+                .map_err(|source| DnsError { host: host.clone(), source })?
+                .collect();
+            if ttl.is_zero() {
+                let addrs: Addrs = Box::new(addrs.into_iter());
+                return Ok(addrs);
+            }
+            cache
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .entries
+                .insert(host, (Instant::now(), addrs.clone()));
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// A host name that could not be resolved.
+#[derive(Debug)]
+pub struct DnsError {
+    host: String,
+    source: std::io::Error,
+}
+
+impl std::fmt::Display for DnsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to resolve {}: {}", self.host, self.source)
+    }
+}
+
+impl std::error::Error for DnsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
@@ -1,22 +1,343 @@
-use magnus::{ExceptionClass, Module};
+use std::error::Error as StdError;
+use std::io::ErrorKind;
+
+use magnus::{method, prelude::*, Exception, ExceptionClass, Module, Ruby, Value};
+
+use crate::dns::DnsError;
 
 static mut WREQ_ERROR: Option<ExceptionClass> = None;
+static mut DECODE_ERROR: Option<ExceptionClass> = None;
+static mut TLS_ERROR: Option<ExceptionClass> = None;
+static mut INVALID_HEADER_ERROR: Option<ExceptionClass> = None;
+static mut TOO_MANY_REDIRECTS_ERROR: Option<ExceptionClass> = None;
+static mut TIMEOUT_ERROR: Option<ExceptionClass> = None;
+static mut INTERRUPTED_ERROR: Option<ExceptionClass> = None;
+static mut HTTP_STATUS_ERROR: Option<ExceptionClass> = None;
+static mut UNAUTHORIZED_ERROR: Option<ExceptionClass> = None;
+static mut FORBIDDEN_ERROR: Option<ExceptionClass> = None;
+static mut NOT_FOUND_ERROR: Option<ExceptionClass> = None;
+static mut TOO_MANY_REQUESTS_ERROR: Option<ExceptionClass> = None;
+static mut SERVER_ERROR: Option<ExceptionClass> = None;
 
 pub fn wreq_error() -> ExceptionClass {
     unsafe { WREQ_ERROR.unwrap() }
 }
 
+/// `Wreq::DecodeError`: the response body is not in the expected format.
+pub fn decode_error() -> ExceptionClass {
+    unsafe { DECODE_ERROR.unwrap() }
+}
+
+/// `Wreq::TlsError`: the TLS handshake or certificate checks failed.
+pub fn tls_error() -> ExceptionClass {
+    unsafe { TLS_ERROR.unwrap() }
+}
+
+/// `Wreq::InvalidHeaderError`: a header name or value given as an option
+/// is not valid HTTP.
+pub fn invalid_header_error() -> ExceptionClass {
+    unsafe { INVALID_HEADER_ERROR.unwrap() }
+}
+
+/// `Wreq::TooManyRedirectsError`: the redirect limit was exceeded.
+pub fn too_many_redirects_error() -> ExceptionClass {
+    unsafe { TOO_MANY_REDIRECTS_ERROR.unwrap() }
+}
+
+/// `Wreq::TimeoutError`: a timeout or the `total_timeout:` budget expired.
+pub fn timeout_error() -> ExceptionClass {
+    unsafe { TIMEOUT_ERROR.unwrap() }
+}
+
+/// `Wreq::InterruptedError`: the request was cancelled by `Client#cancel`,
+/// `abort_all`, `close`, or a Ruby thread interrupt.
+pub fn interrupted_error() -> ExceptionClass {
+    unsafe { INTERRUPTED_ERROR.unwrap() }
+}
+
+/// The error raised for a request cut short by cancellation.
+pub fn interrupted() -> magnus::Error {
+    with_kind(interrupted_error(), "request interrupted".to_owned(), "interrupted")
+}
+
+/// The `Wreq::HTTPStatusError` subclass `Response#raise_for_status` raises
+/// for `status`: 401, 403, 404, and 429 have their own, 5xx statuses raise
+/// `Wreq::ServerError`, and other error statuses the base class.
+pub fn http_status_error(status: u16) -> ExceptionClass {
+    unsafe {
+        match status {
+            401 => UNAUTHORIZED_ERROR,
+            403 => FORBIDDEN_ERROR,
+            404 => NOT_FOUND_ERROR,
+            429 => TOO_MANY_REQUESTS_ERROR,
+            500..=599 => SERVER_ERROR,
+            _ => HTTP_STATUS_ERROR,
+        }
+        .unwrap()
+    }
+}
+
 pub fn init(ruby: &magnus::Ruby, module: &magnus::RModule) -> Result<(), magnus::Error> {
     let error_class = module.define_error("Error", ruby.exception_standard_error())?;
+    let decode_class = module.define_error("DecodeError", error_class)?;
+    let tls_class = module.define_error("TlsError", error_class)?;
+    let invalid_header_class = module.define_error("InvalidHeaderError", error_class)?;
+    let too_many_redirects_class = module.define_error("TooManyRedirectsError", error_class)?;
+    let timeout_class = module.define_error("TimeoutError", error_class)?;
+    let interrupted_class = module.define_error("InterruptedError", error_class)?;
+    let http_status_class = module.define_error("HTTPStatusError", error_class)?;
+    http_status_class.define_method("response", method!(response_reader, 0))?;
+    http_status_class.define_method("status", method!(status_reader, 0))?;
+    let unauthorized_class = module.define_error("UnauthorizedError", http_status_class)?;
+    let forbidden_class = module.define_error("ForbiddenError", http_status_class)?;
+    let not_found_class = module.define_error("NotFoundError", http_status_class)?;
+    let too_many_requests_class = module.define_error("TooManyRequestsError", http_status_class)?;
+    let server_class = module.define_error("ServerError", http_status_class)?;
+    error_class.define_method("error_kind", method!(error_kind_reader, 0))?;
+    error_class.define_method("causes", method!(causes_reader, 0))?;
     unsafe {
         WREQ_ERROR = Some(error_class);
+        DECODE_ERROR = Some(decode_class);
+        TLS_ERROR = Some(tls_class);
+        INVALID_HEADER_ERROR = Some(invalid_header_class);
+        TOO_MANY_REDIRECTS_ERROR = Some(too_many_redirects_class);
+        TIMEOUT_ERROR = Some(timeout_class);
+        INTERRUPTED_ERROR = Some(interrupted_class);
+        HTTP_STATUS_ERROR = Some(http_status_class);
+        UNAUTHORIZED_ERROR = Some(unauthorized_class);
+        FORBIDDEN_ERROR = Some(forbidden_class);
+        NOT_FOUND_ERROR = Some(not_found_class);
+        TOO_MANY_REQUESTS_ERROR = Some(too_many_requests_class);
+        SERVER_ERROR = Some(server_class);
     }
     Ok(())
 }
 
-/// Convert a wreq::Error into a magnus::Error
+/// `Wreq::Error#error_kind`: a Symbol such as `:dns` or `:connect_refused`
+/// for errors raised by the transport, nil for other errors.
+fn error_kind_reader(rb_self: Value) -> Result<Value, magnus::Error> {
+    rb_self.funcall("instance_variable_get", ("@error_kind",))
+}
+
+/// `Wreq::Error#causes`: messages of the underlying Rust errors, outermost
+/// first, for errors converted from one; empty otherwise.
+fn causes_reader(rb_self: Value) -> Result<Value, magnus::Error> {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    let causes: Value = rb_self.funcall("instance_variable_get", ("@causes",))?;
+    if causes.is_nil() {
+        return Ok(ruby.ary_new().as_value());
+    }
+    Ok(causes)
+}
+
+/// `Wreq::HTTPStatusError#response`: the response that failed the check.
+fn response_reader(rb_self: Value) -> Result<Value, magnus::Error> {
+    rb_self.funcall("instance_variable_get", ("@response",))
+}
+
+/// `Wreq::HTTPStatusError#status`: the response's status code.
+fn status_reader(rb_self: Value) -> Result<Value, magnus::Error> {
+    let resp: Value = response_reader(rb_self)?;
+    resp.funcall("status", ())
+}
+
+/// Build an exception of `class` with `error_kind` `:status` that carries
+/// `response`.
+pub fn with_response(class: ExceptionClass, msg: String, response: Value) -> magnus::Error {
+    let exc = match build(class, msg, Some("status")) {
+        Ok(exc) => exc,
+        Err(e) => return e,
+    };
+    match exc.funcall::<_, _, Value>("instance_variable_set", ("@response", response)) {
+        Ok(_) => magnus::Error::from(exc),
+        Err(e) => e,
+    }
+}
+
+/// Convert a wreq::Error into a magnus::Error. TLS failures are raised as
+/// `Wreq::TlsError`, exceeded redirect limits as `Wreq::TooManyRedirectsError`,
+/// and timeouts as `Wreq::TimeoutError`.
 pub fn to_magnus_error(err: wreq::Error) -> magnus::Error {
-    magnus::Error::new(wreq_error(), err.to_string())
+    let kind = classify(&err);
+    let class = match kind {
+        "tls" => tls_error(),
+        "redirect" if is_too_many_redirects(&err) => too_many_redirects_error(),
+        "timeout" | "connect_timeout" => timeout_error(),
+        _ => wreq_error(),
+    };
+    let exc = match build(class, err.to_string(), Some(kind)) {
+        Ok(exc) => exc,
+        Err(e) => return e,
+    };
+    finish(exc, err.source())
+}
+
+/// Build an exception of `class` carrying `kind` as its `error_kind`.
+pub fn with_kind(class: ExceptionClass, msg: String, kind: &str) -> magnus::Error {
+    match build(class, msg, Some(kind)) {
+        Ok(exc) => magnus::Error::from(exc),
+        Err(e) => e,
+    }
+}
+
+/// Build an exception of `class` whose `causes` lists `err` and its sources.
+pub fn with_source(class: ExceptionClass, msg: impl std::fmt::Display, err: &(dyn StdError + 'static)) -> magnus::Error {
+    match build(class, msg.to_string(), None) {
+        Ok(exc) => finish(exc, Some(err)),
+        Err(e) => e,
+    }
+}
+
+/// `Wreq::InvalidHeaderError` for a header that failed to convert.
+pub fn invalid_header(msg: impl std::fmt::Display, err: &(dyn StdError + 'static)) -> magnus::Error {
+    with_source(invalid_header_error(), msg, err)
+}
+
+/// Raise `class` with a Ruby exception as its `cause`, as `raise ..., cause:`
+/// would.
+pub fn with_cause(class: ExceptionClass, msg: impl std::fmt::Display, cause: magnus::Error) -> magnus::Error {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    let Some(cause) = cause.value() else {
+        // Not an exception (a throw or break); let it continue unwinding.
+        return cause;
+    };
+    let exc = match build(class, msg.to_string(), None) {
+        Ok(exc) => exc,
+        Err(e) => return e,
+    };
+    let kwargs = ruby.hash_new();
+    if let Err(e) = kwargs.aset(ruby.to_symbol("cause"), cause) {
+        return e;
+    }
+    match ruby.module_kernel().funcall::<_, _, Value>("raise", (exc, kwargs)) {
+        Err(e) => e,
+        Ok(_) => magnus::Error::from(exc),
+    }
+}
+
+fn build(class: ExceptionClass, msg: String, kind: Option<&str>) -> Result<Exception, magnus::Error> {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    let exc: Exception = class.new_instance((msg.as_str(),))?;
+    if let Some(kind) = kind {
+        let _: Value = exc.funcall("instance_variable_set", ("@error_kind", ruby.to_symbol(kind)))?;
+    }
+    Ok(exc)
+}
+
+/// Record the messages of `source` and its own sources in `@causes`.
+fn finish(exc: Exception, source: Option<&(dyn StdError + 'static)>) -> magnus::Error {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    let causes = ruby.ary_new();
+    let mut source = source;
+    while let Some(e) = source {
+        if let Err(err) = causes.push(e.to_string()) {
+            return err;
+        }
+        source = e.source();
+    }
+    if let Err(e) = exc.funcall::<_, _, Value>("instance_variable_set", ("@causes", causes)) {
+        return e;
+    }
+    magnus::Error::from(exc)
+}
+
+/// The error a client's redirect policy stops with once its limit is
+/// exceeded; carries the limit.
+#[derive(Debug)]
+pub struct TooManyRedirects(pub usize);
+
+impl std::fmt::Display for TooManyRedirects {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "too many redirects (limit {})", self.0)
+    }
+}
+
+impl StdError for TooManyRedirects {}
+
+/// The error a pinned client's redirect policy stops with instead of
+/// following a redirect to another origin.
+#[derive(Debug)]
+pub struct CrossOriginRedirect {
+    pub from: String,
+    pub to: String,
+}
+
+impl std::fmt::Display for CrossOriginRedirect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "refusing redirect from {} to {}: pin_sha256 does not cover other origins", self.from, self.to)
+    }
+}
+
+impl StdError for CrossOriginRedirect {}
+
+/// True if `err` is a redirect error raised by an exceeded redirect limit.
+fn is_too_many_redirects(err: &wreq::Error) -> bool {
+    if !err.is_redirect() {
+        return false;
+    }
+    let mut source: Option<&(dyn StdError + 'static)> = err.source();
+    while let Some(e) = source {
+        if e.is::<TooManyRedirects>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// Classify a wreq error by walking its source chain.
+fn classify(err: &wreq::Error) -> &'static str {
+    let mut source: Option<&(dyn StdError + 'static)> = err.source();
+    while let Some(e) = source {
+        if let Some(io) = e.downcast_ref::<std::io::Error>() {
+            match io.kind() {
+                ErrorKind::ConnectionRefused => return "connect_refused",
+                ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe => {
+                    return "reset"
+                }
+                ErrorKind::TimedOut if err.is_connect() => return "connect_timeout",
+                ErrorKind::TimedOut => return "timeout",
+                _ => {}
+            }
+        }
+        if e.is::<DnsError>() {
+            return "dns";
+        }
+        if e.is::<boring2::ssl::Error>() || e.is::<boring2::error::ErrorStack>() {
+            return "tls";
+        }
+        source = next_source(e);
+    }
+
+    if err.is_timeout() {
+        if err.is_connect() { "connect_timeout" } else { "timeout" }
+    } else if err.is_connect() {
+        "connect"
+    } else if err.is_redirect() {
+        "redirect"
+    } else if err.is_decode() {
+        "decode"
+    } else if err.is_body() {
+        "body"
+    } else if err.is_builder() {
+        "builder"
+    } else if err.is_status() {
+        "status"
+    } else if err.is_request() {
+        "request"
+    } else {
+        "unknown"
+    }
+}
+
+/// The error under `e`. An `io::Error` wrapping another error reports
+/// that error's source rather than the error itself, so step into it.
+fn next_source<'a>(e: &'a (dyn StdError + 'static)) -> Option<&'a (dyn StdError + 'static)> {
+    if let Some(inner) = e.downcast_ref::<std::io::Error>().and_then(|io| io.get_ref()) {
+        let inner: &(dyn StdError + 'static) = inner;
+        return Some(inner);
+    }
+    e.source()
 }
 
 /// Convert any Display error into a magnus::Error
@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use magnus::{gc::Marker, prelude::*, typed_data::Obj, value::Opaque, RHash, Ruby, TryConvert, Value};
+
+use crate::client::RequestExtensions;
+use crate::error::{generic_error, invalid_header};
+use crate::response::Response;
+
+/// Callbacks a client runs around every request, always with the GVL held.
+#[derive(Default)]
+pub struct Hooks {
+    /// `before_request:` may change the headers of the request about to be sent.
+    before_request: Option<Opaque<Value>>,
+    on_request: Option<Opaque<Value>>,
+    on_response: Option<Opaque<Value>>,
+    /// `on_informational:` is validated and kept, but wreq consumes 1xx
+    /// interim responses internally and offers no hook to observe them,
+    /// so it is never called.
+    on_informational: Option<Opaque<Value>>,
+}
+
+impl Hooks {
+    pub fn new(
+        before_request: Option<Value>,
+        on_request: Option<Value>,
+        on_response: Option<Value>,
+        on_informational: Option<Value>,
+    ) -> Result<Self, magnus::Error> {
+        Ok(Hooks {
+            before_request: before_request.map(|v| callable(v, "before_request")).transpose()?,
+            on_request: on_request.map(|v| callable(v, "on_request")).transpose()?,
+            on_response: on_response.map(|v| callable(v, "on_response")).transpose()?,
+            on_informational: on_informational.map(|v| callable(v, "on_informational")).transpose()?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.on_request.is_none() && self.on_response.is_none()
+    }
+
+    pub fn mark(&self, marker: &Marker) {
+        for hook in [self.before_request, self.on_request, self.on_response, self.on_informational]
+            .into_iter()
+            .flatten()
+        {
+            marker.mark(hook);
+        }
+    }
+
+    /// Run `before_request` with `{ method:, url:, headers:, body:, extensions: }`
+    /// and apply its edits to the request's headers: changed or added entries
+    /// are set, and removed ones (or nil values) dropped. A Hash returned by
+    /// the hook is merged in as well. `body` is a binary String, or nil for
+    /// streamed bodies; `extensions` the request's `extensions:` as Strings.
+    /// Changes to anything but the headers are ignored.
+    pub fn before_send(&self, request: &mut wreq::Request) -> Result<(), magnus::Error> {
+        let Some(hook) = self.before_request else {
+            return Ok(());
+        };
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let headers = ruby.hash_new();
+        for name in request.headers().keys() {
+            let values: Vec<String> = request
+                .headers()
+                .get_all(name)
+                .iter()
+                .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+                .collect();
+            headers.aset(name.as_str(), values.join(", "))?;
+        }
+        let original: RHash = headers.funcall("dup", ())?;
+
+        let info = ruby.hash_new();
+        info.aset(ruby.to_symbol("method"), request.method().as_str())?;
+        info.aset(ruby.to_symbol("url"), request.uri().to_string())?;
+        info.aset(ruby.to_symbol("headers"), headers)?;
+        let body = request.body().and_then(|b| b.as_bytes()).map(|b| ruby.str_from_slice(b));
+        info.aset(ruby.to_symbol("body"), body)?;
+        let extensions = ruby.hash_new();
+        if let Some(RequestExtensions(map)) = request.extensions().get::<RequestExtensions>() {
+            for (k, v) in map {
+                extensions.aset(k.as_str(), v.as_str())?;
+            }
+        }
+        info.aset(ruby.to_symbol("extensions"), extensions)?;
+        let returned: Value = ruby.get_inner(hook).funcall("call", (info,))?;
+
+        let headers = match RHash::try_convert(info.aref::<_, Value>(ruby.to_symbol("headers"))?) {
+            Ok(h) => h,
+            Err(_) => return Err(generic_error("before_request: headers must remain a Hash")),
+        };
+        if let Some(extra) = RHash::from_value(returned) {
+            let _: Value = headers.funcall("update", (extra,))?;
+        }
+        original.foreach(|name: String, _: Value| {
+            let value: Value = headers.aref(name.as_str())?;
+            if value.is_nil() {
+                request.headers_mut().remove(name.as_str());
+            }
+            Ok(magnus::r_hash::ForEach::Continue)
+        })?;
+        headers.foreach(|name: Value, value: Value| {
+            if value.is_nil() {
+                return Ok(magnus::r_hash::ForEach::Continue);
+            }
+            let name: String = name.funcall("to_s", ())?;
+            let value: String = value.funcall("to_s", ())?;
+            let unchanged: Value = original.aref(name.to_ascii_lowercase())?;
+            if unchanged.is_nil() || String::try_convert(unchanged)? != value {
+                let header_name = wreq::header::HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|e| invalid_header(format!("invalid header name from before_request: '{}'", name), &e))?;
+                let header_value = wreq::header::HeaderValue::from_str(&value)
+                    .map_err(|e| invalid_header(format!("invalid value for header '{}' from before_request", name), &e))?;
+                request.headers_mut().insert(header_name, header_value);
+            }
+            Ok(magnus::r_hash::ForEach::Continue)
+        })?;
+        Ok(())
+    }
+
+    /// Run `on_request` with the event hash before the request is sent.
+    pub fn before(&self, info: RHash) -> Result<(), magnus::Error> {
+        if let Some(hook) = self.on_request {
+            let ruby = unsafe { Ruby::get_unchecked() };
+            let _: Value = ruby.get_inner(hook).funcall("call", (info,))?;
+        }
+        Ok(())
+    }
+
+    /// Run `on_response` with the response and the event hash, now also
+    /// carrying `status:` and `elapsed:` (seconds).
+    pub fn after(&self, resp: Obj<Response>, info: RHash, elapsed: Duration) -> Result<(), magnus::Error> {
+        if let Some(hook) = self.on_response {
+            let ruby = unsafe { Ruby::get_unchecked() };
+            info.aset(ruby.to_symbol("status"), resp.status())?;
+            info.aset(ruby.to_symbol("elapsed"), elapsed.as_secs_f64())?;
+            let _: Value = ruby.get_inner(hook).funcall("call", (resp, info))?;
+        }
+        Ok(())
+    }
+}
+
+/// The event hash handed to hooks: `{ method:, url:, meta: }`. `meta` is the
+/// caller's `meta:` option, passed through untouched and never sent.
+pub fn request_info(method: &str, url: &str, meta: Option<Value>) -> Result<RHash, magnus::Error> {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    let info = ruby.hash_new();
+    info.aset(ruby.to_symbol("method"), method)?;
+    info.aset(ruby.to_symbol("url"), url)?;
+    info.aset(ruby.to_symbol("meta"), meta.unwrap_or_else(|| ruby.qnil().as_value()))?;
+    Ok(info)
+}
+
+fn callable(val: Value, name: &str) -> Result<Opaque<Value>, magnus::Error> {
+    if !val.respond_to("call", false)? {
+        return Err(generic_error(format!("{} must respond to #call", name)));
+    }
+    Ok(val.into())
+}
@@ -1,8 +1,25 @@
 #![allow(unused_imports)]
 
+mod bench;
+mod breaker;
+mod cache;
 mod client;
+mod config;
+mod cookie_jar;
+mod decode;
+mod dns;
 mod error;
+mod hooks;
+mod log;
+mod options;
+mod pin;
+mod pool;
+mod request;
 mod response;
+mod result;
+mod sse;
+mod stream;
+mod upload;
 
 use magnus::prelude::*;
 
@@ -13,7 +30,13 @@ fn init(ruby: &magnus::Ruby) -> Result<(), magnus::Error> {
     let module = ruby.define_module("Wreq")?;
 
     error::init(ruby, &module)?;
+    config::init(ruby, &module)?;
     response::init(ruby, &module)?;
+    options::init(ruby, &module)?;
+    request::init(ruby, &module)?;
+    result::init(ruby, &module)?;
+    cookie_jar::init(ruby, &module)?;
+    log::init(ruby, &module)?;
     client::init(ruby, &module)?;
 
     Ok(())
@@ -0,0 +1,193 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use magnus::{function, prelude::*, value::Opaque, RArray, Ruby, Value};
+use tracing::field::{Field, Visit};
+use tracing_subscriber::{filter::LevelFilter, layer::SubscriberExt, reload, Layer, Registry};
+
+use crate::error::generic_error;
+
+/// Lines kept for the Ruby sink between deliveries; older ones are dropped.
+const MAX_QUEUED_LINES: usize = 10_000;
+
+/// Level control for the subscriber, installed by the first
+/// `Wreq.log_level=`. Later calls only swap the level.
+static LEVEL: Mutex<Option<reload::Handle<LevelFilter, Registry>>> = Mutex::new(None);
+
+/// Whether `Wreq.log_sink` is set. Events fire on runtime threads that
+/// cannot call into Ruby, so with a sink they are queued and handed over by
+/// `deliver` on a Ruby thread; without one they go straight to stderr.
+static SINKED: AtomicBool = AtomicBool::new(false);
+static QUEUE: Mutex<VecDeque<Line>> = Mutex::new(VecDeque::new());
+
+/// Holds the Ruby sink as its only element, keeping it marked.
+static SINK: OnceLock<Opaque<RArray>> = OnceLock::new();
+
+struct Line {
+    level: &'static str,
+    target: String,
+    message: String,
+}
+
+/// Formats an event's `message` followed by its other fields as `key=value`.
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, "{}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Sends every enabled event to the Ruby sink's queue or stderr.
+struct Forward;
+
+impl<S: tracing::Subscriber> Layer<S> for Forward {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let meta = event.metadata();
+        let mut message = Message::default();
+        event.record(&mut message);
+        let level = level_name(*meta.level());
+        if SINKED.load(Ordering::Relaxed) {
+            let mut queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+            if queue.len() == MAX_QUEUED_LINES {
+                queue.pop_front();
+            }
+            queue.push_back(Line { level, target: meta.target().to_owned(), message: message.0 });
+        } else {
+            eprintln!("[wreq] {} {}: {}", level.to_ascii_uppercase(), meta.target(), message.0);
+        }
+    }
+}
+
+fn level_name(level: tracing::Level) -> &'static str {
+    match level {
+        tracing::Level::ERROR => "error",
+        tracing::Level::WARN => "warn",
+        tracing::Level::INFO => "info",
+        tracing::Level::DEBUG => "debug",
+        tracing::Level::TRACE => "trace",
+    }
+}
+
+fn sink_holder() -> RArray {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    ruby.get_inner(*SINK.get().expect("Wreq log not initialized"))
+}
+
+/// Hand queued lines to `Wreq.log_sink`. Called with the GVL held after
+/// every request; exceptions raised by the sink are discarded so logging
+/// never fails a request.
+pub fn deliver() {
+    if !SINKED.load(Ordering::Relaxed) {
+        return;
+    }
+    let lines: Vec<Line> = QUEUE.lock().unwrap_or_else(|e| e.into_inner()).drain(..).collect();
+    if lines.is_empty() {
+        return;
+    }
+    let ruby = unsafe { Ruby::get_unchecked() };
+    let Ok(sink) = sink_holder().entry::<Value>(0) else { return };
+    if sink.is_nil() {
+        return;
+    }
+    for line in lines {
+        let _: Result<Value, _> = sink.funcall("call", (ruby.to_symbol(line.level), line.target, line.message));
+    }
+}
+
+/// Wreq.log_level = :debug
+///
+/// Installs the process-wide tracing subscriber on first use and sets the
+/// level of the wreq/hyper diagnostics it forwards: `:off`, `:error`,
+/// `:warn`, `:info`, `:debug`, or `:trace` (`nil` is `:off`).
+fn set_log_level(val: Value) -> Result<Value, magnus::Error> {
+    let name: String = if val.is_nil() { "off".to_owned() } else { val.funcall("to_s", ())? };
+    let filter = match name.as_str() {
+        "off" => LevelFilter::OFF,
+        "error" => LevelFilter::ERROR,
+        "warn" => LevelFilter::WARN,
+        "info" => LevelFilter::INFO,
+        "debug" => LevelFilter::DEBUG,
+        "trace" => LevelFilter::TRACE,
+        _ => {
+            return Err(generic_error(format!(
+                "unknown log level: {} (expected off, error, warn, info, debug, or trace)",
+                name
+            )))
+        }
+    };
+    let mut level = LEVEL.lock().unwrap_or_else(|e| e.into_inner());
+    match level.as_ref() {
+        Some(handle) => handle
+            .reload(filter)
+            .map_err(|e| generic_error(format!("cannot change log level: {}", e)))?,
+        None => {
+            let (layer, handle) = reload::Layer::new(filter);
+            let subscriber = Registry::default().with(layer).with(Forward);
+            tracing::subscriber::set_global_default(subscriber)
+                .map_err(|e| generic_error(format!("cannot install log subscriber: {}", e)))?;
+            *level = Some(handle);
+        }
+    }
+    Ok(val)
+}
+
+/// Wreq.log_level => :off
+fn log_level() -> Result<Value, magnus::Error> {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    let level = LEVEL.lock().unwrap_or_else(|e| e.into_inner());
+    let filter = match level.as_ref() {
+        Some(handle) => handle.clone_current().unwrap_or(LevelFilter::OFF),
+        None => LevelFilter::OFF,
+    };
+    let name = match filter.into_level() {
+        Some(level) => level_name(level),
+        None => "off",
+    };
+    Ok(ruby.to_symbol(name).as_value())
+}
+
+/// Wreq.log_sink = ->(level, target, message) { ... } (nil for stderr)
+fn set_log_sink(val: Value) -> Result<Value, magnus::Error> {
+    if !val.is_nil() && !val.respond_to("call", false)? {
+        return Err(generic_error("log_sink must respond to call"));
+    }
+    sink_holder().store(0, val)?;
+    SINKED.store(!val.is_nil(), Ordering::Relaxed);
+    if val.is_nil() {
+        QUEUE.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+    Ok(val)
+}
+
+fn log_sink() -> Result<Value, magnus::Error> {
+    sink_holder().entry(0)
+}
+
+/// Wreq.flush_log: deliver queued lines to the sink now.
+fn flush_log() {
+    deliver();
+}
+
+pub fn init(ruby: &Ruby, module: &magnus::RModule) -> Result<(), magnus::Error> {
+    let holder = ruby.ary_new_from_values(&[ruby.qnil().as_value()]);
+    magnus::gc::register_mark_object(holder);
+    let _ = SINK.set(holder.into());
+
+    module.define_module_function("log_level=", function!(set_log_level, 1))?;
+    module.define_module_function("log_level", function!(log_level, 0))?;
+    module.define_module_function("log_sink=", function!(set_log_sink, 1))?;
+    module.define_module_function("log_sink", function!(log_sink, 0))?;
+    module.define_module_function("flush_log", function!(flush_log, 0))?;
+    Ok(())
+}
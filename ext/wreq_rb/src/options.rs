@@ -0,0 +1,175 @@
+use magnus::{
+    function, gc::Marker, method, prelude::*, r_hash::ForEach, value::Opaque, DataTypeFunctions, Module,
+    RHash, Ruby, TryConvert, Value,
+};
+
+use crate::client::hash_get_duration;
+use crate::error::generic_error;
+
+/// Option names taken by the HTTP methods, plus those of the helpers that
+/// accept the same options Hash.
+const REQUEST_KEYS: &[&str] = &[
+    "headers",
+    "referer",
+    "locale",
+    "accept_language",
+    "body",
+    "body_enum",
+    "body_file",
+    "content_length",
+    "stream_body_over",
+    "json",
+    "form",
+    "multipart",
+    "max_body_size",
+    "allow_incomplete",
+    "truncate_body",
+    "meta",
+    "extensions",
+    "stream",
+    "query",
+    "raw_query",
+    "nested",
+    "gzip",
+    "brotli",
+    "deflate",
+    "zstd",
+    "max_redirects",
+    "timeout",
+    "total_timeout",
+    "auth",
+    "bearer",
+    "basic",
+    "proxy",
+    "emulation",
+    "emulation_os",
+    "sni_hostname",
+    // Helpers and module-level methods.
+    "client",
+    "method",
+    "content_type",
+    "download_buffer_size",
+    "etag_path",
+    "bytes",
+    "raise",
+    "symbolize_keys",
+    "variables",
+    "operation_name",
+    "raise_errors",
+    "last_event_id",
+    "max_reconnects",
+    "reconnect_delay",
+    "count",
+    "concurrency",
+];
+
+/// Time options parsed up front, so a bad value raises when the template
+/// is built rather than on its first use.
+const DURATION_KEYS: &[&str] = &["timeout", "total_timeout", "reconnect_delay"];
+
+/// A frozen, validated set of per-request options, built once and passed
+/// in place of the options Hash (`client.get(url, options)`).
+#[magnus::wrap(class = "Wreq::Options", free_immediately, mark)]
+pub struct Options {
+    /// Symbol-keyed and frozen, as are its `headers` and `query` Hashes.
+    opts: Opaque<RHash>,
+}
+
+impl DataTypeFunctions for Options {
+    fn mark(&self, marker: &Marker) {
+        marker.mark(self.opts);
+    }
+}
+
+impl Options {
+    /// Wreq::Options.new(timeout: 5, headers: { ... })
+    fn rb_new(args: &[Value]) -> Result<Self, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        if args.len() > 1 {
+            return Err(generic_error("Wreq::Options.new takes one options Hash"));
+        }
+        let opts = ruby.hash_new();
+        if let Some(v) = args.first() {
+            merge_into(&opts, request_opts(*v)?)?;
+        }
+        Self::build(opts)
+    }
+
+    /// Validate and freeze a Symbol-keyed Hash no one else holds.
+    fn build(opts: RHash) -> Result<Self, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        for key in DURATION_KEYS {
+            hash_get_duration(&opts, key)?;
+        }
+        for key in ["headers", "query"] {
+            let value: Value = opts.aref(ruby.to_symbol(key))?;
+            if let Some(hash) = RHash::from_value(value) {
+                let copy: RHash = hash.funcall("dup", ())?;
+                let _: Value = copy.funcall("freeze", ())?;
+                opts.aset(ruby.to_symbol(key), copy)?;
+            }
+        }
+        let _: Value = opts.funcall("freeze", ())?;
+        Ok(Options { opts: opts.into() })
+    }
+
+    fn hash(&self) -> RHash {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        ruby.get_inner(self.opts)
+    }
+
+    /// The frozen options Hash.
+    fn to_h(&self) -> RHash {
+        self.hash()
+    }
+
+    fn aref(&self, key: Value) -> Result<Value, magnus::Error> {
+        let key: magnus::Symbol = key.funcall("to_sym", ())?;
+        self.hash().aref(key)
+    }
+
+    /// A new template with `other`'s options (a Hash or `Wreq::Options`)
+    /// layered over these.
+    fn merge(&self, other: Value) -> Result<Self, magnus::Error> {
+        let opts: RHash = self.hash().funcall("dup", ())?;
+        merge_into(&opts, request_opts(other)?)?;
+        Self::build(opts)
+    }
+
+    fn inspect(&self) -> Result<String, magnus::Error> {
+        let hash: String = self.hash().funcall("inspect", ())?;
+        Ok(format!("#<Wreq::Options {}>", hash))
+    }
+}
+
+/// Copy `source` into `target` under Symbol keys, rejecting unknown names.
+fn merge_into(target: &RHash, source: RHash) -> Result<(), magnus::Error> {
+    let ruby = unsafe { Ruby::get_unchecked() };
+    source.foreach(|k: Value, v: Value| {
+        let name: String = k.funcall("to_s", ())?;
+        if !REQUEST_KEYS.contains(&name.as_str()) {
+            return Err(generic_error(format!("unknown request option: {}", name)));
+        }
+        target.aset(ruby.to_symbol(&name), v)?;
+        Ok(ForEach::Continue)
+    })
+}
+
+/// The options Hash of a request method: a Hash as given, or the frozen
+/// Hash of a `Wreq::Options`.
+pub fn request_opts(v: Value) -> Result<RHash, magnus::Error> {
+    if let Ok(options) = <&Options>::try_convert(v) {
+        return Ok(options.hash());
+    }
+    RHash::try_convert(v)
+}
+
+pub fn init(ruby: &Ruby, module: &magnus::RModule) -> Result<(), magnus::Error> {
+    let class = module.define_class("Options", ruby.class_object())?;
+    class.define_singleton_method("new", function!(Options::rb_new, -1))?;
+    class.define_method("to_h", method!(Options::to_h, 0))?;
+    class.define_method("[]", method!(Options::aref, 1))?;
+    class.define_method("merge", method!(Options::merge, 1))?;
+    class.define_method("inspect", method!(Options::inspect, 0))?;
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use boring2::x509::X509;
+
+/// SHA-256 digests of the SubjectPublicKeyInfo a server is allowed to present.
+pub struct PinSet(Vec<[u8; 32]>);
+
+impl PinSet {
+    /// Parse base64 SPKI SHA-256 pins (the `pin-sha256` format used by HPKP
+    /// and `curl --pinnedpubkey sha256//...`).
+    pub fn parse(pins: &[String]) -> Result<Self, String> {
+        let mut digests = Vec::with_capacity(pins.len());
+        for pin in pins {
+            let b64 = pin.trim().trim_start_matches("sha256//");
+            let raw = boring2::base64::decode_block(b64)
+                .map_err(|_| format!("invalid pin_sha256 (not base64): '{}'", pin))?;
+            let digest: [u8; 32] = raw
+                .try_into()
+                .map_err(|_| format!("invalid pin_sha256 (not a SHA-256 digest): '{}'", pin))?;
+            digests.push(digest);
+        }
+        if digests.is_empty() {
+            return Err("pin_sha256 requires at least one pin".to_owned());
+        }
+        Ok(PinSet(digests))
+    }
+
+    /// Check the leaf certificate of `resp`'s connection against the pins.
+    pub fn verify(&self, resp: &wreq::Response) -> Result<(), String> {
+        let der = resp
+            .extensions()
+            .get::<wreq::tls::TlsInfo>()
+            .and_then(|info| info.peer_certificate())
+            .ok_or("certificate pinning failed: no server certificate (is the URL https?)")?;
+        let spki = X509::from_der(der)
+            .and_then(|cert| cert.public_key())
+            .and_then(|key| key.public_key_to_der())
+            .map_err(|e| format!("certificate pinning failed: {}", e))?;
+        let digest = boring2::sha::sha256(&spki);
+        if self.0.contains(&digest) {
+            Ok(())
+        } else {
+            Err(format!(
+                "certificate pinning failed: server key sha256//{} matches no pin",
+                boring2::base64::encode_block(&digest)
+            ))
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use magnus::{RHash, Ruby};
+
+/// Request counts per origin, the closest stand-in for pool metrics that
+/// wreq allows: it exposes neither its pool nor connection lifecycle
+/// events, so idle connections cannot be counted.
+#[derive(Default)]
+pub struct PoolStats {
+    hosts: Mutex<BTreeMap<String, HostStats>>,
+}
+
+#[derive(Default)]
+struct HostStats {
+    /// Requests waiting for or reading a response right now.
+    active: usize,
+    /// Highest `active` seen: how many connections the host needed at once.
+    peak: usize,
+    /// Requests sent so far.
+    total: u64,
+}
+
+/// Marks a request to `host` as active until dropped.
+pub struct InFlight<'a> {
+    stats: &'a PoolStats,
+    host: String,
+}
+
+impl PoolStats {
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, HostStats>> {
+        self.hosts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Count a request to `uri`'s origin (`host:port`) as active.
+    pub fn begin(&self, uri: &http::Uri) -> InFlight<'_> {
+        let host = origin(uri);
+        let mut hosts = self.lock();
+        let entry = hosts.entry(host.clone()).or_default();
+        entry.active += 1;
+        entry.peak = entry.peak.max(entry.active);
+        entry.total += 1;
+        InFlight { stats: self, host }
+    }
+
+    /// `{ idle: nil, active:, per_host: { "host:port" => { active:, peak:, requests: } } }`
+    pub fn to_hash(&self, ruby: &Ruby) -> Result<RHash, magnus::Error> {
+        let hosts = self.lock();
+        let per_host = ruby.hash_new();
+        for (host, stats) in hosts.iter() {
+            let h = ruby.hash_new();
+            h.aset(ruby.to_symbol("active"), stats.active)?;
+            h.aset(ruby.to_symbol("peak"), stats.peak)?;
+            h.aset(ruby.to_symbol("requests"), stats.total)?;
+            per_host.aset(host.as_str(), h)?;
+        }
+        let hash = ruby.hash_new();
+        hash.aset(ruby.to_symbol("idle"), ruby.qnil())?;
+        hash.aset(ruby.to_symbol("active"), hosts.values().map(|s| s.active).sum::<usize>())?;
+        hash.aset(ruby.to_symbol("per_host"), per_host)?;
+        Ok(hash)
+    }
+}
+
+/// `uri`'s `host:port`, with the scheme's default port when none is given.
+pub fn origin(uri: &http::Uri) -> String {
+    let port = uri.port_u16().unwrap_or(if uri.scheme_str() == Some("https") { 443 } else { 80 });
+    format!("{}:{}", uri.host().unwrap_or(""), port)
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if let Some(entry) = self.stats.lock().get_mut(&self.host) {
+            entry.active -= 1;
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::sync::Mutex;
+
+use magnus::{
+    function, gc::Marker, method, prelude::*, typed_data::Obj, value::Opaque, DataTypeFunctions,
+    Module, RHash, Ruby, Symbol, TryConvert, Value,
+};
+
+use crate::error::generic_error;
+use crate::options::request_opts;
+
+/// A reusable request spec: method, URL, and the same per-request options
+/// the HTTP methods take. Sent with `Client#send_request`.
+#[magnus::wrap(class = "Wreq::Request", free_immediately, mark)]
+pub struct Request {
+    spec: Mutex<Spec>,
+}
+
+struct Spec {
+    method: String,
+    url: Option<String>,
+    /// Per-request options keyed by Symbol, as passed to `client.get`.
+    opts: Opaque<RHash>,
+}
+
+impl DataTypeFunctions for Request {
+    fn mark(&self, marker: &Marker) {
+        marker.mark(self.lock().opts);
+    }
+}
+
+impl Request {
+    /// Wreq::Request.new(method = "GET", url = nil, opts = {})
+    fn rb_new(args: &[Value]) -> Result<Self, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let method: String = match args.first() {
+            Some(v) if !v.is_nil() => v.funcall("to_s", ())?,
+            _ => "GET".to_owned(),
+        };
+        let url: Option<String> = match args.get(1) {
+            Some(v) if !v.is_nil() => Some(TryConvert::try_convert(*v)?),
+            _ => None,
+        };
+        let opts = ruby.hash_new();
+        if let Some(v) = args.get(2) {
+            // Symbolize keys so the setters and `options` see one spelling.
+            request_opts(*v)?.foreach(|k: Value, v: Value| {
+                let key: Symbol = k.funcall("to_sym", ())?;
+                opts.aset(key, v)?;
+                Ok(magnus::r_hash::ForEach::Continue)
+            })?;
+        }
+        Ok(Self::from_parts(method, url, opts))
+    }
+
+    fn from_parts(method: String, url: Option<String>, opts: RHash) -> Self {
+        Request {
+            spec: Mutex::new(Spec { method: method.to_ascii_uppercase(), url, opts: opts.into() }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Spec> {
+        self.spec.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The method, URL, and options `Client#send_request` sends.
+    pub fn parts(&self) -> Result<(String, String, RHash), magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let spec = self.lock();
+        let url = spec.url.clone().ok_or_else(|| generic_error("request url is required"))?;
+        Ok((spec.method.clone(), url, ruby.get_inner(spec.opts)))
+    }
+
+    fn opts(&self) -> RHash {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        ruby.get_inner(self.lock().opts)
+    }
+
+    fn method(&self) -> String {
+        self.lock().method.clone()
+    }
+
+    fn set_method(&self, method: Value) -> Result<(), magnus::Error> {
+        let method: String = method.funcall("to_s", ())?;
+        self.lock().method = method.to_ascii_uppercase();
+        Ok(())
+    }
+
+    fn url(&self) -> Option<String> {
+        self.lock().url.clone()
+    }
+
+    fn set_url(&self, url: Option<String>) {
+        self.lock().url = url;
+    }
+
+    fn get_opt(&self, key: &str) -> Result<Value, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        self.opts().aref(ruby.to_symbol(key))
+    }
+
+    /// Set an option, or remove it when `value` is nil.
+    fn set_opt(&self, key: &str, value: Value) -> Result<(), magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let opts = self.opts();
+        if value.is_nil() {
+            let _: Value = opts.delete(ruby.to_symbol(key))?;
+        } else {
+            opts.aset(ruby.to_symbol(key), value)?;
+        }
+        Ok(())
+    }
+
+    fn headers(&self) -> Result<Value, magnus::Error> {
+        self.get_opt("headers")
+    }
+
+    fn set_headers(&self, value: Value) -> Result<(), magnus::Error> {
+        self.set_opt("headers", value)
+    }
+
+    fn body(&self) -> Result<Value, magnus::Error> {
+        self.get_opt("body")
+    }
+
+    fn set_body(&self, value: Value) -> Result<(), magnus::Error> {
+        self.set_opt("body", value)
+    }
+
+    fn query(&self) -> Result<Value, magnus::Error> {
+        self.get_opt("query")
+    }
+
+    fn set_query(&self, value: Value) -> Result<(), magnus::Error> {
+        self.set_opt("query", value)
+    }
+
+    /// The live options Hash, for options without a dedicated setter
+    /// (`req.options[:timeout] = 5`).
+    fn options(&self) -> RHash {
+        self.opts()
+    }
+
+    /// A copy whose options, headers, and query can be changed without
+    /// affecting this request. Other option values are shared.
+    fn dup(&self) -> Result<Self, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let (method, url) = {
+            let spec = self.lock();
+            (spec.method.clone(), spec.url.clone())
+        };
+        let opts: RHash = self.opts().funcall("dup", ())?;
+        for key in ["headers", "query"] {
+            let value: Value = opts.aref(ruby.to_symbol(key))?;
+            if let Some(hash) = RHash::from_value(value) {
+                let copy: RHash = hash.funcall("dup", ())?;
+                opts.aset(ruby.to_symbol(key), copy)?;
+            }
+        }
+        Ok(Self::from_parts(method, url, opts))
+    }
+
+    fn inspect(rb_self: Obj<Self>) -> String {
+        let spec = rb_self.lock();
+        format!("#<Wreq::Request {} {}>", spec.method, spec.url.as_deref().unwrap_or("(no url)"))
+    }
+}
+
+pub fn init(ruby: &Ruby, module: &magnus::RModule) -> Result<(), magnus::Error> {
+    let class = module.define_class("Request", ruby.class_object())?;
+    class.define_singleton_method("new", function!(Request::rb_new, -1))?;
+    class.define_method("method", method!(Request::method, 0))?;
+    class.define_method("method=", method!(Request::set_method, 1))?;
+    class.define_method("url", method!(Request::url, 0))?;
+    class.define_method("url=", method!(Request::set_url, 1))?;
+    class.define_method("headers", method!(Request::headers, 0))?;
+    class.define_method("headers=", method!(Request::set_headers, 1))?;
+    class.define_method("body", method!(Request::body, 0))?;
+    class.define_method("body=", method!(Request::set_body, 1))?;
+    class.define_method("query", method!(Request::query, 0))?;
+    class.define_method("query=", method!(Request::set_query, 1))?;
+    class.define_method("options", method!(Request::options, 0))?;
+    class.define_method("dup", method!(Request::dup, 0))?;
+    class.define_method("clone", method!(Request::dup, 0))?;
+    class.define_method("inspect", method!(Request::inspect, 0))?;
+    Ok(())
+}
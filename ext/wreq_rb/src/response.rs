@@ -1,8 +1,16 @@
 use magnus::{
-    method, prelude::*, Module, RArray, RHash, Ruby, Value,
+    method, prelude::*, try_convert::TryConvert, typed_data::Obj, ExceptionClass, Module, RArray,
+    RHash, RString, Ruby, Value,
 };
 
-use crate::error::generic_error;
+use crate::client::{hash_get_bool, without_gvl};
+use crate::error::{decode_error, generic_error, http_status_error, with_response, with_source};
+use crate::stream::BodyStream;
+
+/// `Response#json` parses bodies at least this large off the GVL; smaller
+/// bodies go through Ruby's JSON.parse. The cutoff has not been
+/// benchmarked: it only keeps typical API responses on the JSON.parse path.
+const JSON_OFFLOAD_THRESHOLD: usize = 1024 * 1024;
 
 /// Wraps a wreq::Response in a Ruby-accessible type.
 #[magnus::wrap(class = "Wreq::Response", free_immediately)]
@@ -14,6 +22,16 @@ pub struct Response {
     version: String,
     content_length: Option<u64>,
     transfer_size: Option<u64>,
+    body_truncated: bool,
+    /// The body stopped short of its Content-Length (`allow_incomplete: true`).
+    incomplete: bool,
+    /// Unread body of a `stream: true` response; `body` is then empty.
+    stream: Option<BodyStream>,
+    /// Decoded body length: of `body`, or of the file `Client#download`
+    /// wrote; None while streaming.
+    body_size: Option<u64>,
+    /// Bytes sent as the request body.
+    request_body_size: u64,
 }
 
 impl Response {
@@ -26,6 +44,7 @@ impl Response {
         content_length: Option<u64>,
         transfer_size: Option<u64>,
     ) -> Self {
+        let body_size = Some(body.len() as u64);
         Self {
             status,
             headers,
@@ -34,21 +53,90 @@ impl Response {
             version,
             content_length,
             transfer_size,
+            body_truncated: false,
+            incomplete: false,
+            stream: None,
+            body_size,
+            request_body_size: 0,
         }
     }
 
-    fn status(&self) -> u16 {
+    /// Mark the body as cut short by `max_body_size:` with `truncate_body: true`.
+    pub fn with_body_truncated(mut self, truncated: bool) -> Self {
+        self.body_truncated = truncated;
+        self
+    }
+
+    /// Mark the body as ending before its Content-Length.
+    pub fn with_incomplete(mut self, incomplete: bool) -> Self {
+        self.incomplete = incomplete;
+        self
+    }
+
+    /// Record the decoded body size when the body is not held in `body`.
+    pub fn with_body_size(mut self, size: Option<u64>) -> Self {
+        self.body_size = size;
+        self
+    }
+
+    /// Record how many request body bytes were sent.
+    pub fn with_request_body_size(mut self, sent: u64) -> Self {
+        self.request_body_size = sent;
+        self
+    }
+
+    /// Report the decoded body size as `content_length` (`content_length: :decoded`).
+    pub fn with_decoded_content_length(mut self) -> Self {
+        self.content_length = self.body_size;
+        self
+    }
+
+    pub fn status(&self) -> u16 {
         self.status
     }
 
-    fn text(&self) -> Result<String, magnus::Error> {
-        String::from_utf8(self.body.clone()).map_err(|e| generic_error(e))
+    /// The start of the body, lossily decoded, for error messages.
+    pub fn body_excerpt(&self, max_chars: usize) -> String {
+        let text = String::from_utf8_lossy(&self.body);
+        match text.char_indices().nth(max_chars) {
+            Some((end, _)) => format!("{}...", &text[..end]),
+            None => text.into_owned(),
+        }
+    }
+
+    /// The body as a String tagged `Encoding::UTF_8`, whatever
+    /// `Encoding.default_external` is. Raises if the body is not valid UTF-8.
+    fn text(&self) -> Result<RString, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let text = std::str::from_utf8(&self.body)
+            .map_err(|e| with_source(decode_error(), "response body is not valid UTF-8", &e))?;
+        // `str_new` copies the bytes and tags the result UTF-8 explicitly.
+        Ok(ruby.str_new(text))
     }
 
     fn body_bytes(&self) -> Vec<u8> {
         self.body.clone()
     }
 
+    /// resp.decode_body(:gzip)
+    ///
+    /// Decode the stored body with one content-coding (`:gzip`, `:brotli`,
+    /// `:deflate`, or `:zstd`) and return the result as a binary String. The
+    /// stored body is left as is.
+    fn decode_body(&self, encoding: Value) -> Result<RString, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let coding: String = encoding.funcall("to_s", ())?;
+        let coding = coding.to_ascii_lowercase();
+        if !matches!(coding.as_str(), "gzip" | "brotli" | "br" | "deflate" | "zstd") {
+            return Err(magnus::Error::new(
+                decode_error(),
+                format!("unknown encoding '{}' (expected gzip, brotli, deflate, or zstd)", coding),
+            ));
+        }
+        let decoded = crate::decode::decode(&coding, &self.body).map_err(|msg| magnus::Error::new(decode_error(), msg))?;
+        Ok(ruby.str_from_slice(&decoded))
+    }
+
     fn headers(&self) -> Result<RHash, magnus::Error> {
         let ruby = unsafe { Ruby::get_unchecked() };
         let hash = ruby.hash_new();
@@ -84,6 +172,131 @@ impl Response {
         self.transfer_size
     }
 
+    fn body_size(&self) -> Option<u64> {
+        self.body_size
+    }
+
+    fn request_body_size(&self) -> u64 {
+        self.request_body_size
+    }
+
+    /// Attach the unread body of a `stream: true` response.
+    pub fn with_stream(mut self, stream: BodyStream) -> Self {
+        self.stream = Some(stream);
+        self.body_size = None;
+        self
+    }
+
+    /// The unread body of a `stream: true` response.
+    pub fn body_stream(&self) -> Option<&BodyStream> {
+        self.stream.as_ref()
+    }
+
+    /// A buffered copy of this response holding `prefix` as its body, for
+    /// `Client#peek`. `more` marks the body as truncated.
+    pub fn with_prefix(&self, prefix: Vec<u8>, more: bool) -> Self {
+        let body_size = Some(prefix.len() as u64);
+        Response {
+            status: self.status,
+            headers: self.headers.clone(),
+            body: prefix,
+            url: self.url.clone(),
+            version: self.version.clone(),
+            content_length: self.content_length,
+            transfer_size: self.transfer_size,
+            body_truncated: more,
+            incomplete: false,
+            stream: None,
+            body_size,
+            request_body_size: self.request_body_size,
+        }
+    }
+
+    /// A copy of a buffered response, for `cache:`; None while streaming.
+    pub fn snapshot(&self) -> Option<Self> {
+        if self.stream.is_some() {
+            return None;
+        }
+        Some(Response {
+            status: self.status,
+            headers: self.headers.clone(),
+            body: self.body.clone(),
+            url: self.url.clone(),
+            version: self.version.clone(),
+            content_length: self.content_length,
+            transfer_size: self.transfer_size,
+            body_truncated: self.body_truncated,
+            incomplete: self.incomplete,
+            stream: None,
+            body_size: self.body_size,
+            request_body_size: self.request_body_size,
+        })
+    }
+
+    /// True when `Cache-Control: no-store` forbids keeping the response.
+    pub(crate) fn is_no_store(&self) -> bool {
+        self.header_values("cache-control")
+            .flat_map(parse_cache_control)
+            .any(|(name, _)| name == "no-store")
+    }
+
+    fn is_streaming(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// resp.each_chunk { |chunk| ... } or resp.each_chunk (an Enumerator)
+    ///
+    /// Yields the body as binary Strings as they arrive. A buffered response
+    /// yields its whole body as one chunk.
+    fn each_chunk(rb_self: Obj<Self>) -> Result<Value, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        if !ruby.block_given() {
+            return Ok(rb_self.enumeratorize("each_chunk", ()).as_value());
+        }
+        match &rb_self.stream {
+            Some(stream) => {
+                while let Some(chunk) = stream.next_chunk()? {
+                    let _: Value = ruby.yield_value(ruby.str_from_slice(&chunk))?;
+                }
+            }
+            None if !rb_self.body.is_empty() => {
+                let _: Value = ruby.yield_value(ruby.str_from_slice(&rb_self.body))?;
+            }
+            None => {}
+        }
+        Ok(rb_self.as_value())
+    }
+
+    /// resp.read(n = nil)
+    ///
+    /// Reads a `stream: true` body like IO#read: up to `n` bytes (nil reads
+    /// the rest), returning nil (or "" for `read()`) at the end.
+    fn read(&self, args: &[Value]) -> Result<Option<RString>, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let n: Option<usize> = match args.first() {
+            Some(v) if !v.is_nil() => Some(TryConvert::try_convert(*v)?),
+            _ => None,
+        };
+        let stream = self
+            .stream
+            .as_ref()
+            .ok_or_else(|| generic_error("read requires a response made with stream: true"))?;
+        Ok(stream.read(n)?.map(|buf| ruby.str_from_slice(&buf)))
+    }
+
+    /// The body was cut short, by a limit or the connection.
+    pub(crate) fn is_partial(&self) -> bool {
+        self.body_truncated || self.incomplete
+    }
+
+    fn is_body_truncated(&self) -> bool {
+        self.body_truncated
+    }
+
+    fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
     fn is_success(&self) -> bool {
         (200..300).contains(&self.status)
     }
@@ -96,15 +309,364 @@ impl Response {
         (400..500).contains(&self.status)
     }
 
+    /// Return self for statuses below 400; otherwise raise the matching
+    /// `Wreq::HTTPStatusError` subclass, which carries this response.
+    fn raise_for_status(rb_self: Obj<Self>) -> Result<Obj<Self>, magnus::Error> {
+        if rb_self.status < 400 {
+            return Ok(rb_self);
+        }
+        let reason = http::StatusCode::from_u16(rb_self.status)
+            .ok()
+            .and_then(|s| s.canonical_reason())
+            .map(|r| format!(" {}", r))
+            .unwrap_or_default();
+        let msg = format!("HTTP {}{} for {}: {}", rb_self.status, reason, rb_self.url, rb_self.body_excerpt(200));
+        Err(with_response(http_status_error(rb_self.status), msg, rb_self.as_value()))
+    }
+
     fn is_server_error(&self) -> bool {
         (500..600).contains(&self.status)
     }
 
-    fn json(&self) -> Result<Value, magnus::Error> {
+    /// resp.json or resp.json(symbolize_keys: true)
+    fn json(&self, args: &[Value]) -> Result<Value, magnus::Error> {
+        let symbolize = match args.first().and_then(|v| RHash::from_value(*v)) {
+            Some(opts) => hash_get_bool(&opts, "symbolize_keys")?.unwrap_or(false),
+            None => false,
+        };
+        self.parse_json(symbolize)
+    }
+
+    /// resp.json! or resp.json!(symbolize_keys: true)
+    ///
+    /// Like `json`, but raises `Wreq::DecodeError` unless the Content-Type
+    /// is JSON (see `json?`), so an HTML error page fails fast instead of
+    /// with a parser error, or worse, parsing.
+    fn json_strict(&self, args: &[Value]) -> Result<Value, magnus::Error> {
+        if !self.is_json() {
+            return Err(magnus::Error::new(
+                decode_error(),
+                format!(
+                    "expected a JSON content type, got '{}' (status {}): {}",
+                    self.content_type().as_deref().unwrap_or("none"),
+                    self.status,
+                    self.body_excerpt(100)
+                ),
+            ));
+        }
+        self.json(args)
+    }
+
+    /// Parse the body as JSON. Bodies of `JSON_OFFLOAD_THRESHOLD` bytes or
+    /// more are parsed with the GVL released so other Ruby threads keep
+    /// running; only building the Ruby objects needs the GVL. Invalid UTF-8
+    /// raises `Wreq::DecodeError` whatever the body's size.
+    pub fn parse_json(&self, symbolize_keys: bool) -> Result<Value, magnus::Error> {
         let ruby = unsafe { Ruby::get_unchecked() };
-        let text = self.text()?;
         let json_module: Value = ruby.class_object().const_get("JSON")?;
-        json_module.funcall("parse", (text,))
+        let text = std::str::from_utf8(&self.body)
+            .map_err(|e| with_source(decode_error(), "response body is not valid UTF-8", &e))?;
+        if self.body.len() < JSON_OFFLOAD_THRESHOLD {
+            let opts = ruby.hash_new();
+            opts.aset(ruby.to_symbol("symbolize_names"), symbolize_keys)?;
+            return json_module.funcall("parse", (ruby.str_new(text), opts));
+        }
+        let body = &self.body;
+        let parsed = unsafe { without_gvl(|_| serde_json::from_slice::<serde_json::Value>(body)) };
+        match parsed {
+            Ok(value) => json_to_ruby(&ruby, &value, symbolize_keys),
+            Err(e) => {
+                let parser_error: ExceptionClass = json_module.funcall("const_get", ("ParserError",))?;
+                Err(magnus::Error::new(parser_error, e.to_string()))
+            }
+        }
+    }
+
+    /// Return the first value of a header (case-insensitive name), if present.
+    pub(crate) fn header_value(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The media type from `Content-Type`, lowercased and without
+    /// parameters (e.g. "application/json"), or nil if absent.
+    fn content_type(&self) -> Option<String> {
+        self.header_value("content-type")
+            .map(|ct| ct.split(';').next().unwrap_or("").trim().to_ascii_lowercase())
+            .filter(|m| !m.is_empty())
+    }
+
+    /// The `Content-Encoding` still applied to the body (every value, joined
+    /// with ", "), or nil. It is removed once a body has been decoded, so a
+    /// value here means decoding was skipped: a disabled or unsupported
+    /// coding such as the shared-dictionary `dcb`/`dcz`.
+    fn content_encoding(&self) -> Option<String> {
+        self.joined_header("content-encoding")
+    }
+
+    /// The `Transfer-Encoding` header as received, e.g. "gzip, chunked", or nil.
+    fn transfer_encoding(&self) -> Option<String> {
+        self.joined_header("transfer-encoding")
+    }
+
+    fn joined_header(&self, name: &str) -> Option<String> {
+        let values: Vec<&str> = self.header_values(name).collect();
+        (!values.is_empty()).then(|| values.join(", "))
+    }
+
+    /// The `charset` parameter of `Content-Type`, or nil if absent.
+    fn charset(&self) -> Option<String> {
+        let ct = self.header_value("content-type")?;
+        ct.split(';').skip(1).find_map(|param| {
+            let (k, v) = param.split_once('=')?;
+            k.trim()
+                .eq_ignore_ascii_case("charset")
+                .then(|| v.trim().trim_matches('"').to_owned())
+        })
+    }
+
+    /// Return self if the media type is `expected` (ignoring parameters
+    /// and case), else raise `Wreq::DecodeError`.
+    fn assert_content_type(rb_self: Obj<Self>, expected: String) -> Result<Obj<Self>, magnus::Error> {
+        let wanted = media_type(&expected);
+        match rb_self.content_type() {
+            Some(actual) if actual == wanted => Ok(rb_self),
+            actual => Err(magnus::Error::new(decode_error(), rb_self.content_type_mismatch(&wanted, actual))),
+        }
+    }
+
+    /// Like `assert_content_type`, but an `expected` ending in "/" (e.g.
+    /// "application/") matches any subtype, and a mismatch raises
+    /// `Wreq::Error`.
+    fn assert_content_type_bang(rb_self: Obj<Self>, expected: String) -> Result<Obj<Self>, magnus::Error> {
+        let wanted = media_type(&expected);
+        match rb_self.content_type() {
+            Some(actual) if actual == wanted || (wanted.ends_with('/') && actual.starts_with(&wanted)) => Ok(rb_self),
+            actual => Err(generic_error(rb_self.content_type_mismatch(&wanted, actual))),
+        }
+    }
+
+    fn content_type_mismatch(&self, wanted: &str, actual: Option<String>) -> String {
+        format!(
+            "expected content type '{}', got '{}' (status {}): {}",
+            wanted,
+            actual.as_deref().unwrap_or("none"),
+            self.status,
+            self.body_excerpt(100)
+        )
+    }
+
+    /// All values of a header (case-insensitive name), in received order.
+    pub(crate) fn header_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Parse every `WWW-Authenticate` header into `{ scheme:, params: {} }`
+    /// hashes. Parameter names are lowercased; a bare token68 credential
+    /// (e.g. `Negotiate abc==`) is returned under the `"token68"` key.
+    fn auth_challenges(&self) -> Result<RArray, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let ary = ruby.ary_new();
+        for value in self.header_values("www-authenticate") {
+            for (scheme, params) in parse_auth_challenges(value) {
+                let hash = ruby.hash_new();
+                let params_hash = ruby.hash_new();
+                for (k, v) in params {
+                    params_hash.aset(k, v)?;
+                }
+                hash.aset(ruby.to_symbol("scheme"), scheme)?;
+                hash.aset(ruby.to_symbol("params"), params_hash)?;
+                ary.push(hash)?;
+            }
+        }
+        Ok(ary)
+    }
+
+    /// Targets of every `Link` header (RFC 8288) keyed by `rel`, e.g.
+    /// `{ "next" => "...", "last" => "..." }`. A link with several
+    /// relations (`rel="next last"`) appears under each; the first link for a
+    /// relation wins. Relative targets are resolved against the response URL.
+    fn links(&self) -> Result<RHash, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let hash = ruby.hash_new();
+        let base = url::Url::parse(&self.url).ok();
+        for value in self.header_values("link") {
+            for (target, rels) in parse_link_header(value) {
+                let target = match base.as_ref().and_then(|b| b.join(&target).ok()) {
+                    Some(url) => url.to_string(),
+                    None => target,
+                };
+                for rel in rels {
+                    if hash.get(rel.as_str()).is_none() {
+                        hash.aset(rel, target.as_str())?;
+                    }
+                }
+            }
+        }
+        Ok(hash)
+    }
+
+    /// resp.filename or resp.filename(fallback_to_url: true)
+    ///
+    /// The file name suggested by `Content-Disposition` (`filename*` wins
+    /// over `filename`), reduced to its last path component. With
+    /// `fallback_to_url: true`, the last segment of the URL path is used
+    /// when the header names no file.
+    fn filename(&self, args: &[Value]) -> Result<Option<String>, magnus::Error> {
+        let fallback = match args.first() {
+            Some(v) => hash_get_bool(&RHash::try_convert(*v)?, "fallback_to_url")?.unwrap_or(false),
+            None => false,
+        };
+        let from_header = self
+            .header_value("content-disposition")
+            .and_then(parse_disposition_filename);
+        Ok(match from_header {
+            Some(name) => Some(name),
+            None if fallback => url_filename(&self.url),
+            None => None,
+        })
+    }
+
+    /// `Cache-Control` (all occurrences) as `{ max_age:, s_maxage:,
+    /// stale_while_revalidate:, stale_if_error:, no_store:, no_cache:,
+    /// private:, public:, must_revalidate:, proxy_revalidate:,
+    /// no_transform:, immutable: }`, or nil without the header. Durations
+    /// are Integer seconds (nil when absent or not a number); the rest are
+    /// booleans. Unknown directives are ignored and the first occurrence of
+    /// a repeated one wins.
+    fn cache_control(&self) -> Result<Option<RHash>, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let mut directives: Vec<(String, Option<String>)> = Vec::new();
+        let mut present = false;
+        for value in self.header_values("cache-control") {
+            present = true;
+            for (name, arg) in parse_cache_control(value) {
+                if !directives.iter().any(|(n, _)| *n == name) {
+                    directives.push((name, arg));
+                }
+            }
+        }
+        if !present {
+            return Ok(None);
+        }
+        let hash = ruby.hash_new();
+        for name in ["max-age", "s-maxage", "stale-while-revalidate", "stale-if-error"] {
+            let secs = directives
+                .iter()
+                .find(|(n, _)| n == name)
+                .and_then(|(_, arg)| arg.as_deref()?.parse::<u64>().ok());
+            hash.aset(ruby.to_symbol(name.replace('-', "_")), secs)?;
+        }
+        for name in [
+            "no-store",
+            "no-cache",
+            "private",
+            "public",
+            "must-revalidate",
+            "proxy-revalidate",
+            "no-transform",
+            "immutable",
+        ] {
+            let set = directives.iter().any(|(n, _)| n == name);
+            hash.aset(ruby.to_symbol(name.replace('-', "_")), set)?;
+        }
+        Ok(Some(hash))
+    }
+
+    /// resp.server_timing => [{ name: "db", dur: 53.0, desc: nil }, ...]
+    ///
+    /// Metrics from every `Server-Timing` header, in order. `dur` is a Float
+    /// in milliseconds and `desc` a String; either is nil when the metric
+    /// has none. Empty when the header is absent.
+    fn server_timing(&self) -> Result<RArray, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let metrics = ruby.ary_new();
+        for value in self.header_values("server-timing") {
+            for metric in parse_server_timing(value) {
+                let hash = ruby.hash_new();
+                hash.aset(ruby.to_symbol("name"), metric.name)?;
+                hash.aset(ruby.to_symbol("dur"), metric.dur)?;
+                hash.aset(ruby.to_symbol("desc"), metric.desc)?;
+                metrics.push(hash)?;
+            }
+        }
+        Ok(metrics)
+    }
+
+    /// `Expires` as a UTC Time, or nil when absent or not an HTTP date
+    /// (e.g. the common `Expires: 0`).
+    fn expires(&self) -> Result<Option<Value>, magnus::Error> {
+        self.http_date("expires")
+    }
+
+    /// `Date` as a UTC Time, or nil when absent or not an HTTP date.
+    fn date(&self) -> Result<Option<Value>, magnus::Error> {
+        self.http_date("date")
+    }
+
+    /// resp.header_int("Retry-After") => 120
+    ///
+    /// The first value of header `name` as an Integer, or nil when absent or
+    /// not a (possibly signed) decimal number.
+    fn header_int(&self, name: String) -> Option<i64> {
+        self.header_value(&name)?.trim().parse().ok()
+    }
+
+    /// resp.header_time("Last-Modified") => 2026-10-21 07:28:00 UTC
+    ///
+    /// The first value of header `name` parsed as an HTTP date (IMF-fixdate,
+    /// RFC 850, or asctime), as a UTC Time; nil when absent or malformed.
+    fn header_time(&self, name: String) -> Result<Option<Value>, magnus::Error> {
+        self.http_date(&name)
+    }
+
+    fn http_date(&self, name: &str) -> Result<Option<Value>, magnus::Error> {
+        let Some(time) = self.header_value(name).and_then(|v| httpdate::parse_http_date(v.trim()).ok()) else {
+            return Ok(None);
+        };
+        let secs = match time.duration_since(std::time::UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        };
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let time: Value = ruby.class_time().funcall("at", (secs,))?;
+        Ok(Some(time.funcall("utc", ())?))
+    }
+
+    /// True for `application/json` and any `+json` media type
+    /// (e.g. "application/vnd.api+json").
+    fn is_json(&self) -> bool {
+        matches!(self.content_type(), Some(ct) if ct == "application/json" || ct.ends_with("+json"))
+    }
+
+    fn is_html(&self) -> bool {
+        matches!(
+            self.content_type().as_deref(),
+            Some("text/html") | Some("application/xhtml+xml")
+        )
+    }
+
+    /// Parse `<meta http-equiv="refresh" content="5; url=...">` from an HTML
+    /// body. Returns `{ delay:, url: }` (url may be nil) or nil when absent
+    /// or the response is not HTML. The URL is returned as written.
+    fn meta_refresh(&self) -> Result<Option<RHash>, magnus::Error> {
+        if !self.is_html() {
+            return Ok(None);
+        }
+        let body = String::from_utf8_lossy(&self.body);
+        let Some((delay, url)) = find_meta_refresh(&body) else {
+            return Ok(None);
+        };
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let hash = ruby.hash_new();
+        hash.aset(ruby.to_symbol("delay"), delay)?;
+        hash.aset(ruby.to_symbol("url"), url)?;
+        Ok(Some(hash))
     }
 
     fn inspect(&self) -> String {
@@ -114,28 +676,505 @@ impl Response {
         )
     }
 
-    fn to_s(&self) -> Result<String, magnus::Error> {
+    fn to_s(&self) -> Result<RString, magnus::Error> {
         self.text()
     }
 }
 
+/// `expected`'s media type, lowercased and without parameters.
+fn media_type(expected: &str) -> String {
+    expected.split(';').next().unwrap_or("").trim().to_ascii_lowercase()
+}
+
+/// Build Ruby objects from a parsed JSON value, matching JSON.parse:
+/// objects become Hashes with String (or Symbol) keys, integers Integers,
+/// other numbers Floats.
+fn json_to_ruby(ruby: &Ruby, value: &serde_json::Value, symbolize_keys: bool) -> Result<Value, magnus::Error> {
+    Ok(match value {
+        serde_json::Value::Null => ruby.qnil().as_value(),
+        serde_json::Value::Bool(b) => b.into_value_with(ruby),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_value_with(ruby),
+            (None, Some(u)) => u.into_value_with(ruby),
+            // Kept as written (`arbitrary_precision`): integers past u64
+            // become Bignums, as with JSON.parse, rather than lossy Floats.
+            _ => {
+                let text = n.to_string();
+                let integral = !text.contains(['.', 'e', 'E']);
+                ruby.str_new(&text).funcall(if integral { "to_i" } else { "to_f" }, ())?
+            }
+        },
+        serde_json::Value::String(s) => ruby.str_new(s).as_value(),
+        serde_json::Value::Array(items) => {
+            let ary = ruby.ary_new_capa(items.len());
+            for item in items {
+                ary.push(json_to_ruby(ruby, item, symbolize_keys)?)?;
+            }
+            ary.as_value()
+        }
+        serde_json::Value::Object(map) => {
+            let hash = ruby.hash_new();
+            for (k, v) in map {
+                let value = json_to_ruby(ruby, v, symbolize_keys)?;
+                if symbolize_keys {
+                    hash.aset(ruby.to_symbol(k), value)?;
+                } else {
+                    hash.aset(ruby.str_new(k), value)?;
+                }
+            }
+            hash.as_value()
+        }
+    })
+}
+
+type Challenge = (String, Vec<(String, String)>);
+
+/// Parse a `WWW-Authenticate` value (RFC 7235), which may hold several
+/// comma-separated challenges, each with auth-params or a token68.
+fn parse_auth_challenges(input: &str) -> Vec<Challenge> {
+    let b = input.as_bytes();
+    let mut i = 0;
+    let mut out: Vec<Challenge> = Vec::new();
+    let is_ws = |c: u8| c == b' ' || c == b'\t';
+    let is_tchar = |c: u8| !is_ws(c) && c != b',' && c != b'=' && c != b'"';
+    let read_token = |i: &mut usize| {
+        let start = *i;
+        while *i < b.len() && is_tchar(b[*i]) {
+            *i += 1;
+        }
+        input[start..*i].to_owned()
+    };
+    let skip_ws = |i: &mut usize| {
+        while *i < b.len() && is_ws(b[*i]) {
+            *i += 1;
+        }
+    };
+
+    while i < b.len() {
+        while i < b.len() && (is_ws(b[i]) || b[i] == b',') {
+            i += 1;
+        }
+        let scheme = read_token(&mut i);
+        if scheme.is_empty() {
+            i += 1;
+            continue;
+        }
+        let mut params = Vec::new();
+        // True until a comma or parameter follows the scheme; only then may
+        // a bare token be a token68 rather than the next challenge's scheme.
+        let mut after_scheme = true;
+        loop {
+            skip_ws(&mut i);
+            if i < b.len() && b[i] == b',' {
+                i += 1;
+                after_scheme = false;
+                continue;
+            }
+            let save = i;
+            let name = read_token(&mut i);
+            if name.is_empty() {
+                break;
+            }
+            skip_ws(&mut i);
+            if i < b.len() && b[i] == b'=' {
+                let mut j = i;
+                while j < b.len() && b[j] == b'=' {
+                    j += 1;
+                }
+                let mut k = j;
+                skip_ws(&mut k);
+                if j - i == 1 && k < b.len() && b[k] != b',' {
+                    i = k;
+                    let value = if b[i] == b'"' {
+                        read_quoted(input, &mut i)
+                    } else {
+                        read_token(&mut i)
+                    };
+                    params.push((name.to_ascii_lowercase(), value));
+                } else if after_scheme {
+                    params.push(("token68".to_owned(), format!("{}{}", name, &input[i..j])));
+                    i = j;
+                } else {
+                    i = save;
+                    break;
+                }
+            } else if after_scheme && (i >= b.len() || b[i] == b',') {
+                params.push(("token68".to_owned(), name));
+            } else {
+                i = save;
+                break;
+            }
+            after_scheme = false;
+        }
+        out.push((scheme, params));
+    }
+    out
+}
+
+/// Read a quoted-string starting at `input[*i] == '"'`, unescaping `\x`.
+fn read_quoted(input: &str, i: &mut usize) -> String {
+    let mut out = String::new();
+    let mut chars = input[*i + 1..].char_indices();
+    let base = *i + 1;
+    *i = input.len();
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some((_, escaped)) = chars.next() {
+                    out.push(escaped);
+                }
+            }
+            '"' => {
+                *i = base + pos + 1;
+                break;
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+struct ServerTiming {
+    name: String,
+    dur: Option<f64>,
+    desc: Option<String>,
+}
+
+/// Split a `Server-Timing` value into metrics:
+/// `cache;desc="Cache Read";dur=23.2, db;dur=53, app`. Parameter names are
+/// case-insensitive and the first of a repeated one wins; a `dur` that is
+/// not a number is ignored.
+fn parse_server_timing(value: &str) -> Vec<ServerTiming> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < value.len() {
+        let rest = &value[i..];
+        let end = rest.find([',', ';']).unwrap_or(rest.len());
+        let mut metric = ServerTiming { name: rest[..end].trim().to_owned(), dur: None, desc: None };
+        i += end;
+        let (mut seen_dur, mut seen_desc) = (false, false);
+        while value[i..].starts_with(';') {
+            i += 1;
+            let rest = &value[i..];
+            let end = rest.find([',', ';', '=']).unwrap_or(rest.len());
+            let param = rest[..end].trim().to_ascii_lowercase();
+            i += end;
+            let mut arg = None;
+            if value[i..].starts_with('=') {
+                i += 1;
+                i += value[i..].len() - value[i..].trim_start().len();
+                if value[i..].starts_with('"') {
+                    arg = Some(read_quoted(value, &mut i));
+                }
+                let end = value[i..].find([',', ';']).unwrap_or(value.len() - i);
+                if arg.is_none() {
+                    arg = Some(value[i..i + end].trim().to_owned());
+                }
+                i += end;
+            }
+            match param.as_str() {
+                "dur" if !seen_dur => {
+                    seen_dur = true;
+                    metric.dur = arg.and_then(|a| a.parse::<f64>().ok()).filter(|d| d.is_finite());
+                }
+                "desc" if !seen_desc => {
+                    seen_desc = true;
+                    metric.desc = arg;
+                }
+                _ => {}
+            }
+        }
+        // Skip the separating comma.
+        i += 1;
+        if !metric.name.is_empty() {
+            out.push(metric);
+        }
+    }
+    out
+}
+
+/// Split a `Cache-Control` value into lowercased directive names and their
+/// arguments (unquoted): `max-age=60, no-cache="Set-Cookie", private`.
+fn parse_cache_control(value: &str) -> Vec<(String, Option<String>)> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < value.len() {
+        let rest = &value[i..];
+        let end = rest.find([',', '=']).unwrap_or(rest.len());
+        let name = rest[..end].trim().to_ascii_lowercase();
+        i += end;
+        let mut arg = None;
+        if value[i..].starts_with('=') {
+            i += 1;
+            let ws = value[i..].len() - value[i..].trim_start().len();
+            i += ws;
+            if value[i..].starts_with('"') {
+                arg = Some(read_quoted(value, &mut i));
+            }
+            let end = value[i..].find(',').unwrap_or(value.len() - i);
+            if arg.is_none() {
+                arg = Some(value[i..i + end].trim().to_owned());
+            }
+            i += end;
+        }
+        // Skip the separating comma.
+        i += 1;
+        if !name.is_empty() {
+            out.push((name, arg));
+        }
+    }
+    out
+}
+
+/// Split a `Link` header value into (target, rels) pairs:
+/// `<https://a/?page=2>; rel="next", <https://a/?page=9>; rel=last`.
+/// Commas inside `<...>` or quoted parameters do not separate links. Relation
+/// types are lowercased; links without `rel` are dropped.
+fn parse_link_header(value: &str) -> Vec<(String, Vec<String>)> {
+    let mut links = Vec::new();
+    let mut i = 0;
+    while let Some(start) = value[i..].find('<') {
+        let start = i + start + 1;
+        let Some(len) = value[start..].find('>') else { break };
+        let target = value[start..start + len].trim().to_owned();
+        i = start + len + 1;
+        let mut rels = Vec::new();
+        // Parameters run until the comma that ends this link.
+        loop {
+            while value[i..].starts_with([' ', '\t']) {
+                i += 1;
+            }
+            if !value[i..].starts_with(';') {
+                break;
+            }
+            i += 1;
+            let rest = &value[i..];
+            let name_end = rest.find(['=', ';', ',']).unwrap_or(rest.len());
+            let name = rest[..name_end].trim().to_ascii_lowercase();
+            i += name_end;
+            if !value[i..].starts_with('=') {
+                continue;
+            }
+            i += 1;
+            while value[i..].starts_with([' ', '\t']) {
+                i += 1;
+            }
+            let param = if value[i..].starts_with('"') {
+                read_quoted(value, &mut i)
+            } else {
+                let end = value[i..].find([';', ',']).map_or(value.len(), |p| i + p);
+                let token = value[i..end].trim().to_owned();
+                i = end;
+                token
+            };
+            if name == "rel" && rels.is_empty() {
+                rels = param.split_whitespace().map(|r| r.to_ascii_lowercase()).collect();
+            }
+        }
+        if !rels.is_empty() {
+            links.push((target, rels));
+        }
+    }
+    links
+}
+
+/// The `filename*` (RFC 6266 / RFC 5987) or `filename` parameter of a
+/// Content-Disposition value, stripped of any directory part.
+fn parse_disposition_filename(value: &str) -> Option<String> {
+    let mut plain = None;
+    let mut extended = None;
+    let mut i = match value.find(';') {
+        Some(pos) => pos + 1,
+        None => return None,
+    };
+    while i < value.len() {
+        let rest = &value[i..];
+        let Some(eq) = rest.find('=') else { break };
+        // A bare parameter without `=` (`attachment; foo; filename=x`) is skipped.
+        let name = rest[..eq].rsplit(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        i += eq + 1;
+        while value[i..].starts_with([' ', '\t']) {
+            i += 1;
+        }
+        let param = if value[i..].starts_with('"') {
+            read_quoted(value, &mut i)
+        } else {
+            let end = value[i..].find(';').map_or(value.len(), |p| i + p);
+            let token = value[i..end].trim().to_owned();
+            i = end;
+            token
+        };
+        if let Some(next) = value[i..].find(';') {
+            i += next + 1;
+        } else {
+            i = value.len();
+        }
+        match name.as_str() {
+            "filename*" => extended = decode_ext_value(&param).or(extended),
+            "filename" => plain = Some(param),
+            _ => {}
+        }
+    }
+    extended
+        .or(plain)
+        .map(|name| name.rsplit(['/', '\\']).next().unwrap_or("").trim().to_owned())
+        .filter(|name| !name.is_empty() && name != "." && name != "..")
+}
+
+/// Decode an RFC 5987 ext-value: `charset'language'percent-encoded`.
+/// UTF-8 and ISO-8859-1 are understood; other charsets yield None.
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?.trim();
+    let _language = parts.next()?;
+    let bytes: Vec<u8> = percent_encoding::percent_decode_str(parts.next()?).collect();
+    if charset.eq_ignore_ascii_case("utf-8") {
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(bytes.iter().map(|&b| b as char).collect())
+    } else {
+        None
+    }
+}
+
+/// The last non-empty segment of a URL's path, percent-decoded.
+fn url_filename(url: &str) -> Option<String> {
+    let without_query = url.split(['?', '#']).next().unwrap_or("");
+    let path = match without_query.find("://") {
+        Some(pos) => {
+            let after = &without_query[pos + 3..];
+            &after[after.find('/')?..]
+        }
+        None => without_query,
+    };
+    let segment = path.rsplit('/').next().filter(|s| !s.is_empty())?;
+    let name = percent_encoding::percent_decode_str(segment).decode_utf8_lossy().into_owned();
+    (!name.is_empty() && name != "." && name != "..").then_some(name)
+}
+
+/// Find the first meta refresh directive in an HTML document.
+fn find_meta_refresh(html: &str) -> Option<(u64, Option<String>)> {
+    // ASCII lowercasing keeps byte offsets identical to the original.
+    let lower = html.to_ascii_lowercase();
+    let mut pos = 0;
+    while let Some(start) = lower[pos..].find("<meta") {
+        let tag_start = pos + start + "<meta".len();
+        let tag_end = lower[tag_start..].find('>').map(|i| tag_start + i)?;
+        let attrs = parse_tag_attributes(&html[tag_start..tag_end]);
+        let is_refresh = attrs
+            .iter()
+            .any(|(k, v)| k == "http-equiv" && v.eq_ignore_ascii_case("refresh"));
+        if is_refresh {
+            if let Some((_, content)) = attrs.iter().find(|(k, _)| k == "content") {
+                return parse_refresh_content(content);
+            }
+        }
+        pos = tag_end;
+    }
+    None
+}
+
+/// Parse `name="value"` / `name='value'` / `name=value` pairs from the inside
+/// of an HTML tag. Attribute names are lowercased.
+fn parse_tag_attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    let mut chars = tag.char_indices().peekable();
+    loop {
+        while chars.next_if(|(_, c)| c.is_whitespace() || *c == '/').is_some() {}
+        let Some(&(name_start, _)) = chars.peek() else { break };
+        while chars.next_if(|(_, c)| !c.is_whitespace() && *c != '=').is_some() {}
+        let name_end = chars.peek().map(|(i, _)| *i).unwrap_or(tag.len());
+        let name = tag[name_start..name_end].to_ascii_lowercase();
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        if chars.next_if(|(_, c)| *c == '=').is_none() {
+            attrs.push((name, String::new()));
+            continue;
+        }
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let value = match chars.peek().copied() {
+            Some((i, q)) if q == '"' || q == '\'' => {
+                chars.next();
+                let end = tag[i + 1..].find(q).map(|e| i + 1 + e).unwrap_or(tag.len());
+                while chars.next_if(|(j, _)| *j <= end).is_some() {}
+                &tag[i + 1..end]
+            }
+            Some((i, _)) => {
+                while chars.next_if(|(_, c)| !c.is_whitespace()).is_some() {}
+                let end = chars.peek().map(|(j, _)| *j).unwrap_or(tag.len());
+                &tag[i..end]
+            }
+            None => "",
+        };
+        attrs.push((name, value.to_owned()));
+    }
+    attrs
+}
+
+/// Parse a refresh `content` value such as `5; url='/next'`.
+fn parse_refresh_content(content: &str) -> Option<(u64, Option<String>)> {
+    let content = content.trim();
+    let (delay_part, rest) = match content.find(|c: char| c == ';' || c == ',') {
+        Some(i) => (&content[..i], Some(&content[i + 1..])),
+        None => (content, None),
+    };
+    let delay: u64 = delay_part.trim().split('.').next()?.parse().ok()?;
+    let url = rest.and_then(|r| {
+        let r = r.trim();
+        let r = if r.len() >= 4 && r[..4].eq_ignore_ascii_case("url=") {
+            &r[4..]
+        } else {
+            r
+        };
+        let r = r.trim().trim_matches(|c: char| c == '"' || c == '\'').trim();
+        if r.is_empty() { None } else { Some(r.to_owned()) }
+    });
+    Some((delay, url))
+}
+
 pub fn init(ruby: &magnus::Ruby, module: &magnus::RModule) -> Result<(), magnus::Error> {
     let class = module.define_class("Response", ruby.class_object())?;
     class.define_method("status", method!(Response::status, 0))?;
     class.define_method("code", method!(Response::status, 0))?;
     class.define_method("text", method!(Response::text, 0))?;
+    class.define_method("decode_body", method!(Response::decode_body, 1))?;
     class.define_method("body", method!(Response::text, 0))?;
     class.define_method("body_bytes", method!(Response::body_bytes, 0))?;
     class.define_method("headers", method!(Response::headers, 0))?;
     class.define_method("url", method!(Response::url, 0))?;
     class.define_method("version", method!(Response::http_version, 0))?;
     class.define_method("content_length", method!(Response::content_length, 0))?;
+    class.define_method("body_size", method!(Response::body_size, 0))?;
+    class.define_method("request_body_size", method!(Response::request_body_size, 0))?;
     class.define_method("transfer_size", method!(Response::transfer_size, 0))?;
+    class.define_method("streaming?", method!(Response::is_streaming, 0))?;
+    class.define_method("each_chunk", method!(Response::each_chunk, 0))?;
+    class.define_method("read", method!(Response::read, -1))?;
+    class.define_method("body_truncated?", method!(Response::is_body_truncated, 0))?;
+    class.define_method("incomplete?", method!(Response::is_incomplete, 0))?;
     class.define_method("success?", method!(Response::is_success, 0))?;
     class.define_method("redirect?", method!(Response::is_redirect, 0))?;
     class.define_method("client_error?", method!(Response::is_client_error, 0))?;
     class.define_method("server_error?", method!(Response::is_server_error, 0))?;
-    class.define_method("json", method!(Response::json, 0))?;
+    class.define_method("json", method!(Response::json, -1))?;
+    class.define_method("json!", method!(Response::json_strict, -1))?;
+    class.define_method("content_type", method!(Response::content_type, 0))?;
+    class.define_method("json?", method!(Response::is_json, 0))?;
+    class.define_method("content_encoding", method!(Response::content_encoding, 0))?;
+    class.define_method("transfer_encoding", method!(Response::transfer_encoding, 0))?;
+    class.define_method("raise_for_status", method!(Response::raise_for_status, 0))?;
+    class.define_method("charset", method!(Response::charset, 0))?;
+    class.define_method("assert_content_type", method!(Response::assert_content_type, 1))?;
+    class.define_method("assert_content_type!", method!(Response::assert_content_type_bang, 1))?;
+    class.define_method("auth_challenges", method!(Response::auth_challenges, 0))?;
+    class.define_method("meta_refresh", method!(Response::meta_refresh, 0))?;
+    class.define_method("links", method!(Response::links, 0))?;
+    class.define_method("cache_control", method!(Response::cache_control, 0))?;
+    class.define_method("server_timing", method!(Response::server_timing, 0))?;
+    class.define_method("expires", method!(Response::expires, 0))?;
+    class.define_method("date", method!(Response::date, 0))?;
+    class.define_method("header_int", method!(Response::header_int, 1))?;
+    class.define_method("header_time", method!(Response::header_time, 1))?;
+    class.define_method("filename", method!(Response::filename, -1))?;
     class.define_method("inspect", method!(Response::inspect, 0))?;
     class.define_method("to_s", method!(Response::to_s, 0))?;
     Ok(())
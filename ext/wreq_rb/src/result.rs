@@ -0,0 +1,75 @@
+use magnus::{
+    gc::Marker, method, prelude::*, typed_data::Obj, value::Opaque, DataTypeFunctions, Exception,
+    Module, Ruby,
+};
+
+use crate::error::wreq_error;
+use crate::response::Response;
+
+/// Outcome of a `Client#try_*` call: a response or the `Wreq::Error` that
+/// would otherwise have been raised.
+#[magnus::wrap(class = "Wreq::Result", free_immediately, mark)]
+pub struct RequestResult {
+    response: Option<Opaque<Obj<Response>>>,
+    error: Option<Opaque<Exception>>,
+}
+
+impl DataTypeFunctions for RequestResult {
+    fn mark(&self, marker: &Marker) {
+        if let Some(resp) = self.response {
+            marker.mark(resp);
+        }
+        if let Some(err) = self.error {
+            marker.mark(err);
+        }
+    }
+}
+
+impl RequestResult {
+    /// Capture `Wreq::Error`s; anything else (argument errors, interrupts,
+    /// exceptions raised by hooks) still propagates.
+    pub fn capture(outcome: Result<Obj<Response>, magnus::Error>) -> Result<Self, magnus::Error> {
+        match outcome {
+            Ok(resp) => Ok(RequestResult { response: Some(resp.into()), error: None }),
+            Err(e) if e.is_kind_of(wreq_error()) => {
+                let exc = e.value().and_then(Exception::from_value).ok_or(e)?;
+                Ok(RequestResult { response: None, error: Some(exc.into()) })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn is_ok(&self) -> bool {
+        self.response.is_some()
+    }
+
+    fn response(&self) -> Option<Obj<Response>> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        self.response.map(|r| ruby.get_inner(r))
+    }
+
+    fn error(&self) -> Option<Exception> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        self.error.map(|e| ruby.get_inner(e))
+    }
+
+    fn inspect(&self) -> Result<String, magnus::Error> {
+        match (self.response(), self.error()) {
+            (Some(resp), _) => Ok(format!("#<Wreq::Result ok status={}>", resp.status())),
+            (None, Some(err)) => {
+                let msg: String = err.funcall("message", ())?;
+                Ok(format!("#<Wreq::Result error={:?}>", msg))
+            }
+            (None, None) => Ok("#<Wreq::Result>".to_owned()),
+        }
+    }
+}
+
+pub fn init(ruby: &Ruby, module: &magnus::RModule) -> Result<(), magnus::Error> {
+    let class = module.define_class("Result", ruby.class_object())?;
+    class.define_method("ok?", method!(RequestResult::is_ok, 0))?;
+    class.define_method("response", method!(RequestResult::response, 0))?;
+    class.define_method("error", method!(RequestResult::error, 0))?;
+    class.define_method("inspect", method!(RequestResult::inspect, 0))?;
+    Ok(())
+}
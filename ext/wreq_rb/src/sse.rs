@@ -0,0 +1,128 @@
+/// One dispatched server-sent event.
+pub struct Event {
+    /// The `event:` field, "message" when the event named none.
+    pub event: String,
+    pub data: String,
+    /// The last event id seen on the stream when the event was dispatched.
+    pub id: Option<String>,
+}
+
+/// Incremental `text/event-stream` parser following the EventSource
+/// processing model: lines end in CRLF, LF, or CR; a blank line dispatches
+/// the buffered event; `id:` and `retry:` persist across reconnections.
+#[derive(Default)]
+pub struct Parser {
+    /// Bytes of an incomplete line.
+    buf: Vec<u8>,
+    data: String,
+    has_data: bool,
+    event: String,
+    last_id: Option<String>,
+    retry_ms: Option<u64>,
+    /// Whether the start of the current connection's stream has been seen,
+    /// so a leading BOM is stripped once.
+    started: bool,
+}
+
+impl Parser {
+    /// Parse `chunk`, returning the events it completes.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<Event> {
+        self.buf.extend_from_slice(chunk);
+        if !self.started {
+            if self.buf.len() < 3 && b"\xEF\xBB\xBF".starts_with(&self.buf) {
+                return Vec::new();
+            }
+            if self.buf.starts_with(b"\xEF\xBB\xBF") {
+                self.buf.drain(..3);
+            }
+            self.started = true;
+        }
+        let mut events = Vec::new();
+        let mut start = 0;
+        while let Some(pos) = self.buf[start..].iter().position(|&b| b == b'\n' || b == b'\r') {
+            let end = start + pos;
+            let mut next = end + 1;
+            if self.buf[end] == b'\r' {
+                // A CR at the end of the buffer may be the first half of a CRLF.
+                if next == self.buf.len() {
+                    break;
+                }
+                if self.buf[next] == b'\n' {
+                    next += 1;
+                }
+            }
+            let line = String::from_utf8_lossy(&self.buf[start..end]).into_owned();
+            if let Some(event) = self.process_line(&line) {
+                events.push(event);
+            }
+            start = next;
+        }
+        self.buf.drain(..start);
+        events
+    }
+
+    /// Forget the partial event and line of a dropped connection, keeping
+    /// the last event id and retry interval for the next one.
+    pub fn reconnected(&mut self) {
+        self.buf.clear();
+        self.data.clear();
+        self.has_data = false;
+        self.event.clear();
+        self.started = false;
+    }
+
+    /// The id to send as `Last-Event-ID`, if any (an empty `id:` clears it).
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_id.as_deref().filter(|id| !id.is_empty())
+    }
+
+    pub fn set_last_event_id(&mut self, id: String) {
+        self.last_id = Some(id);
+    }
+
+    /// The reconnection delay set by the server's last `retry:` field.
+    pub fn retry_ms(&self) -> Option<u64> {
+        self.retry_ms
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = value.to_owned(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+                self.has_data = true;
+            }
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_owned()),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                self.retry_ms = value.parse().ok();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let event = std::mem::take(&mut self.event);
+        let mut data = std::mem::take(&mut self.data);
+        if !std::mem::take(&mut self.has_data) {
+            return None;
+        }
+        data.pop();
+        Some(Event {
+            event: if event.is_empty() { "message".to_owned() } else { event },
+            data,
+            id: self.last_event_id().map(str::to_owned),
+        })
+    }
+}
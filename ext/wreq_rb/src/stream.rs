@@ -0,0 +1,130 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use tokio_util::sync::CancellationToken;
+
+use crate::client::{runtime, without_gvl};
+use crate::error::{generic_error, interrupted, to_magnus_error};
+
+/// The unread body of a `stream: true` response, pulled chunk by chunk.
+pub struct BodyStream {
+    /// Taken out while a read is in flight so the lock is never held with
+    /// the GVL released.
+    resp: Mutex<Option<wreq::Response>>,
+    /// Bytes pulled from the connection but not yet handed out by `read`.
+    leftover: Mutex<Bytes>,
+    done: AtomicBool,
+    /// The owning client's cancel token, so `Client#cancel` stops reads.
+    cancel: CancellationToken,
+    /// The owning client's `bytes_received` total.
+    received: Arc<AtomicU64>,
+}
+
+enum ChunkOutcome {
+    Chunk(Bytes),
+    Eof,
+    Err(wreq::Error),
+    Interrupted,
+}
+
+impl BodyStream {
+    pub fn new(resp: wreq::Response, cancel: CancellationToken, received: Arc<AtomicU64>) -> Self {
+        BodyStream {
+            resp: Mutex::new(Some(resp)),
+            leftover: Mutex::new(Bytes::new()),
+            done: AtomicBool::new(false),
+            cancel,
+            received,
+        }
+    }
+
+    /// Next chunk of the body, or None at the end.
+    pub fn next_chunk(&self) -> Result<Option<Bytes>, magnus::Error> {
+        let leftover = std::mem::take(&mut *self.leftover.lock().unwrap_or_else(|e| e.into_inner()));
+        if !leftover.is_empty() {
+            return Ok(Some(leftover));
+        }
+        self.pull()
+    }
+
+    /// IO#read semantics: up to `n` bytes (fewer only at the end of the
+    /// body), or everything left when `n` is None. At the end, `read(n)`
+    /// returns None and `read(None)` an empty buffer.
+    pub fn read(&self, n: Option<usize>) -> Result<Option<Vec<u8>>, magnus::Error> {
+        let mut buf = Vec::new();
+        while !matches!(n, Some(n) if buf.len() >= n) {
+            let Some(chunk) = self.next_chunk()? else { break };
+            match n {
+                Some(n) if buf.len() + chunk.len() > n => {
+                    let take = n - buf.len();
+                    buf.extend_from_slice(&chunk[..take]);
+                    *self.leftover.lock().unwrap_or_else(|e| e.into_inner()) = chunk.slice(take..);
+                }
+                _ => buf.extend_from_slice(&chunk),
+            }
+        }
+        Ok(match n {
+            Some(n) if n > 0 && buf.is_empty() => None,
+            _ => Some(buf),
+        })
+    }
+
+    /// Drop the rest of the body along with its connection, which is not
+    /// returned to the pool. Later reads see the end of the body.
+    pub fn close(&self) {
+        let taken = self.resp.lock().unwrap_or_else(|e| e.into_inner()).take();
+        self.done.store(true, Ordering::Release);
+        drop(taken);
+    }
+
+    /// Pull the next chunk off the connection with the GVL released.
+    fn pull(&self) -> Result<Option<Bytes>, magnus::Error> {
+        let rt = runtime()?;
+        let taken = self.resp.lock().unwrap_or_else(|e| e.into_inner()).take();
+        let Some(mut resp) = taken else {
+            if self.done.load(Ordering::Acquire) {
+                return Ok(None);
+            }
+            return Err(generic_error("body stream is being read by another thread"));
+        };
+        let cancel = self.cancel.clone();
+        let outcome = unsafe {
+            without_gvl(|thread_token| {
+                let outcome = rt.block_on(async {
+                    tokio::select! {
+                        biased;
+                        _ = thread_token.cancelled() => ChunkOutcome::Interrupted,
+                        _ = cancel.cancelled() => ChunkOutcome::Interrupted,
+                        res = resp.chunk() => match res {
+                            Ok(Some(chunk)) => ChunkOutcome::Chunk(chunk),
+                            Ok(None) => ChunkOutcome::Eof,
+                            Err(e) => ChunkOutcome::Err(e),
+                        },
+                    }
+                });
+                (outcome, resp)
+            })
+        };
+        let (outcome, resp) = outcome;
+        match outcome {
+            ChunkOutcome::Chunk(chunk) => {
+                self.received.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                *self.resp.lock().unwrap_or_else(|e| e.into_inner()) = Some(resp);
+                Ok(Some(chunk))
+            }
+            ChunkOutcome::Eof => {
+                self.done.store(true, Ordering::Release);
+                Ok(None)
+            }
+            ChunkOutcome::Err(e) => {
+                self.done.store(true, Ordering::Release);
+                Err(to_magnus_error(e))
+            }
+            ChunkOutcome::Interrupted => {
+                self.done.store(true, Ordering::Release);
+                Err(interrupted())
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use bytes::Bytes;
+use magnus::{prelude::*, RString, Ruby, Value};
+use tokio::sync::mpsc;
+
+use crate::error::{generic_error, with_cause, wreq_error};
+
+/// Chunks buffered between the Ruby producer and the connection. Kept small
+/// so a fast producer blocks instead of queueing the whole body in memory.
+pub const BODY_CHANNEL_CAPACITY: usize = 4;
+
+type Chunk = Result<Bytes, std::io::Error>;
+
+/// Pulls body chunks from a Ruby Enumerator or String on the calling
+/// (GVL-holding) thread and hands them to the in-flight request through a
+/// bounded channel.
+pub struct ChunkProducer {
+    source: Source,
+    tx: mpsc::Sender<Chunk>,
+    /// Size promised by `content_length:`, checked as chunks are produced.
+    declared: Option<u64>,
+    produced: u64,
+    /// Raise errors from the enumerator as `Wreq::Error` (with the original
+    /// as its cause) rather than as-is.
+    wrap_errors: bool,
+}
+
+enum Source {
+    Enumerator(Value),
+    /// A frozen copy of a `body:` String, sent `chunk_size` bytes at a time.
+    String { string: RString, chunk_size: usize },
+}
+
+impl ChunkProducer {
+    /// Create a producer for `enumerator` and the streaming body it feeds.
+    pub fn new(enumerator: Value) -> (Self, wreq::Body) {
+        Self::with_source(Source::Enumerator(enumerator))
+    }
+
+    /// Create a producer that sends `string` in slices of `chunk_size`
+    /// bytes, each copied only as the connection is ready for it. Later
+    /// changes to `string` do not affect the body.
+    pub fn from_string(string: RString, chunk_size: usize) -> Result<(Self, wreq::Body), magnus::Error> {
+        // `dup` shares the buffer until either copy is modified.
+        let string: RString = string.funcall("dup", ())?;
+        string.freeze();
+        let len = string.len() as u64;
+        let (producer, body) = Self::with_source(Source::String { string, chunk_size });
+        Ok((producer.with_declared_length(len), body))
+    }
+
+    fn with_source(source: Source) -> (Self, wreq::Body) {
+        let (tx, rx) = mpsc::channel::<Chunk>(BODY_CHANNEL_CAPACITY);
+        let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|chunk| (chunk, rx))
+        });
+        let producer = ChunkProducer { source, tx, declared: None, produced: 0, wrap_errors: false };
+        (producer, wreq::Body::wrap_stream(stream))
+    }
+
+    /// Require the enumerator to yield exactly `len` bytes in total.
+    pub fn with_declared_length(mut self, len: u64) -> Self {
+        self.declared = Some(len);
+        self
+    }
+
+    /// Report a `StandardError` raised by the enumerator as a `Wreq::Error`.
+    pub fn wrapping_errors(mut self) -> Self {
+        self.wrap_errors = true;
+        self
+    }
+
+    /// Next chunk from the source, or None once it is exhausted.
+    /// Errors if the chunks disagree with a declared length, so the request
+    /// is aborted instead of sent malformed. Must be called with the GVL held.
+    pub fn next_chunk(&mut self) -> Result<Option<Bytes>, magnus::Error> {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let enumerator = match self.source {
+            Source::Enumerator(enumerator) => enumerator,
+            Source::String { string, chunk_size } => {
+                let start = self.produced as usize;
+                // SAFETY: the String is frozen, and the slice is copied
+                // before any Ruby code can run.
+                let rest = unsafe { string.as_slice() }.get(start..).unwrap_or_default();
+                if rest.is_empty() {
+                    return Ok(None);
+                }
+                let chunk = Bytes::copy_from_slice(&rest[..rest.len().min(chunk_size)]);
+                self.produced += chunk.len() as u64;
+                return Ok(Some(chunk));
+            }
+        };
+        loop {
+            let val: Value = match enumerator.funcall("next", ()) {
+                Ok(v) => v,
+                Err(e) if e.is_kind_of(ruby.exception_stop_iteration()) => {
+                    return match self.declared {
+                        Some(len) if self.produced < len => Err(generic_error(format!(
+                            "body ended after {} bytes but content_length is {}",
+                            self.produced, len
+                        ))),
+                        _ => Ok(None),
+                    };
+                }
+                Err(e) if self.wrap_errors && e.is_kind_of(ruby.exception_standard_error()) => {
+                    return Err(with_cause(
+                        wreq_error(),
+                        format!("request body aborted after {} bytes: the body producer raised", self.produced),
+                        e,
+                    ));
+                }
+                Err(e) => return Err(e),
+            };
+            let s = match RString::from_value(val) {
+                Some(s) => s,
+                None => val.funcall("to_s", ())?,
+            };
+            // SAFETY: the slice is copied immediately, before any Ruby code can run.
+            let bytes = Bytes::copy_from_slice(unsafe { s.as_slice() });
+            if !bytes.is_empty() {
+                self.produced += bytes.len() as u64;
+                if let Some(len) = self.declared.filter(|&len| self.produced > len) {
+                    return Err(generic_error(format!(
+                        "body is longer than content_length ({} bytes)",
+                        len
+                    )));
+                }
+                return Ok(Some(bytes));
+            }
+        }
+    }
+
+    /// The sending half of the body channel.
+    pub fn sender(&self) -> &mpsc::Sender<Chunk> {
+        &self.tx
+    }
+}
@@ -86,6 +86,1254 @@ class ClientTest < Minitest::Test
       "got positions #{positions.inspect} in: #{received.inspect}"
   end
 
+  def test_dns_cache_ttl
+    # Connection: close makes every request open, and so resolve, anew.
+    server = TestServer.new { |_req| TestServer.response(200, "ok", "Connection" => "close") }
+    url = server.url.sub("127.0.0.1", "localhost")
+    client = Wreq::Client.new(dns_cache_ttl: 60)
+    2.times { assert_equal 200, client.get(url).status }
+    assert_equal({ hits: 1, misses: 1, entries: 1, ttl: 60.0 }, client.dns_cache_stats)
+
+    assert_nil Wreq::Client.new.dns_cache_stats
+    assert_raises(Wreq::Error) { Wreq::Client.new(dns_cache_ttl: -1) }
+  ensure
+    server&.close
+  end
+
+  def test_exists
+    client = Wreq::Client.new
+    assert client.exists?("https://httpbin.org/get")
+    refute client.exists?("https://httpbin.org/status/404")
+  end
+
+  def test_exists_falls_back_to_ranged_get_on_405
+    server = TestServer.new do |req|
+      if req[:method] == "HEAD"
+        TestServer.response(405)
+      else
+        TestServer.response(206, "x", "Content-Range" => "bytes 0-0/10")
+      end
+    end
+    assert Wreq::Client.new.exists?(server.url)
+    assert_equal "bytes=0-0", server.requests.last[:headers]["range"]
+  ensure
+    server&.close
+  end
+
+  def test_exists_swallows_connection_errors
+    client = Wreq::Client.new(connect_timeout: 2)
+    refute client.exists?("http://127.0.0.1:1/")
+    assert_raises(Wreq::Error) { client.exists?("http://127.0.0.1:1/", raise: true) }
+  end
+
+  def test_exists_raises_non_connection_errors
+    client = Wreq::Client.new
+    assert_raises(Wreq::InvalidHeaderError) { client.exists?("http://127.0.0.1:1/", headers: { "bad name" => "v" }) }
+  end
+
+  def test_byte_counters
+    server = TestServer.new { |_req| TestServer.response(200, "hello world") }
+    client = Wreq::Client.new(gzip: false, brotli: false, deflate: false, zstd: false)
+    client.post(server.url, body: "12345")
+    client.post(server.url, body: "678")
+    assert_equal 8, client.bytes_sent
+    assert_equal 22, client.bytes_received
+
+    client.reset_counters
+    assert_equal 0, client.bytes_sent
+    assert_equal 0, client.bytes_received
+
+    resp = client.get(server.url, stream: true)
+    assert_equal 0, client.bytes_received
+    assert_equal "hello", resp.read(5)
+    resp.read
+    assert_equal 11, client.bytes_received
+  ensure
+    server&.close
+  end
+
+  def test_healthy
+    server = TestServer.new { |_req| TestServer.response(503) }
+    client = Wreq::Client.new
+    assert client.healthy?(server.url), "an HTTP error still means the host is reachable"
+    refute client.healthy?("http://127.0.0.1:1/", timeout: 1)
+  ensure
+    server&.close
+  end
+
+  def test_healthy_raises_on_a_closed_client
+    client = Wreq::Client.new
+    client.close
+    err = assert_raises(Wreq::Error) { client.healthy?("http://127.0.0.1:1/") }
+    assert_equal "client is closed", err.message
+  end
+
+  def test_send_accept_encoding_false_still_decodes
+    require "zlib"
+    server = TestServer.new do |req|
+      if req[:path] == "/identity"
+        TestServer.response(200, "hello plain", "Content-Encoding" => "identity")
+      else
+        TestServer.response(200, Zlib.gzip("hello gzip"), "Content-Encoding" => "gzip")
+      end
+    end
+    client = Wreq::Client.new(send_accept_encoding: false)
+    resp = client.get(server.url)
+    assert_nil server.requests.last[:headers]["accept-encoding"]
+    assert_equal "hello gzip", resp.text
+    assert_nil resp.content_encoding
+    refute resp.headers.key?("content-encoding")
+    refute resp.headers.key?("content-length")
+
+    plain = client.get(server.url("/identity"))
+    assert_equal "hello plain", plain.text
+    assert_nil plain.content_encoding
+    assert_equal ["11"], plain.headers["content-length"]
+  ensure
+    server&.close
+  end
+
+  def test_content_length_raw_and_decoded_modes
+    require "zlib"
+    text = "hello gzip " * 50
+    compressed = Zlib.gzip(text)
+    server = TestServer.new do |_req|
+      TestServer.response(200, compressed, "Content-Encoding" => "gzip")
+    end
+
+    raw = Wreq::Client.new(send_accept_encoding: false, content_length: :raw).get(server.url)
+    assert_equal text, raw.text
+    assert_equal compressed.bytesize, raw.content_length
+    assert_equal text.bytesize, raw.body_size
+
+    decoded = Wreq::Client.new(send_accept_encoding: false, content_length: :decoded).get(server.url)
+    assert_equal text.bytesize, decoded.content_length
+    assert_equal text.bytesize, decoded.body_size
+
+    assert_raises(Wreq::Error) { Wreq::Client.new(content_length: :compressed) }
+  ensure
+    server&.close
+  end
+
+  def test_per_request_gzip_overrides_client
+    require "zlib"
+    compressed = Zlib.gzip("hello gzip")
+    server = TestServer.new do |_req|
+      TestServer.response(200, compressed, "Content-Encoding" => "gzip")
+    end
+
+    [Wreq::Client.new, Wreq::Client.new(send_accept_encoding: false)].each do |client|
+      assert_equal "hello gzip", client.get(server.url).text
+      assert_equal compressed.b, client.get(server.url, gzip: false).body_bytes.pack("C*")
+    end
+
+    raw_client = Wreq::Client.new(send_accept_encoding: false, gzip: false)
+    assert_equal compressed.b, raw_client.get(server.url).body_bytes.pack("C*")
+    assert_equal "hello gzip", raw_client.get(server.url, gzip: true).text
+  ensure
+    server&.close
+  end
+
+  def test_download_identical_across_buffer_sizes
+    require "tmpdir"
+    payload = Random.new(7).bytes(300_000)
+    server = TestServer.new { |_req| TestServer.response(200, payload, "Content-Type" => "application/octet-stream") }
+    client = Wreq::Client.new
+
+    Dir.mktmpdir do |dir|
+      [1, 4096, 1 << 20].each do |size|
+        path = File.join(dir, "out-#{size}.bin")
+        resp = client.download(server.url, path, download_buffer_size: size)
+        assert_equal 200, resp.status
+        assert_equal payload, File.binread(path), "content differs with download_buffer_size: #{size}"
+      end
+    end
+  ensure
+    server&.close
+  end
+
+  def test_download_error_status_keeps_file
+    require "tmpdir"
+    server = TestServer.new { |_req| TestServer.response(404, "not here") }
+    Dir.mktmpdir do |dir|
+      path = File.join(dir, "out.bin")
+      File.write(path, "previous")
+      resp = Wreq::Client.new.download(server.url, path)
+      assert_equal 404, resp.status
+      assert_equal "not here", resp.body
+      assert_equal "previous", File.read(path)
+
+      missing = File.join(dir, "missing.bin")
+      Wreq::Client.new.download(server.url, missing)
+      refute File.exist?(missing)
+    end
+  ensure
+    server&.close
+  end
+
+  def test_download_if_changed_downloads_then_skips
+    require "tmpdir"
+    require "time"
+    modified = Time.utc(2024, 1, 2, 3, 4, 5)
+    server = TestServer.new do |req|
+      if req[:headers]["if-none-match"] == "\"v1\"" || req[:headers]["if-modified-since"] == modified.httpdate
+        TestServer.response(304)
+      else
+        TestServer.response(200, "fresh", "Last-Modified" => modified.httpdate, "ETag" => "\"v1\"")
+      end
+    end
+    client = Wreq::Client.new
+
+    Dir.mktmpdir do |dir|
+      path = File.join(dir, "mirror.txt")
+      etag_path = "#{path}.etag"
+      assert_equal :downloaded, client.download_if_changed(server.url, path, etag_path: etag_path)
+      assert_equal "fresh", File.read(path)
+      assert_equal modified, File.mtime(path).utc
+      assert_equal "\"v1\"", File.read(etag_path)
+
+      assert_equal :unchanged, client.download_if_changed(server.url, path, etag_path: etag_path)
+      assert_equal modified.httpdate, server.requests.last[:headers]["if-modified-since"]
+      assert_equal "\"v1\"", server.requests.last[:headers]["if-none-match"]
+      assert_equal "fresh", File.read(path)
+      assert_equal [File.basename(path), File.basename(etag_path)].sort, Dir.children(dir).sort
+    end
+  ensure
+    server&.close
+  end
+
+  def test_download_if_changed_error_status_keeps_file
+    require "tmpdir"
+    server = TestServer.new { |_req| TestServer.response(500, "oops") }
+    Dir.mktmpdir do |dir|
+      path = File.join(dir, "mirror.txt")
+      File.write(path, "old")
+      assert_equal :error, Wreq::Client.new.download_if_changed(server.url, path)
+      assert_equal "old", File.read(path)
+      assert_equal ["mirror.txt"], Dir.children(dir)
+    end
+  ensure
+    server&.close
+  end
+
+  def test_on_informational_accepted_and_interim_responses_skipped
+    server = TestServer.new do |_req|
+      "HTTP/1.1 103 Early Hints\r\nLink: </app.css>; rel=preload\r\n\r\n".b +
+        TestServer.response(200, "final", "X-Final" => "yes")
+    end
+    calls = []
+    client = Wreq::Client.new(on_informational: ->(status, headers) { calls << [status, headers] })
+    resp = client.get(server.url)
+    assert_equal 200, resp.status
+    assert_equal "final", resp.body
+    assert_equal "yes", resp.headers["x-final"]&.first
+    assert_empty calls
+
+    err = assert_raises(Wreq::Error) { Wreq::Client.new(on_informational: 42) }
+    assert_match(/on_informational must respond to #call/, err.message)
+  ensure
+    server&.close
+  end
+
+  def test_default_scheme_for_scheme_less_urls
+    assert_match %r{\Ahttps://httpbin\.org/}, Wreq::Client.new.get("httpbin.org/get").url
+
+    server = TestServer.new { |_req| TestServer.response(200, "plain") }
+    client = Wreq::Client.new(default_scheme: "http")
+    assert_equal "plain", client.get(server.url("/x").delete_prefix("http://")).text
+    assert_equal "/x", server.requests.last[:path]
+    assert_equal "plain", client.get(server.url("/y").delete_prefix("http:")).text
+  ensure
+    server&.close
+  end
+
+  def test_default_scheme_http_conflicts_with_https_only
+    err = assert_raises(Wreq::Error) { Wreq::Client.new(https_only: true, default_scheme: :http) }
+    assert_match(/https_only/, err.message)
+    assert_raises(Wreq::Error) { Wreq::Client.new(default_scheme: "ftp") }
+    Wreq::Client.new(https_only: true, default_scheme: "https")
+  end
+
+  def test_head_size_reads_content_length
+    server = TestServer.new do |req|
+      case req[:path]
+      when "/file" then "HTTP/1.1 200 OK\r\nContent-Length: 123456\r\n\r\n"
+      when "/missing" then TestServer.response(404, "nope")
+      else "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n"
+      end
+    end
+    client = Wreq::Client.new
+    assert_equal 123_456, client.head_size(server.url("/file"))
+    assert_equal "HEAD", server.requests.last[:method]
+    assert_nil client.head_size(server.url("/chunked"))
+    assert_nil client.head_size(server.url("/missing"))
+    assert_raises(Wreq::Error) { client.head_size("http://127.0.0.1:1/") }
+  ensure
+    server&.close
+  end
+
+  def test_pool_stats_counts_active_and_peak_requests
+    release = Queue.new
+    server = TestServer.new do |req|
+      release.pop if req[:path] == "/slow"
+      TestServer.response(200)
+    end
+    client = Wreq::Client.new
+    host = server.url.delete_prefix("http://").chomp("/")
+    assert_equal({ idle: nil, active: 0, per_host: {} }, client.pool_stats)
+
+    threads = 3.times.map { Thread.new { client.get(server.url("/slow")) } }
+    sleep 0.1 until server.requests.size == 3
+    stats = client.pool_stats
+    assert_equal 3, stats[:active]
+    assert_equal 3, stats[:per_host][host][:active]
+
+    3.times { release << true }
+    threads.each(&:join)
+    client.get(server.url("/fast"))
+    assert_equal({ active: 0, peak: 3, requests: 4 }, client.pool_stats[:per_host][host])
+    assert_nil client.pool_stats[:idle]
+  ensure
+    server&.close
+  end
+
+  def test_upload_streams_file_with_inferred_content_type
+    require "tempfile"
+    server = TestServer.new { |_req| TestServer.response(201) }
+    client = Wreq::Client.new(emulation: false)
+    data = (0...100_000).map { |i| (i % 256).chr }.join.b
+    Tempfile.create(["upload", ".png"]) do |f|
+      f.binmode
+      f.write(data)
+      f.flush
+
+      resp = client.upload(server.url + "put", f.path)
+      assert_equal 201, resp.status
+      req = server.requests.last
+      assert_equal "PUT", req[:method]
+      assert_equal "image/png", req[:headers]["content-type"]
+      assert_equal data.bytesize.to_s, req[:headers]["content-length"]
+      assert_equal data, req[:body].b
+
+      client.upload(server.url + "patch", f.path, method: :patch, content_type: "application/x-custom")
+      req = server.requests.last
+      assert_equal "PATCH", req[:method]
+      assert_equal "application/x-custom", req[:headers]["content-type"]
+      assert_equal data, req[:body].b
+    end
+
+    assert_raises(Wreq::Error) { client.upload(server.url, "/nonexistent/file.bin") }
+  ensure
+    server&.close
+  end
+
+  def test_log_level_forwards_to_sink
+    server = TestServer.new { |_req| TestServer.response(200, "ok") }
+    lines = []
+    Wreq.log_sink = ->(level, target, message) { lines << [level, target, message] }
+    Wreq.log_level = :trace
+    Wreq.log_level = :trace
+    assert_equal :trace, Wreq.log_level
+
+    Wreq::Client.new.get(server.url)
+    refute_empty lines
+    assert(lines.all? { |level, target, message| level.is_a?(Symbol) && target.is_a?(String) && message.is_a?(String) })
+
+    Wreq.log_level = :off
+    lines.clear
+    Wreq::Client.new.get(server.url)
+    Wreq.flush_log
+    assert_empty lines
+
+    assert_raises(Wreq::Error) { Wreq.log_level = :verbose }
+    assert_raises(Wreq::Error) { Wreq.log_sink = 42 }
+  ensure
+    Wreq.log_level = :off
+    Wreq.log_sink = nil
+    server&.close
+  end
+
+  def test_socket_option_raises
+    require "socket"
+    a, b = UNIXSocket.pair
+    err = assert_raises(Wreq::Error) { Wreq::Client.new(socket: a) }
+    assert_match(/socket: is not supported/, err.message)
+  ensure
+    a&.close
+    b&.close
+  end
+
+  def test_max_header_size
+    server = TestServer.new do |_req|
+      TestServer.response(200, "ok", "X-Big" => ["a" * 20_000, "b" * 20_000])
+    end
+
+    err = assert_raises(Wreq::Error) { Wreq::Client.new(max_header_size: 16 * 1024).get(server.url) }
+    assert_equal :headers, err.error_kind
+    assert_match(/max_header_size/, err.message)
+
+    assert_equal "ok", Wreq::Client.new.get(server.url).text
+    assert_raises(Wreq::Error) { Wreq::Client.new(max_header_size: 0) }
+  ensure
+    server&.close
+  end
+
+  def test_connect_timeout_bounds_a_stalled_tls_handshake
+    # Accepts TCP connections but never answers the TLS ClientHello.
+    server = TCPServer.new("127.0.0.1", 0)
+    port = server.addr[1]
+    held = []
+    acceptor = Thread.new { loop { held << server.accept } }
+
+    client = Wreq::Client.new(connect_timeout: 0.5, timeout: 10)
+    started = Process.clock_gettime(Process::CLOCK_MONOTONIC)
+    err = assert_raises(Wreq::TimeoutError) { client.get("https://127.0.0.1:#{port}/") }
+    elapsed = Process.clock_gettime(Process::CLOCK_MONOTONIC) - started
+    assert_equal :connect_timeout, err.error_kind
+    assert elapsed < 5, "handshake stall took #{elapsed.round(2)}s to fail"
+
+    err = assert_raises(Wreq::Error) { Wreq::Client.new(tls_handshake_timeout: 5) }
+    assert_match(/tls_handshake_timeout: is not supported/, err.message)
+  ensure
+    acceptor&.kill
+    held&.each(&:close)
+    server&.close
+  end
+
+  def test_each_event_reconnects_with_last_event_id
+    server = TestServer.new do |req|
+      body = if req[:headers]["last-event-id"]
+        "id: 3\ndata: third\n\n"
+      else
+        "retry: 10\n: keep-alive\nid: 1\nevent: greeting\ndata: hello\ndata: world\n\nid: 2\r\ndata: second\r\n\r\ndata: partial"
+      end
+      TestServer.response(200, body, "Content-Type" => "text/event-stream; charset=utf-8")
+    end
+
+    events = []
+    last = Wreq::Client.new.each_event(server.url("/events")) do |event|
+      events << event
+      :stop if event[:id] == "3"
+    end
+
+    assert_equal "3", last
+    assert_equal [
+      { event: "greeting", data: "hello\nworld", id: "1" },
+      { event: "message", data: "second", id: "2" },
+      { event: "message", data: "third", id: "3" },
+    ], events
+    assert_equal 2, server.requests.size
+    assert_equal "text/event-stream", server.requests.first[:headers]["accept"]
+    assert_nil server.requests.first[:headers]["last-event-id"]
+    assert_equal "2", server.requests.last[:headers]["last-event-id"]
+  ensure
+    server&.close
+  end
+
+  def test_each_event_stops_after_max_reconnects_and_on_failures
+    server = TestServer.new do |req|
+      case req[:path]
+      when "/events" then TestServer.response(200, "data: tick\n\n", "Content-Type" => "text/event-stream")
+      when "/done" then TestServer.response(204)
+      when "/html" then TestServer.response(200, "<html></html>", "Content-Type" => "text/html")
+      else TestServer.response(503, "down")
+      end
+    end
+    client = Wreq::Client.new
+
+    ticks = 0
+    assert_nil client.each_event(server.url("/events"), max_reconnects: 2, reconnect_delay: 0.01) { ticks += 1 }
+    assert_equal 3, ticks
+
+    assert_nil client.each_event(server.url("/done")) { flunk "no events expected" }
+    assert_raises(Wreq::ServerError) { client.each_event(server.url("/down")) {} }
+    assert_raises(Wreq::DecodeError) { client.each_event(server.url("/html")) {} }
+    assert_raises(Wreq::Error) { client.each_event(server.url("/events")) }
+  ensure
+    server&.close
+  end
+
+  def test_graphql
+    server = TestServer.new do |req|
+      body = JSON.parse(req[:body])
+      result = if body["variables"]["id"] == 0
+        { data: nil, errors: [{ message: "not found" }, { message: "bad id" }] }
+      else
+        { data: { user: { name: "Ada" } } }
+      end
+      TestServer.response(200, JSON.generate(result), "Content-Type" => "application/json")
+    end
+    client = Wreq::Client.new
+    query = "query($id: ID!) { user(id: $id) { name } }"
+
+    result = client.graphql(server.url("/graphql"), query, variables: { id: 1 }, operation_name: "User")
+    assert_equal "Ada", result["data"]["user"]["name"]
+    sent = JSON.parse(server.requests.last[:body])
+    assert_equal({ "query" => query, "variables" => { "id" => 1 }, "operationName" => "User" }, sent)
+    assert_equal "application/json", server.requests.last[:headers]["content-type"]
+
+    client.graphql(server.url("/graphql"), "{ viewer { id } }")
+    assert_equal({ "query" => "{ viewer { id } }", "variables" => {} }, JSON.parse(server.requests.last[:body]))
+
+    lenient = client.graphql(server.url("/graphql"), query, variables: { id: 0 })
+    assert_equal 2, lenient["errors"].size
+    err = assert_raises(Wreq::Error) do
+      client.graphql(server.url("/graphql"), query, variables: { id: 0 }, raise_errors: true, symbolize_keys: true)
+    end
+    assert_equal :graphql, err.error_kind
+    assert_match(/not found; bad id/, err.message)
+  ensure
+    server&.close
+  end
+
+  def test_cache_serves_hits_and_expires_entries
+    server = TestServer.new do |req|
+      headers = req[:path] == "/private" ? { "Cache-Control" => "no-store" } : {}
+      TestServer.response(200, "body #{req[:path]}", headers)
+    end
+    client = Wreq::Client.new(cache: { max_entries: 10, ttl: 0.5 })
+
+    assert_equal "body /a", client.get(server.url("/a")).body
+    assert_equal "body /a", client.get(server.url("/a")).body
+    assert_equal 1, server.requests.size
+    client.get(server.url("/b"))
+    assert_equal 2, server.requests.size
+    assert_equal({ hits: 1, misses: 2, entries: 2, max_entries: 10, ttl: 0.5 }, client.cache_stats)
+
+    2.times { client.get(server.url("/private")) }
+    assert_equal 4, server.requests.size
+
+    sleep 0.6
+    client.get(server.url("/a"))
+    assert_equal 5, server.requests.size
+
+    client.cache_clear
+    assert_equal 0, client.cache_stats[:entries]
+    client.get(server.url("/a"))
+    assert_equal 6, server.requests.size
+    assert_nil Wreq::Client.new.cache_stats
+  ensure
+    server&.close
+  end
+
+  def test_cache_keeps_credentials_and_vary_apart
+    server = TestServer.new do |req|
+      headers = {}
+      headers["Vary"] = "Accept-Language" if req[:path] == "/vary"
+      headers["Vary"] = "*" if req[:path] == "/star"
+      TestServer.response(200, "#{req[:headers]["authorization"]} #{req[:headers]["accept-language"]}", headers)
+    end
+    client = Wreq::Client.new(cache: true)
+
+    assert_equal "Bearer alice ", client.get(server.url("/me"), bearer: "alice").body
+    assert_equal "Bearer bob ", client.get(server.url("/me"), bearer: "bob").body
+    assert_equal 2, server.requests.size
+
+    assert_equal " en", client.get(server.url("/vary"), headers: { "Accept-Language" => "en" }).body
+    assert_equal " de", client.get(server.url("/vary"), headers: { "Accept-Language" => "de" }).body
+    assert_equal " de", client.get(server.url("/vary"), headers: { "Accept-Language" => "de" }).body
+    assert_equal 4, server.requests.size
+
+    2.times { client.get(server.url("/star")) }
+    assert_equal 6, server.requests.size
+
+    nonce = 0
+    signed = Wreq::Client.new(cache: true, before_request: ->(_req) { { "Authorization" => "Sig #{nonce += 1}" } })
+    assert_equal "Sig 1 ", signed.get(server.url("/me")).body
+    assert_equal "Sig 2 ", signed.get(server.url("/me")).body
+    assert_equal 8, server.requests.size
+  ensure
+    server&.close
+  end
+
+  def test_download_resumable_fresh_then_resumes
+    require "tmpdir"
+    data = "0123456789abcdef"
+    server = ranged_file_server(data)
+    client = Wreq::Client.new
+    Dir.mktmpdir do |dir|
+      path = File.join(dir, "file.bin")
+      assert_equal({ bytes: 16, resumed: false }, client.download_resumable(server.url, path))
+      assert_equal data, File.read(path)
+      assert_nil server.requests.last[:headers]["range"]
+
+      File.write(path, data[0, 6])
+      assert_equal({ bytes: 16, resumed: true }, client.download_resumable(server.url, path))
+      assert_equal data, File.read(path)
+      assert_equal "bytes=6-", server.requests.last[:headers]["range"]
+      assert_equal "\"v1\"", server.requests.last[:headers]["if-range"]
+
+      requests = server.requests.size
+      assert_equal({ bytes: 16, resumed: true }, client.download_resumable(server.url, path))
+      assert_equal requests + 1, server.requests.size # HEAD only: already complete
+    end
+  ensure
+    server&.close
+  end
+
+  def test_download_resumable_without_range_support_downloads_in_full
+    require "tmpdir"
+    data = "0123456789abcdef"
+    server = ranged_file_server(data, ranges: false)
+    Dir.mktmpdir do |dir|
+      path = File.join(dir, "file.bin")
+      File.write(path, "stale")
+      assert_equal({ bytes: 16, resumed: false }, Wreq::Client.new.download_resumable(server.url, path))
+      assert_equal data, File.read(path)
+      assert_equal %w[HEAD GET], server.requests.map { |r| r[:method] }
+      assert_nil server.requests.last[:headers]["range"]
+    end
+  ensure
+    server&.close
+  end
+
+  def test_download_resumable_checks_content_range_and_refetches_on_416
+    require "tmpdir"
+    data = "0123456789abcdef"
+    mode = :wrong_range
+    server = TestServer.new do |req|
+      headers = { "Content-Length" => data.bytesize, "Accept-Ranges" => "bytes" }
+      if req[:method] == "HEAD"
+        TestServer.response(200, "", headers)
+      elsif req[:headers]["range"] && mode == :wrong_range
+        TestServer.response(206, data, headers.merge("Content-Range" => "bytes 0-15/16"))
+      elsif req[:headers]["range"]
+        TestServer.response(416, "", "Content-Range" => "bytes */16")
+      else
+        TestServer.response(200, data, headers)
+      end
+    end
+    client = Wreq::Client.new
+    Dir.mktmpdir do |dir|
+      path = File.join(dir, "file.bin")
+      File.write(path, "012345")
+      assert_raises(Wreq::Error) { client.download_resumable(server.url, path) }
+      assert_equal "012345", File.read(path)
+
+      mode = :unsatisfiable
+      assert_equal({ bytes: 16, resumed: false }, client.download_resumable(server.url, path))
+      assert_equal data, File.read(path)
+      assert_nil server.requests.last[:headers]["range"]
+    end
+  ensure
+    server&.close
+  end
+
+  def test_circuit_breaker_opens_cools_down_and_recovers
+    healthy = false
+    server = TestServer.new { |_req| healthy ? TestServer.response(200, "ok") : TestServer.response(503, "down") }
+    client = Wreq::Client.new(circuit_breaker: { failure_threshold: 2, cooldown: 0.5 })
+
+    2.times { assert_equal 503, client.get(server.url).status }
+    err = assert_raises(Wreq::Error) { client.get(server.url("/other")) }
+    assert_equal :circuit_open, err.error_kind
+    assert_equal 2, server.requests.size
+
+    # The probe after the cooldown fails, so the circuit reopens.
+    sleep 0.6
+    assert_equal 503, client.get(server.url).status
+    assert_raises(Wreq::Error) { client.get(server.url) }
+    assert_equal 3, server.requests.size
+
+    healthy = true
+    sleep 0.6
+    assert_equal 200, client.get(server.url).status
+    assert_equal 200, client.get(server.url).status
+    assert_equal 5, server.requests.size
+
+    other = Wreq::Client.new(circuit_breaker: true)
+    kinds = 6.times.map { assert_raises(Wreq::Error) { other.get("http://127.0.0.1:1/") }.error_kind }
+    assert_equal :circuit_open, kinds.last
+    kinds.first(5).each { |kind| assert_includes [:connect_refused, :connect], kind }
+  ensure
+    server&.close
+  end
+
+  def test_error_kind_connect_refused
+    err = assert_raises(Wreq::Error) { Wreq::Client.new.get("http://127.0.0.1:1/") }
+    assert_includes [:connect_refused, :connect], err.error_kind
+  end
+
+  def test_error_kind_dns
+    err = assert_raises(Wreq::Error) { Wreq::Client.new.get("https://does-not-exist.invalid/") }
+    assert_equal :dns, err.error_kind
+    err = assert_raises(Wreq::Error) { Wreq::Client.new(dns_cache_ttl: 60).get("https://does-not-exist.invalid/") }
+    assert_equal :dns, err.error_kind
+    err = assert_raises(Wreq::Error) { Wreq.get("https://does-not-exist.invalid/") }
+    assert_equal :dns, err.error_kind
+  end
+
+  def test_error_kind_tls_for_untrusted_certificate
+    server = TestServer.new(tls: true) { |_req| TestServer.response(200, "ok") }
+    err = assert_raises(Wreq::TlsError) { Wreq::Client.new.get(server.url) }
+    assert_equal :tls, err.error_kind
+  ensure
+    server&.close
+  end
+
+  def test_error_kind_nil_for_option_errors
+    err = assert_raises(Wreq::Error) { Wreq::Client.new(emulation: "nope_1") }
+    assert_nil err.error_kind
+  end
+
+  def test_transport_errors_expose_causes
+    err = assert_raises(Wreq::Error) { Wreq::Client.new.get("http://127.0.0.1:1/") }
+    refute_empty err.causes
+    assert err.causes.all? { |c| c.is_a?(String) }
+    assert_equal [], Wreq::Error.new("plain").causes
+  end
+
+  def test_invalid_header_error
+    err = assert_raises(Wreq::InvalidHeaderError) do
+      Wreq::Client.new.get("http://127.0.0.1:1/", headers: { "X-Bad" => "a\nb" })
+    end
+    assert_kind_of Wreq::Error, err
+    assert_match(/X-Bad/, err.message)
+    refute_empty err.causes
+    assert_raises(Wreq::InvalidHeaderError) { Wreq::Client.new(headers: { "bad name" => "v" }) }
+  end
+
+  def test_json_errors_keep_ruby_cause
+    err = assert_raises(Wreq::DecodeError) { Wreq::Client.new.post("http://127.0.0.1:1/", json: { n: Float::NAN }) }
+    assert_kind_of JSON::GeneratorError, err.cause
+
+    server = TestServer.new { |_req| TestServer.response(200, "<html>", "Content-Type" => "text/html") }
+    err = assert_raises(Wreq::DecodeError) { Wreq::Client.new.get_json(server.url) }
+    assert_kind_of JSON::ParserError, err.cause
+  ensure
+    server&.close
+  end
+
+  def test_hooks_receive_meta
+    server = TestServer.new { |_req| TestServer.response(201) }
+    events = []
+    client = Wreq::Client.new(
+      on_request: ->(info) { events << [:request, info] },
+      on_response: ->(resp, info) { events << [:response, resp.status, info] }
+    )
+    meta = { request_id: "abc" }
+    client.post(server.url, body: "x", meta: meta)
+
+    assert_equal [:request, :response], events.map(&:first)
+    request_info = events[0][1]
+    assert_equal "POST", request_info[:method]
+    assert_equal server.url, request_info[:url]
+    assert_same meta, request_info[:meta]
+
+    _, status, response_info = events[1]
+    assert_equal 201, status
+    assert_equal 201, response_info[:status]
+    assert_same meta, response_info[:meta]
+    assert_kind_of Float, response_info[:elapsed]
+    refute server.requests.last[:headers].values.any? { |v| v.include?("abc") }
+  ensure
+    server&.close
+  end
+
+  def test_before_request_signs_outgoing_request
+    require "openssl"
+    server = TestServer.new { |_req| TestServer.response(200) }
+    seen = nil
+    signer = lambda do |req|
+      seen = req.dup
+      req[:headers]["x-signature"] = OpenSSL::HMAC.hexdigest("SHA256", "key", "#{req[:method]} #{req[:url]} #{req[:body]}")
+      req[:headers].delete("x-drop")
+      { "x-extra" => "1" }
+    end
+    client = Wreq::Client.new(before_request: signer)
+    client.post(server.url("/sign"), body: "payload", headers: { "X-Drop" => "gone", "X-Keep" => "kept" })
+
+    assert_equal "POST", seen[:method]
+    assert_equal server.url("/sign"), seen[:url]
+    assert_equal "payload", seen[:body]
+    headers = server.requests.last[:headers]
+    assert_equal OpenSSL::HMAC.hexdigest("SHA256", "key", "POST #{server.url("/sign")} payload"), headers["x-signature"]
+    assert_equal "1", headers["x-extra"]
+    assert_equal "kept", headers["x-keep"]
+    assert_nil headers["x-drop"]
+  ensure
+    server&.close
+  end
+
+  def test_before_request_exception_aborts_request
+    server = TestServer.new { |_req| TestServer.response(200) }
+    client = Wreq::Client.new(before_request: ->(_req) { raise ArgumentError, "no key" })
+    err = assert_raises(ArgumentError) { client.get(server.url) }
+    assert_equal "no key", err.message
+    assert_empty server.requests
+    assert_raises(Wreq::Error) { Wreq::Client.new(before_request: 42) }
+  ensure
+    server&.close
+  end
+
+  def test_hook_must_be_callable
+    assert_raises(Wreq::Error) { Wreq::Client.new(on_request: 42) }
+  end
+
+  def test_with_timeout_scopes_and_nests
+    server = TestServer.new do |req|
+      sleep 0.5 if req[:path] == "/slow"
+      TestServer.response(200)
+    end
+    client = Wreq::Client.new
+
+    client.with_timeout(5) do
+      client.with_timeout(0.1) do
+        assert_raises(Wreq::Error) { client.get(server.url("/slow")) }
+        # an explicit per-request timeout still wins
+        assert_equal 200, client.get(server.url("/slow"), timeout: 5).status
+      end
+      assert_equal 200, client.get(server.url("/slow")).status
+    end
+    assert_equal 200, client.get(server.url("/slow")).status
+  ensure
+    server&.close
+  end
+
+  def test_with_timeout_restored_after_exception
+    server = TestServer.new do |req|
+      sleep 0.5 if req[:path] == "/slow"
+      TestServer.response(200)
+    end
+    client = Wreq::Client.new
+    assert_raises(RuntimeError) { client.with_timeout(0.1) { raise "boom" } }
+    assert_equal 200, client.get(server.url("/slow")).status
+  ensure
+    server&.close
+  end
+
+  def test_with_timeout_is_fiber_local
+    server = TestServer.new do |req|
+      sleep 0.5 if req[:path] == "/slow"
+      TestServer.response(200)
+    end
+    client = Wreq::Client.new
+
+    client.with_timeout(0.1) do
+      assert_equal 200, Fiber.new { client.get(server.url("/slow")).status }.resume
+    end
+    Fiber.new { client.with_timeout(0.1) { Fiber.yield } }.resume
+    assert_equal 200, client.get(server.url("/slow")).status
+  ensure
+    server&.close
+  end
+
+  def test_with_cookies_scopes_and_nests
+    server = TestServer.new { |_req| TestServer.response(200) }
+    client = Wreq::Client.new(cookie_store: true)
+    client.cookie_jar.add("b=jar; Path=/", server.url)
+
+    client.with_cookies({ "a" => "1", b: 2 }, server.url) do
+      client.get(server.url("/outer"))
+      client.with_cookies({ "b" => "3", "c" => "4" }, server.url) do
+        client.get(server.url("/inner"))
+      end
+      client.get(server.url("/unwound"))
+    end
+    client.get(server.url("/after"))
+
+    cookies = server.requests.map { |r| r[:headers]["cookie"]&.split("; ")&.sort }
+    assert_equal [%w[a=1 b=2], %w[a=1 b=3 c=4], %w[a=1 b=2], %w[b=jar]], cookies
+  ensure
+    server&.close
+  end
+
+  def test_with_cookies_restored_after_exception
+    server = TestServer.new { |_req| TestServer.response(200) }
+    client = Wreq::Client.new(cookie_store: true, base_url: server.url)
+    assert_raises(RuntimeError) { client.with_cookies("a" => "1") { raise "boom" } }
+    client.get("/")
+    assert_nil server.requests.last[:headers]["cookie"]
+    assert_raises(Wreq::Error) { client.with_cookies("bad name" => "x") {} }
+    assert_raises(Wreq::Error) { Wreq::Client.new.with_cookies({ "a" => "1" }, server.url) {} }
+  ensure
+    server&.close
+  end
+
+  def test_allowed_methods_parses_allow_header
+    server = TestServer.new do |req|
+      if req[:path] == "/items"
+        TestServer.response(200, "", "Allow" => ["GET,post , HEAD", "OPTIONS, GET"])
+      else
+        TestServer.response(200)
+      end
+    end
+    client = Wreq::Client.new
+    assert_equal %w[GET POST HEAD OPTIONS], client.allowed_methods(server.url("/items"))
+    assert_equal "OPTIONS", server.requests.last[:method]
+    assert_equal [], client.allowed_methods(server.url("/none"))
+  ensure
+    server&.close
+  end
+
+  def test_pin_sha256_matching_and_mismatching
+    server = TestServer.new(tls: true) { |_req| TestServer.response(200, "ok", "Set-Cookie" => "sid=1; Path=/") }
+    pin = [OpenSSL::Digest::SHA256.digest(server.cert.public_key.to_der)].pack("m0")
+
+    client = Wreq::Client.new(verify_cert: false, pin_sha256: pin)
+    assert_equal 200, client.get(server.url).status
+
+    wrong = ["\0" * 32].pack("m0")
+    client = Wreq::Client.new(verify_cert: false, cookie_store: true, pin_sha256: [wrong])
+    err = assert_raises(Wreq::TlsError) { client.get(server.url) }
+    assert_equal :tls, err.error_kind
+    assert_equal "[]", client.cookie_jar.dump
+  ensure
+    server&.close
+  end
+
+  def test_pin_sha256_refuses_cross_origin_redirects
+    other = TestServer.new { |_req| TestServer.response(200, "elsewhere") }
+    server = TestServer.new(tls: true) do |req|
+      case req[:path]
+      when "/away" then TestServer.response(302, "", "Location" => other.url("/"))
+      when "/here" then TestServer.response(302, "", "Location" => "/done")
+      else TestServer.response(200, "done")
+      end
+    end
+    pin = [OpenSSL::Digest::SHA256.digest(server.cert.public_key.to_der)].pack("m0")
+    client = Wreq::Client.new(verify_cert: false, pin_sha256: pin)
+
+    assert_equal "done", client.get(server.url("/here")).text
+    err = assert_raises(Wreq::Error) { client.get(server.url("/away")) }
+    assert_equal :redirect, err.error_kind
+    err = assert_raises(Wreq::Error) { client.get(server.url("/away"), max_redirects: 5) }
+    assert_equal :redirect, err.error_kind
+    assert_empty other.requests
+  ensure
+    server&.close
+    other&.close
+  end
+
+  def test_pin_sha256_rejects_malformed_pin
+    assert_raises(Wreq::Error) { Wreq::Client.new(pin_sha256: "not a pin") }
+  end
+
+  def test_default_headers_include_emulation_and_user_headers
+    client = Wreq::Client.new(
+      user_agent: "Custom/1.0",
+      headers: { "X-Team" => "core", "Authorization" => "Bearer secret" }
+    )
+    headers = client.default_headers
+    assert_equal "Custom/1.0", headers["user-agent"]
+    assert_equal "core", headers["x-team"]
+    assert_equal "[REDACTED]", headers["authorization"]
+    assert headers.key?("accept"), "expected emulation headers"
+
+    plain = Wreq::Client.new(emulation: false, headers: { "X-Only" => "1" })
+    assert_equal({ "x-only" => "1" }, plain.default_headers)
+  end
+
+  def test_proxy_schemes_accepted
+    %w[http://127.0.0.1:8080 https://127.0.0.1:8443 socks4://127.0.0.1:1080
+       socks4a://127.0.0.1:1080 socks5://127.0.0.1:1080 socks5h://127.0.0.1:1080].each do |url|
+      assert_kind_of Wreq::Client, Wreq::Client.new(proxy: url), url
+    end
+    assert_kind_of Wreq::Client,
+      Wreq::Client.new(proxy: "socks5://127.0.0.1:1080", proxy_user: "u@x", proxy_pass: "p:w")
+  end
+
+  def test_proxy_credentials_with_reserved_characters
+    proxy = TestServer.new { |_req| TestServer.response(200, "via proxy") }
+    user = "dom\\ain@corp:1"
+    pass = "p@ss:w/rd?#é"
+    client = Wreq::Client.new(proxy: proxy.url(""), proxy_user: user, proxy_pass: pass)
+    assert_equal "via proxy", client.get("http://upstream.invalid/").text
+    auth = proxy.requests.last[:headers]["proxy-authorization"]
+    assert_equal "#{user}:#{pass}".b, auth.delete_prefix("Basic ").unpack1("m0")
+
+    Wreq::Client.new(proxy: proxy.url(""), proxy_user: "token-only").get("http://upstream.invalid/")
+    auth = proxy.requests.last[:headers]["proxy-authorization"]
+    assert_equal "token-only:", auth.delete_prefix("Basic ").unpack1("m0")
+  ensure
+    proxy&.close
+  end
+
+  def test_proxy_unsupported_scheme_names_scheme
+    err = assert_raises(Wreq::Error) { Wreq::Client.new(proxy: "ftp://127.0.0.1:21") }
+    assert_includes err.message, "ftp"
+  end
+
+  def test_socks4_rejects_password
+    assert_raises(Wreq::Error) do
+      Wreq::Client.new(proxy: "socks4://127.0.0.1:1080", proxy_user: "u", proxy_pass: "p")
+    end
+  end
+
+  def test_ocsp_option_accepted
+    assert_equal 200, Wreq::Client.new(ocsp: false).get("https://httpbin.org/get").status
+  end
+
+  def test_try_get_returns_result
+    server = TestServer.new { |_req| TestServer.response(404, "missing") }
+    client = Wreq::Client.new(connect_timeout: 2)
+
+    ok = client.try_get(server.url)
+    assert ok.ok?
+    assert_equal 404, ok.response.status
+    assert_nil ok.error
+
+    failed = client.try_post("http://127.0.0.1:1/", body: "x")
+    refute failed.ok?
+    assert_nil failed.response
+    assert_kind_of Wreq::Error, failed.error
+    assert_includes [:connect_refused, :connect], failed.error.error_kind
+  ensure
+    server&.close
+  end
+
+  def test_try_get_still_raises_non_wreq_errors
+    assert_raises(TypeError) { Wreq::Client.new.try_get(42) }
+  end
+
+  def test_base_url_resolution
+    server = TestServer.new { |_req| TestServer.response(200) }
+    origin = "http://127.0.0.1:#{server.port}"
+    cases = [
+      ["/api/v1", "users", "/api/v1/users"],
+      ["/api/v1/", "users", "/api/v1/users"],
+      ["/api/v1", "users?page=2#top", "/api/v1/users?page=2"],
+      ["/api/v1", "/health", "/health"],
+      ["/api/v1", "../v2/users", "/api/v2/users"],
+      ["/api/v1", "?page=2", "/api/v1?page=2"],
+      ["/api/v1?key=k", "?page=2", "/api/v1?key=k&page=2"],
+      ["/api/v1?key=k", "users", "/api/v1/users"],
+      ["/api/v1", "", "/api/v1/"],
+      ["/api/v1", "#frag", "/api/v1/"],
+      ["/elsewhere", "#{origin}/abs?x=1#frag", "/abs?x=1"],
+    ]
+    cases.each do |base, input, expected|
+      client = Wreq::Client.new(base_url: origin + base)
+      client.get(input)
+      assert_equal expected, server.requests.last[:path], "#{base.inspect} + #{input.inspect}"
+    end
+  ensure
+    server&.close
+  end
+
+  def test_base_uri
+    assert_nil Wreq::Client.new.base_uri
+    assert_equal "https://api.example.com/v1", Wreq::Client.new(base_url: "https://api.example.com/v1").base_uri
+    assert_raises(Wreq::Error) { Wreq::Client.new(base_url: "not a url") }
+  end
+
+  def test_verify_host_false_still_verifies_the_chain
+    server = TestServer.new(tls: true) { |_req| TestServer.response(200, "ok") }
+    # The certificate names 127.0.0.1 but is self-signed, so only turning
+    # off chain verification lets it through.
+    assert_raises(Wreq::TlsError) { Wreq::Client.new.get(server.url) }
+    assert_raises(Wreq::TlsError) { Wreq::Client.new(verify_host: false).get(server.url) }
+    assert_equal 200, Wreq::Client.new(verify_cert: false).get(server.url).status
+  ensure
+    server&.close
+  end
+
+  def test_verify_hostname_false_accepts_name_mismatch_by_ip
+    require "resolv"
+    ip = Resolv.getaddresses("httpbin.org").find { |a| a.match?(/\A\d+(\.\d+){3}\z/) }
+    skip "httpbin.org has no IPv4 address" unless ip
+    url = "https://#{ip}/get"
+
+    assert_raises(Wreq::TlsError) { Wreq::Client.new.get(url) }
+    assert_equal 200, Wreq::Client.new(verify_hostname: false).get(url).status
+    assert_equal 200, Wreq::Client.new(verify_host: false).get(url).status
+  end
+
+  def test_reuse_connections_false_opens_new_connections
+    server = TestServer.new { |_req| TestServer.response(200, "ok") }
+    pooled = Wreq::Client.new
+    2.times { pooled.get(server.url) }
+    assert_equal 1, server.connections
+
+    fresh = Wreq::Client.new(reuse_connections: false)
+    2.times { fresh.get(server.url) }
+    assert_equal 3, server.connections
+  ensure
+    server&.close
+  end
+
+  def test_proxies_rotate_round_robin
+    proxies = %w[a b c].map do |tag|
+      TestServer.new { |_req| TestServer.response(200, tag) }
+    end
+    client = Wreq::Client.new(proxies: proxies.map { |p| p.url("") })
+
+    tags = 6.times.map { client.get("http://upstream.invalid/").text }
+    assert_equal %w[a b c a b c], tags
+    assert_equal "http://upstream.invalid/", proxies[0].requests.last[:path]
+    assert_equal "c", client.get("http://upstream.invalid/", proxy: proxies[2].url("")).text
+    assert_equal "a", client.get("http://upstream.invalid/").text
+
+    random = Wreq::Client.new(proxies: proxies.map { |p| p.url("") }, proxy_strategy: :random)
+    20.times { assert_includes %w[a b c], random.get("http://upstream.invalid/").text }
+    assert_raises(Wreq::Error) { Wreq::Client.new(proxies: ["http://p:1"], proxy_strategy: :fastest) }
+  ensure
+    proxies&.each(&:close)
+  end
+
+  def test_durations_accept_numbers_and_strings
+    server = TestServer.new do |req|
+      sleep 0.5 if req[:path] == "/slow"
+      TestServer.response(200, "ok")
+    end
+    [1, 1.5, "2s", "1500ms", "1m"].each do |timeout|
+      assert_equal 200, Wreq::Client.new(timeout: timeout, connect_timeout: timeout).get(server.url).status
+    end
+    assert_raises(Wreq::Error) { Wreq::Client.new.get(server.url("/slow"), timeout: "100ms") }
+    assert_raises(Wreq::Error) { Wreq::Client.new.get(server.url("/slow"), timeout: 0.1) }
+
+    [0, -1, "0s", "soon", "5 parsecs", Float::INFINITY, :fast].each do |bad|
+      assert_raises(Wreq::Error, "timeout: #{bad.inspect}") { Wreq::Client.new(timeout: bad) }
+    end
+    Wreq::Client.new(dns_cache_ttl: 0)
+    assert_raises(Wreq::Error) { Wreq::Client.new(dns_cache_ttl: -5) }
+    assert_raises(Wreq::Error) { Wreq::Client.new.with_timeout("0ms") { } }
+  ensure
+    server&.close
+  end
+
+  def test_runtime_build_failure_raises_wreq_error
+    require "rbconfig"
+    script = <<~RUBY
+      require "wreq-rb"
+      2.times do
+        Wreq.get("http://127.0.0.1:1/")
+      rescue Wreq::Error => e
+        puts e.message
+      end
+    RUBY
+    lib = File.expand_path("../lib", __dir__)
+    out = IO.popen({ "WREQ_RUNTIME_THREADS" => "none" }, [RbConfig.ruby, "-I", lib, "-e", script], err: [:child, :out], &:read)
+    assert $?.success?, out
+    lines = out.lines.map(&:chomp)
+    assert_equal 2, lines.size, out
+    assert lines.all? { |l| l.include?("failed to start the async runtime") && l.include?("WREQ_RUNTIME_THREADS") }, out
+  end
+
+  def test_send_request_and_dup
+    server = TestServer.new { |_req| TestServer.response(200) }
+    client = Wreq::Client.new
+    base = Wreq::Request.new(:post, server.url("/items"), headers: { "X-Trace" => "a" }, query: { page: 1 })
+    base.body = "one"
+    assert_equal "POST", base.method
+    client.send_request(base)
+
+    copy = base.dup
+    copy.headers["X-Trace"] = "b"
+    copy.query = nil
+    copy.method = "put"
+    copy.body = "two"
+    client.send_request(copy)
+    client.send_request(base)
+
+    first, second, third = server.requests
+    assert_equal ["POST", "/items?page=1", "a", "one"], [first[:method], first[:path], first[:headers]["x-trace"], first[:body]]
+    assert_equal ["PUT", "/items", "b", "two"], [second[:method], second[:path], second[:headers]["x-trace"], second[:body]]
+    assert_equal ["POST", "/items?page=1", "a", "one"], [third[:method], third[:path], third[:headers]["x-trace"], third[:body]]
+    assert_raises(Wreq::Error) { client.send_request(Wreq::Request.new) }
+  ensure
+    server&.close
+  end
+
+  def test_requests_after_close_raise
+    server = TestServer.new { |_req| TestServer.response(200) }
+    client = Wreq::Client.new
+    refute client.closed?
+    client.get(server.url)
+
+    client.close
+    assert client.closed?
+    err = assert_raises(Wreq::Error) { client.get(server.url) }
+    assert_equal "client is closed", err.message
+    assert_raises(Wreq::Error) { client.post(server.url, body: "x") }
+    assert_equal 1, server.requests.size
+  ensure
+    server&.close
+  end
+
+  def test_total_timeout_caps_redirect_chain
+    server = TestServer.new do |req|
+      sleep 0.3
+      hop = req[:path][%r{\A/hop/(\d+)\z}, 1].to_i
+      hop < 5 ? TestServer.response(302, "", "Location" => "/hop/#{hop + 1}") : TestServer.response(200, "done")
+    end
+    client = Wreq::Client.new(redirect: 10, timeout: 5, total_timeout: 0.8)
+
+    started = Process.clock_gettime(Process::CLOCK_MONOTONIC)
+    err = assert_raises(Wreq::TimeoutError) { client.get(server.url("/hop/0")) }
+    elapsed = Process.clock_gettime(Process::CLOCK_MONOTONIC) - started
+    assert_kind_of Wreq::Error, err
+    assert_equal :timeout, err.error_kind
+    assert_match(/total_timeout/, err.message)
+    assert_operator elapsed, :<, 1.5
+
+    assert_equal "done", client.get(server.url("/hop/4"), total_timeout: 3).text
+  ensure
+    server&.close
+  end
+
+  def test_total_timeout_counts_body_producer_time
+    server = TestServer.new { |_req| TestServer.response(200) }
+    body = Enumerator.new { |out| 10.times { sleep 0.1; out << "x" } }
+    assert_raises(Wreq::TimeoutError) { Wreq::Client.new(total_timeout: 0.35).post(server.url, body: body) }
+  ensure
+    server&.close
+  end
+
+  def test_proxy_chain
+    Wreq::Client.new(proxy_chain: ["http://proxy.example:8080"])
+    err = assert_raises(Wreq::Error) do
+      Wreq::Client.new(proxy_chain: ["http://corp.example:3128", "socks5h://upstream.example:1080"])
+    end
+    assert_match(/more than one proxy/, err.message)
+    assert_includes err.message, "corp.example"
+    assert_raises(Wreq::Error) { Wreq::Client.new(proxy_chain: []) }
+  end
+
+  def test_sni_hostname
+    server = TestServer.new(tls: true) { |_req| TestServer.response(200) }
+    assert_equal 200, Wreq::Client.new(tls_sni: false, verify_cert: false).get(server.url).status
+
+    err = assert_raises(Wreq::Error) { Wreq::Client.new(sni_hostname: "cdn.example.com") }
+    assert_match(/cannot send a different name/, err.message)
+    err = assert_raises(Wreq::Error) { Wreq::Client.new(sni_hostname: false) }
+    assert_match(/tls_sni: false/, err.message)
+    assert_raises(Wreq::Error) { Wreq.get(server.url, sni_hostname: "cdn.example.com") }
+  ensure
+    server&.close
+  end
+
+  def test_benchmark_summary
+    server = TestServer.new { |req| req[:path] == "/missing" ? TestServer.response(404) : TestServer.response(200, "ok") }
+    client = Wreq::Client.new
+    stats = Wreq.benchmark(server.url, count: 20, concurrency: 4, client: client)
+    assert_equal 20, stats[:requests]
+    assert_equal 0, stats[:errors]
+    assert_equal({ 200 => 20 }, stats[:statuses])
+    assert_operator stats[:requests_per_sec], :>, 0
+    assert_operator stats[:min], :<=, stats[:p50]
+    assert_operator stats[:p50], :<=, stats[:p95]
+    assert_operator stats[:p95], :<=, stats[:max]
+    assert_equal 20, server.requests.size
+    assert_operator server.connections, :<=, 4
+
+    assert_equal({ 404 => 3 }, Wreq.benchmark(server.url("/missing"), count: 3, concurrency: 1)[:statuses])
+    capped = Wreq.benchmark(server.url, count: 2, concurrency: 1, max_body_size: 1)
+    assert_equal 2, capped[:errors]
+
+    server.requests.clear
+    Wreq.benchmark(server.url, method: :post, body: "payload", count: 3, concurrency: 2)
+    assert_equal ["payload"] * 3, server.requests.map { |r| r[:body] }
+    assert_raises(Wreq::Error) { Wreq.benchmark(server.url, body_enum: %w[a b].each, count: 2) }
+
+    port = TCPServer.open("127.0.0.1", 0) { |s| s.addr[1] }
+    failed = Wreq.benchmark("http://127.0.0.1:#{port}/", count: 2, concurrency: 2)
+    assert_equal 2, failed[:errors]
+    assert_nil failed[:p50]
+  ensure
+    server&.close
+  end
+
   private
 
   # Spins up a local TCP server, yields the port formatted into a URL, captures
@@ -113,4 +1361,23 @@ class ClientTest < Minitest::Test
     server.close
     received
   end
+
+  # Serves `data` with an ETag, answering HEAD with headers only and, when
+  # `ranges` is set, `Range: bytes=N-` requests with a 206.
+  def ranged_file_server(data, ranges: true)
+    TestServer.new do |req|
+      headers = { "Content-Length" => data.bytesize, "ETag" => "\"v1\"" }
+      headers["Accept-Ranges"] = "bytes" if ranges
+      start = req[:headers]["range"]&.[](/\Abytes=(\d+)-\z/, 1)
+      if req[:method] == "HEAD"
+        TestServer.response(200, "", headers)
+      elsif ranges && start
+        part = data.byteslice(start.to_i..)
+        range = "bytes #{start}-#{data.bytesize - 1}/#{data.bytesize}"
+        TestServer.response(206, part, headers.merge("Content-Length" => part.bytesize, "Content-Range" => range))
+      else
+        TestServer.response(200, data, headers)
+      end
+    end
+  end
 end
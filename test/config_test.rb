@@ -0,0 +1,49 @@
+# frozen_string_literal: true
+
+require_relative "test_helper"
+
+class ConfigTest < Minitest::Test
+  def setup
+    @server = TestServer.new { |_req| TestServer.response(200) }
+  end
+
+  def teardown
+    Wreq.config.reset
+    @server&.close
+  end
+
+  def test_configure_applies_to_new_clients_and_module_helpers
+    Wreq.configure do |c|
+      c.user_agent = "GlobalAgent/1.0"
+      c.emulation = false
+    end
+
+    Wreq::Client.new.get(@server.url)
+    Wreq.get(@server.url)
+    assert_equal ["GlobalAgent/1.0"] * 2, @server.requests.map { |r| r[:headers]["user-agent"] }
+  end
+
+  def test_explicit_options_take_precedence
+    Wreq.configure { |c| c.user_agent = "GlobalAgent/1.0" }
+    Wreq::Client.new(user_agent: "Explicit/2.0", emulation: false).get(@server.url)
+    assert_equal "Explicit/2.0", @server.requests.last[:headers]["user-agent"]
+  end
+
+  def test_config_accessors
+    Wreq.configure { |c| c[:timeout] = 10 }
+    assert_equal 10, Wreq.config.timeout
+    assert_equal({ timeout: 10 }, Wreq.config.to_h)
+    assert Wreq.config.respond_to?(:proxy=)
+  end
+
+  def test_unknown_option_raises
+    assert_raises(NoMethodError) { Wreq.configure { |c| c.timeoutt = 1 } }
+    assert_raises(Wreq::Error) { Wreq.config[:bogus] = 1 }
+  end
+
+  def test_reset
+    Wreq.configure { |c| c.user_agent = "GlobalAgent/1.0" }
+    Wreq.config.reset
+    assert_empty Wreq.config.to_h
+  end
+end
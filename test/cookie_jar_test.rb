@@ -0,0 +1,143 @@
+# frozen_string_literal: true
+
+require_relative "test_helper"
+require "tmpdir"
+
+class CookieJarTest < Minitest::Test
+  def test_cookie_store_exposes_jar
+    client = Wreq::Client.new(cookie_store: true)
+    assert_kind_of Wreq::CookieJar, client.cookie_jar
+    assert_nil Wreq::Client.new.cookie_jar
+  end
+
+  def test_dump_and_load_round_trip
+    jar = Wreq::CookieJar.new
+    jar.add("session=abc123; Path=/; Max-Age=3600; Secure; HttpOnly", "https://httpbin.org/")
+
+    restored = Wreq::CookieJar.load(jar.dump)
+    cookie = JSON.parse(restored.dump).find { |c| c["name"] == "session" }
+    refute_nil cookie
+    assert_equal "abc123", cookie["value"]
+    assert_equal "httpbin.org", cookie["domain"]
+    assert_equal "/", cookie["path"]
+    assert cookie["secure"]
+    assert cookie["http_only"]
+    assert_in_delta Time.now.to_i + 3600, cookie["expires"], 5
+  end
+
+  def test_dump_and_load_keep_host_only_cookies
+    jar = Wreq::CookieJar.new
+    jar.add("host=1; Path=/", "https://example.com/")
+    jar.add("wide=2; Domain=example.com; Path=/", "https://example.com/")
+
+    cookies = JSON.parse(Wreq::CookieJar.load(jar.dump).dump).to_h { |c| [c["name"], c] }
+    assert cookies["host"]["host_only"]
+    refute cookies["wide"]["host_only"]
+    assert_equal "example.com", cookies["host"]["domain"]
+  end
+
+  def test_restored_jar_sends_session_cookies
+    client = Wreq::Client.new(cookie_store: true)
+    client.get("https://httpbin.org/cookies/set?session=abc123")
+    saved = client.cookie_jar.dump
+
+    restored = Wreq::Client.new(cookie_jar: Wreq::CookieJar.load(saved))
+    resp = restored.get("https://httpbin.org/cookies")
+    assert_equal "abc123", resp.json["cookies"]["session"]
+  end
+
+  def test_shared_jar_survives_concurrent_set_cookie
+    server = TestServer.new do |req|
+      name = req[:path].delete_prefix("/set/")
+      TestServer.response(200, "ok", "Set-Cookie" => "#{name}=v#{name}; Path=/")
+    end
+    jar = Wreq::CookieJar.new
+    clients = 2.times.map { Wreq::Client.new(cookie_jar: jar) }
+
+    writers = 8.times.map do |t|
+      Thread.new do
+        25.times { |i| clients[t % 2].get(server.url("/set/c#{t}_#{i}")) }
+      end
+    end
+    reader = Thread.new do
+      dumps = 0
+      while writers.any?(&:alive?)
+        JSON.parse(jar.dump)
+        jar.set(server.url, "reader", "r")
+        dumps += 1
+      end
+      dumps
+    end
+    writers.each(&:join)
+    assert_operator reader.value, :>, 0
+
+    cookies = JSON.parse(jar.dump).to_h { |c| [c["name"], c["value"]] }
+    8.times { |t| 25.times { |i| assert_equal "vc#{t}_#{i}", cookies["c#{t}_#{i}"] } }
+    assert_equal "r", cookies["reader"]
+    assert_equal 201, cookies.size
+  ensure
+    server&.close
+  end
+
+  def test_load_rejects_invalid_data
+    assert_raises(Wreq::Error) { Wreq::CookieJar.load("not json") }
+  end
+
+  def test_cookie_jar_path_load_and_save
+    Dir.mktmpdir do |dir|
+      path = File.join(dir, "cookies.txt")
+      File.write(path, <<~TXT)
+        # Netscape HTTP Cookie File
+        httpbin.org\tFALSE\t/\tTRUE\t#{Time.now.to_i + 3600}\tsession\tabc123
+        this line is corrupt
+      TXT
+
+      client = Wreq::Client.new(cookie_jar_path: path)
+      assert_equal "abc123", client.get("https://httpbin.org/cookies").json["cookies"]["session"]
+
+      client.get("https://httpbin.org/cookies/set?theme=dark")
+      client.close
+      saved = File.read(path)
+      assert_match(/\tsession\tabc123$/, saved)
+      assert_match(/\ttheme\tdark$/, saved)
+    end
+  end
+
+  def test_cookie_jar_path_missing_file
+    path = File.join(Dir.tmpdir, "wreq-missing-#{rand(1 << 32)}.txt")
+    client = Wreq::Client.new(cookie_jar_path: path)
+    assert_kind_of Wreq::CookieJar, client.cookie_jar
+  end
+
+  def test_set_scoped_cookie_only_sent_to_matching_requests
+    server = TestServer.new { |_req| TestServer.response(200) }
+    jar = Wreq::CookieJar.new
+    jar.set(server.url, "scoped", "yes", path: "/api", http_only: true, expires: Time.now + 60)
+    client = Wreq::Client.new(cookie_jar: jar)
+
+    client.get(server.url("/api/items"))
+    client.get(server.url("/other"))
+    client.get("http://localhost:#{server.port}/api/items")
+
+    cookies = server.requests.map { |r| r[:headers]["cookie"] }
+    assert_equal ["scoped=yes", nil, nil], cookies
+  ensure
+    server&.close
+  end
+
+  def test_set_accepts_string_keys_and_rejects_invalid_cookies
+    server = TestServer.new { |_req| TestServer.response(200) }
+    jar = Wreq::CookieJar.new
+    jar.set(server.url, "scoped", "yes", "path" => "/api", "secure" => false)
+    client = Wreq::Client.new(cookie_jar: jar)
+    client.get(server.url("/api"))
+    client.get(server.url("/other"))
+    assert_equal ["scoped=yes", nil], server.requests.map { |r| r[:headers]["cookie"] }
+
+    assert_raises(Wreq::Error) { jar.set(server.url, "bad;name", "x") }
+    assert_raises(Wreq::Error) { jar.set(server.url, "name", "a; Domain=evil.example") }
+    assert_raises(Wreq::Error) { jar.set(server.url, "", "x") }
+  ensure
+    server&.close
+  end
+end
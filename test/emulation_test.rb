@@ -51,4 +51,62 @@ class EmulationTest < Minitest::Test
       Wreq::Client.new(emulation: "chrome_145", emulation_os: "beos")
     end
   end
+
+  def test_locale_replaces_emulation_accept_language
+    client = Wreq::Client.new(emulation: "chrome_145", locale: "fr-FR")
+    resp = client.get("https://httpbin.org/headers")
+    assert_equal "fr-FR,fr;q=0.9", resp.json["headers"]["Accept-Language"]
+  end
+
+  def test_accept_language_without_emulation
+    client = Wreq::Client.new(emulation: false, accept_language: "de;q=1.0")
+    resp = client.get("https://httpbin.org/headers")
+    assert_equal "de;q=1.0", resp.json["headers"]["Accept-Language"]
+  end
+
+  def test_weighted_locale_array
+    server = TestServer.new { |_req| TestServer.response(200) }
+    client = Wreq::Client.new(emulation: "chrome_145", locale: ["en-US", "en", "fr"])
+    client.get(server.url)
+    assert_equal "en-US,en;q=0.9,fr;q=0.8", server.requests.last[:headers]["accept-language"]
+
+    client.get(server.url, locale: [["de-DE", 1], ["de", 0.75], ["*", 0.1]])
+    assert_equal "de-DE,de;q=0.75,*;q=0.1", server.requests.last[:headers]["accept-language"]
+
+    client.get(server.url, locale: "pt-BR")
+    assert_equal "pt-BR,pt;q=0.9", server.requests.last[:headers]["accept-language"]
+  ensure
+    server&.close
+  end
+
+  def test_invalid_locale
+    assert_raises(Wreq::Error) { Wreq::Client.new(locale: "fr FR") }
+    assert_raises(Wreq::Error) { Wreq::Client.new(locale: [["fr", 2]]) }
+    err = assert_raises(Wreq::Error) { Wreq::Client.new(accept_language: []) }
+    assert_equal "accept_language: array must not be empty", err.message
+  end
+
+  def test_module_default_emulation
+    assert_equal "chrome_145", Wreq.default_emulation
+    server = TestServer.new { |_req| TestServer.response(200) }
+    chrome = Wreq::Client.new
+
+    Wreq.default_emulation = "firefox_146"
+    assert_equal "firefox_146", Wreq.default_emulation
+    Wreq::Client.new.get(server.url)
+    assert_match(/Firefox/, server.requests.last[:headers]["user-agent"])
+
+    # Clients built earlier keep their emulation.
+    chrome.get(server.url)
+    assert_match(/Chrome/, server.requests.last[:headers]["user-agent"])
+
+    assert_raises(Wreq::Error) { Wreq.default_emulation = "netscape_4" }
+    assert_equal "firefox_146", Wreq.default_emulation
+
+    Wreq.default_emulation = nil
+    assert_equal "chrome_145", Wreq.default_emulation
+  ensure
+    Wreq.default_emulation = nil
+    server&.close
+  end
 end
@@ -44,6 +44,74 @@ class RequestTest < Minitest::Test
     assert_equal "qux", body["args"]["baz"]
   end
 
+  def test_nested_query_uses_bracket_notation
+    server = TestServer.new { |_req| TestServer.response(200) }
+    query = { filter: { status: "open", tags: ["a", "b"] }, page: 2 }
+    Wreq.get(server.url("/search"), query: query, nested: true)
+
+    path = server.requests.last[:path]
+    assert_equal "/search?filter%5Bstatus%5D=open&filter%5Btags%5D%5B%5D=a&filter%5Btags%5D%5B%5D=b&page=2", path
+  ensure
+    server&.close
+  end
+
+  def test_raw_query_skips_encoding
+    server = TestServer.new { |_req| TestServer.response(200) }
+    value = "a+b%20c"
+
+    Wreq.get(server.url("/q"), query: { q: value })
+    assert_equal "/q?q=a%2Bb%2520c", server.requests.last[:path]
+
+    Wreq.get(server.url("/q"), query: { q: value }, raw_query: true)
+    assert_equal "/q?q=a+b%20c", server.requests.last[:path]
+
+    Wreq.get(server.url("/q"), query: "?q=#{value}&sig=x%2Fy")
+    assert_equal "/q?q=a+b%20c&sig=x%2Fy", server.requests.last[:path]
+
+    # Appended to the URL's own query, which is kept as written.
+    Wreq.get(server.url("/q?x=1%2B1"), query: { q: value }, raw_query: true)
+    assert_equal "/q?x=1%2B1&q=a+b%20c", server.requests.last[:path]
+  ensure
+    server&.close
+  end
+
+  def test_nested_form_and_flat_default
+    server = TestServer.new { |_req| TestServer.response(200) }
+    Wreq.post(server.url, form: { ids: [1, 2], user: { name: "Ada" } }, nested: true)
+    assert_equal "ids%5B%5D=1&ids%5B%5D=2&user%5Bname%5D=Ada", server.requests.last[:body]
+
+    Wreq.post(server.url, form: { ids: [1, 2] })
+    assert_equal "ids=%5B1%2C+2%5D", server.requests.last[:body]
+  ensure
+    server&.close
+  end
+
+  def test_get_with_json_body
+    server = TestServer.new { |_req| TestServer.response(200) }
+    query = { "query" => { "match" => { "title" => "wreq" } } }
+    Wreq.get(server.url("/_search"), json: query)
+
+    req = server.requests.last
+    assert_equal "GET", req[:method]
+    assert_equal "application/json", req[:headers]["content-type"]
+    assert_equal JSON.generate(query).bytesize.to_s, req[:headers]["content-length"]
+    assert_equal query, JSON.parse(req[:body])
+  ensure
+    server&.close
+  end
+
+  def test_delete_with_body
+    server = TestServer.new { |_req| TestServer.response(200) }
+    Wreq.delete(server.url("/items"), body: "ids=1,2,3")
+
+    req = server.requests.last
+    assert_equal "DELETE", req[:method]
+    assert_equal "9", req[:headers]["content-length"]
+    assert_equal "ids=1,2,3", req[:body]
+  ensure
+    server&.close
+  end
+
   def test_head_request
     resp = Wreq.head("https://httpbin.org/get")
     assert_equal 200, resp.status
@@ -89,4 +157,393 @@ class RequestTest < Minitest::Test
     assert_nil body["headers"]["X-Nil"]
     assert_equal "ok", body["headers"]["X-Str"]
   end
+
+  def test_multipart_binary_field
+    server = TestServer.new { |_req| TestServer.response(200) }
+    bytes = "\x00\xFF\xFE\x80sig".b
+    Wreq.post(server.url, multipart: { "text" => "hello", "sig" => bytes })
+
+    body = server.requests.last[:body]
+    assert_includes body, bytes
+    assert_includes body, "Content-Type: application/octet-stream"
+    assert_includes body, "hello"
+  ensure
+    server&.close
+  end
+
+  def test_large_body_streamed_with_content_length
+    server = TestServer.new { |_req| TestServer.response(200) }
+    body = "x" * 300_000
+    client = Wreq::Client.new(stream_body_over: 1024)
+    client.post(server.url, body: body)
+
+    req = server.requests.last
+    assert_equal body.bytesize.to_s, req[:headers]["content-length"]
+    assert_nil req[:headers]["transfer-encoding"]
+    assert_equal body, req[:body]
+  ensure
+    server&.close
+  end
+
+  def test_large_body_streamed_without_copying
+    skip "peak RSS can only be reset on Linux" unless File.writable?("/proc/self/clear_refs")
+    peak_rss = -> { File.read("/proc/self/status")[/VmHWM:\s+(\d+)/, 1].to_i * 1024 }
+    server = TCPServer.new("127.0.0.1", 0)
+    received = 0
+    sink = Thread.new do
+      conn = server.accept
+      length = 0
+      while (line = conn.gets) && line != "\r\n"
+        name, value = line.split(":", 2)
+        length = value.to_i if name.casecmp?("content-length")
+      end
+      received += conn.read([length - received, 65_536].min).bytesize while received < length
+      conn.write("HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+      conn.close
+    end
+    body = "x" * (64 * 1024 * 1024)
+
+    File.write("/proc/self/clear_refs", "5")
+    baseline = peak_rss.call
+    Wreq::Client.new(stream_body_over: 1024).post("http://127.0.0.1:#{server.addr[1]}/", body: body)
+    sink.join(5)
+    assert_equal body.bytesize, received
+    assert_operator peak_rss.call - baseline, :<, 32 * 1024 * 1024
+  ensure
+    sink&.kill
+    server&.close
+  end
+
+  def test_enumerator_body_streamed_chunked
+    server = TestServer.new { |_req| TestServer.response(200) }
+    chunk = "x" * 65_536
+    body = Enumerator.new { |out| 64.times { out << chunk } }
+    Wreq.post(server.url, body: body)
+
+    req = server.requests.last
+    assert_equal "chunked", req[:headers]["transfer-encoding"]
+    assert_equal 64 * chunk.bytesize, req[:body].bytesize
+  ensure
+    server&.close
+  end
+
+  def test_block_body_with_content_length
+    server = TestServer.new { |_req| TestServer.response(200) }
+    producer = proc { |out| 3.times { |i| out << "part#{i};" } }
+    Wreq.post(server.url, body: producer, headers: { "Content-Length" => "18" })
+
+    req = server.requests.last
+    assert_equal "18", req[:headers]["content-length"]
+    assert_nil req[:headers]["transfer-encoding"]
+    assert_equal "part0;part1;part2;", req[:body]
+  ensure
+    server&.close
+  end
+
+  def test_enumerator_body_error_propagates
+    server = TestServer.new { |_req| TestServer.response(200) }
+    body = Enumerator.new { |out| out << "a"; raise ArgumentError, "boom" }
+    err = assert_raises(ArgumentError) { Wreq.post(server.url, body: body) }
+    assert_equal "boom", err.message
+  ensure
+    server&.close
+  end
+
+  class ChunkSource
+    def initialize(chunks, fail_after: nil)
+      @chunks = chunks
+      @fail_after = fail_after
+    end
+
+    def each
+      @chunks.each_with_index do |chunk, i|
+        raise IOError, "cipher failed" if i == @fail_after
+        yield chunk
+      end
+    end
+  end
+
+  def test_body_enum_streams_each
+    server = TestServer.new { |_req| TestServer.response(200) }
+    Wreq.post(server.url, body_enum: ChunkSource.new(%w[enc1 enc2 enc3]))
+    req = server.requests.last
+    assert_equal "chunked", req[:headers]["transfer-encoding"]
+    assert_equal "enc1enc2enc3", req[:body]
+
+    Wreq.post(server.url, body_enum: ChunkSource.new(%w[ab cd]), content_length: 4)
+    assert_equal "4", server.requests.last[:headers]["content-length"]
+    assert_equal "abcd", server.requests.last[:body]
+  ensure
+    server&.close
+  end
+
+  def test_request_body_size_counts_sent_bytes
+    server = TestServer.new { |_req| TestServer.response(200) }
+    client = Wreq::Client.new
+    assert_equal 0, client.get(server.url).request_body_size
+    assert_equal 5, client.post(server.url, body: "hello").request_body_size
+    assert_equal 12, client.post(server.url, body_enum: ChunkSource.new(%w[enc1 enc2 enc3])).request_body_size
+    assert_equal "enc1enc2enc3", server.requests.last[:body]
+  ensure
+    server&.close
+  end
+
+  def test_streamed_body_applies_backpressure
+    chunk = "x" * 65_536
+    total_chunks = 512 # 32 MiB
+    produced = 0
+    body = Enumerator.new do |out|
+      total_chunks.times do
+        produced += 1
+        out << chunk
+      end
+    end
+
+    server = TCPServer.new("127.0.0.1", 0)
+    received = 0
+    server_thread = Thread.new do
+      conn = server.accept
+      nil while conn.gets != "\r\n"
+      sleep 1 # a stalled reader: the producer must wait instead of buffering
+      Thread.current[:produced_while_stalled] = produced
+      while (data = conn.readpartial(65_536) rescue nil)
+        received += data.bytesize
+        break if data.end_with?("0\r\n\r\n")
+      end
+      conn.write("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+      conn.close
+    end
+
+    resp = Wreq.post("http://127.0.0.1:#{server.addr[1]}/", body: body)
+    server_thread.join
+    assert_equal 200, resp.status
+    assert_equal total_chunks, produced
+    assert_operator received, :>, chunk.bytesize * total_chunks
+    # Socket buffers hold a few MiB; without backpressure all 32 MiB would
+    # have been pulled from the enumerator during the stall.
+    assert_operator server_thread[:produced_while_stalled], :<, total_chunks / 2
+  ensure
+    server&.close
+  end
+
+  def test_body_enum_error_aborts_with_wreq_error
+    server = TestServer.new { |_req| TestServer.response(200) }
+    err = assert_raises(Wreq::Error) do
+      Wreq.post(server.url, body_enum: ChunkSource.new(%w[a b c], fail_after: 1))
+    end
+    assert_kind_of IOError, err.cause
+    assert_equal "cipher failed", err.cause.message
+    assert_raises(Wreq::Error) { Wreq.post(server.url, body_enum: 42) }
+  ensure
+    server&.close
+  end
+
+  def test_content_length_option_for_enumerator_body
+    server = TestServer.new { |_req| TestServer.response(200) }
+    body = Enumerator.new { |out| out << "hello "; out << "world" }
+    Wreq.post(server.url, body: body, content_length: 11)
+
+    req = server.requests.last
+    assert_equal "11", req[:headers]["content-length"]
+    assert_nil req[:headers]["transfer-encoding"]
+    assert_equal "hello world", req[:body]
+  ensure
+    server&.close
+  end
+
+  def test_content_length_mismatch_raises
+    server = TestServer.new { |_req| TestServer.response(200) }
+    short = Enumerator.new { |out| out << "abc" }
+    err = assert_raises(Wreq::Error) { Wreq.post(server.url, body: short, content_length: 10) }
+    assert_match(/content_length/, err.message)
+
+    long = Enumerator.new { |out| out << "abc"; out << "def" }
+    assert_raises(Wreq::Error) { Wreq.post(server.url, body: long, content_length: 4) }
+
+    assert_raises(Wreq::Error) { Wreq.post(server.url, body: "abc", content_length: 4) }
+  ensure
+    server&.close
+  end
+
+  def test_proc_body_evaluated_per_request
+    server = TestServer.new { |_req| TestServer.response(200) }
+    calls = 0
+    opts = { body: -> { calls += 1; "nonce=#{calls}" } }
+    assert_equal 0, calls
+
+    2.times { Wreq.post(server.url, opts) }
+    assert_equal 2, calls
+    assert_equal ["nonce=1", "nonce=2"], server.requests.map { |r| r[:body] }
+  ensure
+    server&.close
+  end
+
+  def test_multipart_streams_file_part
+    require "tempfile"
+    file = Tempfile.new(["upload", ".bin"])
+    file.binmode
+    file.write(Random.new(1).bytes(5 * 1024 * 1024))
+    file.close
+
+    server = TestServer.new { |_req| TestServer.response(200) }
+    Wreq.post(server.url, multipart: { "file" => { path: file.path } })
+
+    req = server.requests.last
+    body = req[:body]
+    assert_equal body.bytesize.to_s, req[:headers]["content-length"]
+    assert_includes body, %(filename="#{File.basename(file.path)}")
+    assert_includes body, File.binread(file.path)
+  ensure
+    server&.close
+    file&.unlink
+  end
+
+  def test_referer_option_and_headers_precedence
+    server = TestServer.new { |_req| TestServer.response(200) }
+    Wreq.get(server.url, referer: "https://example.com/from")
+    assert_equal "https://example.com/from", server.requests.last[:headers]["referer"]
+
+    Wreq.get(server.url, referer: "https://example.com/from", headers: { "Referer" => "https://example.com/explicit" })
+    assert_equal "https://example.com/explicit", server.requests.last[:headers]["referer"]
+  ensure
+    server&.close
+  end
+
+  def test_auto_referer_across_redirect_chain
+    server = TestServer.new do |req|
+      case req[:path]
+      when "/a" then TestServer.response(302, "", "Location" => "/b")
+      when "/b" then TestServer.response(302, "", "Location" => "/c")
+      else TestServer.response(200, "done")
+      end
+    end
+
+    Wreq::Client.new(redirect: 5, auto_referer: true).get(server.url("/a"))
+    referers = server.requests.to_h { |r| [r[:path], r[:headers]["referer"]] }
+    assert_nil referers["/a"]
+    assert_equal server.url("/a"), referers["/b"]
+    assert_equal server.url("/b"), referers["/c"]
+
+    server.requests.clear
+    Wreq::Client.new(redirect: 5, auto_referer: false).get(server.url("/a"))
+    assert server.requests.all? { |r| r[:headers]["referer"].nil? }
+  ensure
+    server&.close
+  end
+
+  def test_per_request_max_redirects
+    server = TestServer.new do |req|
+      hop = req[:path][%r{\A/hop/(\d+)\z}, 1]&.to_i
+      hop && hop < 3 ? TestServer.response(302, "", "Location" => "/hop/#{hop + 1}") : TestServer.response(200, "done")
+    end
+    client = Wreq::Client.new(redirect: 10)
+
+    assert_equal "done", client.get(server.url("/hop/0")).text
+    assert_equal "done", client.get(server.url("/hop/0"), max_redirects: 3).text
+    err = assert_raises(Wreq::TooManyRedirectsError) { client.get(server.url("/hop/0"), max_redirects: 2) }
+    assert_kind_of Wreq::Error, err
+    assert_equal :redirect, err.error_kind
+  ensure
+    server&.close
+  end
+
+  def test_post_json_parses_response
+    server = TestServer.new do |req|
+      TestServer.response(201, JSON.generate("echo" => JSON.parse(req[:body])), "Content-Type" => "application/json")
+    end
+    client = Wreq::Client.new
+    result = client.post_json(server.url, { "name" => "Ada" })
+    assert_equal({ "echo" => { "name" => "Ada" } }, result)
+    assert_equal "application/json", server.requests.last[:headers]["content-type"]
+
+    assert_equal({ echo: { name: "Ada" } }, client.put_json(server.url, { name: "Ada" }, symbolize_keys: true))
+  ensure
+    server&.close
+  end
+
+  def test_post_form_and_post_multipart_helpers
+    server = TestServer.new do |req|
+      TestServer.response(200, req[:body], "Content-Type" => req[:headers]["content-type"])
+    end
+    client = Wreq::Client.new
+
+    resp = client.post_form(server.url, { "name" => "Ada", "lang" => "ruby" }, headers: { "X-Id" => "1" })
+    assert_equal "name=Ada&lang=ruby", resp.text
+    assert_equal "application/x-www-form-urlencoded", resp.content_type
+    assert_equal "POST", server.requests.last[:method]
+    assert_equal "1", server.requests.last[:headers]["x-id"]
+    assert_equal "ids%5B%5D=1&ids%5B%5D=2", client.post_form(server.url, { ids: [1, 2] }, nested: true).text
+
+    resp = client.post_multipart(server.url, { "text" => "hello", "file" => { content: "data", filename: "a.txt" } })
+    assert_equal "multipart/form-data", resp.content_type
+    assert_includes resp.text, %(name="text"\r\n\r\nhello)
+    assert_includes resp.text, %(filename="a.txt")
+  ensure
+    server&.close
+  end
+
+  def test_get_json_raises_on_error_status
+    server = TestServer.new { |_req| TestServer.response(422, '{"error":"invalid widget"}') }
+    err = assert_raises(Wreq::Error) { Wreq::Client.new.get_json(server.url) }
+    assert_equal :status, err.error_kind
+    assert_includes err.message, "422"
+    assert_includes err.message, "invalid widget"
+  ensure
+    server&.close
+  end
+
+  def test_module_helpers_reuse_passed_client
+    server = TestServer.new { |_req| TestServer.response(200, "ok") }
+    client = Wreq::Client.new
+    3.times { assert_equal 200, Wreq.get(server.url, client: client).status }
+    assert_equal 1, server.connections
+  ensure
+    server&.close
+  end
+
+  def test_extensions_reach_before_request_and_are_not_sent
+    server = TestServer.new { |_req| TestServer.response(200, "ok") }
+    seen = []
+    client = Wreq::Client.new(before_request: ->(req) { seen << req[:extensions] })
+    resp = client.get(server.url, extensions: { trace_id: "abc-123", attempt: 2 })
+    assert_equal "ok", resp.text
+    headers = server.requests.last[:headers]
+    refute headers.key?("trace_id")
+    refute(headers.values.any? { |v| v.include?("abc-123") })
+
+    client.get(server.url)
+    assert_equal [{ "trace_id" => "abc-123", "attempt" => "2" }, {}], seen
+
+    assert_raises(TypeError) { Wreq.get(server.url, extensions: "trace_id=abc") }
+  ensure
+    server&.close
+  end
+
+  def test_options_template_matches_raw_hash
+    server = TestServer.new { |req| TestServer.response(200, req[:path]) }
+    client = Wreq::Client.new
+    raw = { "headers" => { "X-Tag" => "t1" }, query: { page: 2 } }
+    opts = Wreq::Options.new(raw)
+
+    assert_equal "/?page=2", client.get(server.url, raw).text
+    assert_equal "/?page=2", client.get(server.url, opts).text
+    assert_equal "t1", server.requests.last[:headers]["x-tag"]
+    assert_equal "/?page=2", Wreq.get(server.url, opts).text
+    assert_equal "/?page=3", client.get(server.url, opts.merge(query: { page: 3 })).text
+    assert_equal "/?page=2", Wreq::Request.new(:get, server.url, opts).then { |r| client.send_request(r) }.text
+
+    assert opts.to_h.frozen?
+    assert opts[:headers].frozen?
+    assert_equal({ "X-Tag" => "t1" }, opts["headers"])
+    assert_equal [:headers, :query], opts.to_h.keys
+  ensure
+    server&.close
+  end
+
+  def test_options_rejects_unknown_keys_and_bad_values
+    err = assert_raises(Wreq::Error) { Wreq::Options.new(timout: 5) }
+    assert_includes err.message, "unknown request option: timout"
+    assert_raises(Wreq::Error) { Wreq::Options.new(timeout: "soon") }
+    assert_raises(Wreq::Error) { Wreq::Options.new(timeout: 5).merge(bogus: true) }
+  end
 end
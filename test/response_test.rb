@@ -63,4 +63,626 @@ class ResponseTest < Minitest::Test
     assert cookies.length >= 2,
       "expected at least 2 set-cookie values, got #{cookies.length}: #{cookies.inspect}"
   end
+
+  def test_meta_refresh
+    html = %(<html><head><META HTTP-EQUIV="Refresh" content="3; URL='/next?page=2'"></head></html>)
+    server = TestServer.new do |req|
+      if req[:path] == "/json"
+        TestServer.response(200, html, "Content-Type" => "application/json")
+      else
+        TestServer.response(200, html, "Content-Type" => "text/html; charset=utf-8")
+      end
+    end
+
+    refresh = Wreq.get(server.url("/")).meta_refresh
+    assert_equal({ delay: 3, url: "/next?page=2" }, refresh)
+    assert_nil Wreq.get(server.url("/json")).meta_refresh
+  ensure
+    server&.close
+  end
+
+  def test_content_type_and_assert_content_type
+    server = TestServer.new do |_req|
+      TestServer.response(200, "{}", "Content-Type" => "Application/JSON; charset=UTF-8")
+    end
+    resp = Wreq.get(server.url)
+    assert_equal "application/json", resp.content_type
+    assert_equal "UTF-8", resp.charset
+    assert_same resp, resp.assert_content_type("application/json")
+    assert_same resp, resp.assert_content_type("APPLICATION/JSON; charset=utf-8")
+    err = assert_raises(Wreq::DecodeError) { resp.assert_content_type("text/html") }
+    assert_kind_of Wreq::Error, err
+    assert_raises(Wreq::DecodeError) { resp.assert_content_type("application/") }
+  ensure
+    server&.close
+  end
+
+  def test_json_predicate
+    types = {
+      "/json" => "application/json; charset=utf-8",
+      "/api" => "application/vnd.api+json",
+      "/html" => "text/html",
+      "/jsonp" => "application/javascript",
+    }
+    server = TestServer.new do |req|
+      headers = types.key?(req[:path]) ? { "Content-Type" => types[req[:path]] } : {}
+      TestServer.response(200, "{}", headers)
+    end
+    assert Wreq.get(server.url("/json")).json?
+    assert Wreq.get(server.url("/api")).json?
+    refute Wreq.get(server.url("/html")).json?
+    refute Wreq.get(server.url("/jsonp")).json?
+    refute Wreq.get(server.url("/none")).json?
+  ensure
+    server&.close
+  end
+
+  def test_assert_content_type_bang_guards_html_error_pages
+    server = TestServer.new do |req|
+      if req[:path] == "/html"
+        TestServer.response(502, "<html>Bad Gateway</html>", "Content-Type" => "text/html")
+      else
+        TestServer.response(200, "{}", "Content-Type" => "application/problem+json")
+      end
+    end
+    resp = Wreq.get(server.url)
+    assert_same resp, resp.assert_content_type!("application/")
+    assert_equal({}, resp.assert_content_type!("application/problem+json").json)
+
+    html = Wreq.get(server.url("/html"))
+    err = assert_raises(Wreq::Error) { html.assert_content_type!("application/json") }
+    assert_match(%r{got 'text/html' \(status 502\)}, err.message)
+    refute_kind_of Wreq::DecodeError, err
+    assert_raises(Wreq::Error) { html.assert_content_type!("application/") }
+  ensure
+    server&.close
+  end
+
+  def test_auth_challenges
+    server = TestServer.new do |_req|
+      TestServer.response(401, "", "WWW-Authenticate" => [
+        %(Basic realm="Users, Inc.", charset="UTF-8", Bearer realm=api, error="invalid_token"),
+        %(Digest realm="x", qop="auth,auth-int", nonce="abc\\"def"),
+        "Negotiate YIIabc==",
+      ])
+    end
+    challenges = Wreq.get(server.url).auth_challenges
+    assert_equal [
+      { scheme: "Basic", params: { "realm" => "Users, Inc.", "charset" => "UTF-8" } },
+      { scheme: "Bearer", params: { "realm" => "api", "error" => "invalid_token" } },
+      { scheme: "Digest", params: { "realm" => "x", "qop" => "auth,auth-int", "nonce" => %(abc"def) } },
+      { scheme: "Negotiate", params: { "token68" => "YIIabc==" } },
+    ], challenges
+  ensure
+    server&.close
+  end
+
+  def test_auth_challenges_absent
+    assert_equal [], Wreq.get("https://httpbin.org/get").auth_challenges
+  end
+
+  def test_multi_member_gzip_body_is_fully_decoded
+    require "zlib"
+    body = Zlib.gzip("first member, ") + Zlib.gzip("second member")
+    server = TestServer.new do |_req|
+      TestServer.response(200, body, "Content-Encoding" => "gzip", "Content-Type" => "text/plain")
+    end
+
+    # wreq's built-in decoder
+    assert_equal "first member, second member", Wreq::Client.new.get(server.url).text
+    # decoding done by wreq-rb when Accept-Encoding is not sent
+    client = Wreq::Client.new(send_accept_encoding: false)
+    assert_equal "first member, second member", client.get(server.url).text
+  ensure
+    server&.close
+  end
+
+  def test_body_truncated_at_max_body_size
+    server = TestServer.new { |_req| TestServer.response(200, "a" * 10_000) }
+    client = Wreq::Client.new(max_body_size: 1000, truncate_body: true)
+
+    resp = client.get(server.url)
+    assert resp.body_truncated?
+    assert_equal "a" * 1000, resp.body
+
+    full = client.get(server.url, max_body_size: 20_000)
+    refute full.body_truncated?
+    assert_equal 10_000, full.body.bytesize
+  ensure
+    server&.close
+  end
+
+  def test_max_body_size_raises_without_truncate
+    server = TestServer.new { |_req| TestServer.response(200, "a" * 10_000) }
+    err = assert_raises(Wreq::Error) { Wreq.get(server.url, max_body_size: 1000) }
+    assert_equal :body, err.error_kind
+  ensure
+    server&.close
+  end
+
+  def test_body_not_truncated_by_default
+    server = TestServer.new { |_req| TestServer.response(200, "ok") }
+    refute Wreq.get(server.url).body_truncated?
+  ensure
+    server&.close
+  end
+
+  def test_each_chunk_streams_body
+    body = ("chunk" * 10_000).b
+    server = TestServer.new { |_req| TestServer.response(200, body) }
+    resp = Wreq.get(server.url, stream: true)
+    assert resp.streaming?
+    assert_equal "", resp.body
+
+    chunks = []
+    assert_same resp, resp.each_chunk { |c| chunks << c }
+    assert_equal body, chunks.join
+    assert_equal Encoding::BINARY, chunks.first.encoding
+    assert_equal [], resp.each_chunk.to_a
+  ensure
+    server&.close
+  end
+
+  def test_each_chunk_without_block_returns_enumerator
+    server = TestServer.new { |_req| TestServer.response(200, "a" * 200_000) }
+    enum = Wreq.get(server.url, stream: true).each_chunk
+    assert_kind_of Enumerator, enum
+    sizes = enum.with_index.map { |chunk, i| [i, chunk.bytesize] }
+    assert_equal 200_000, sizes.sum(&:last)
+    assert_equal (0...sizes.size).to_a, sizes.map(&:first)
+  ensure
+    server&.close
+  end
+
+  def test_each_chunk_on_buffered_response
+    server = TestServer.new { |_req| TestServer.response(200, "whole") }
+    resp = Wreq.get(server.url)
+    refute resp.streaming?
+    assert_equal ["whole"], resp.each_chunk.to_a
+  ensure
+    server&.close
+  end
+
+  def test_read_from_streaming_response
+    body = ("0123456789" * 20_000).b
+    server = TestServer.new { |_req| TestServer.response(200, body) }
+    resp = Wreq.get(server.url, stream: true)
+
+    assert_equal "", resp.read(0)
+    assert_equal "0123", resp.read(4)
+    assert_equal "456789", resp.read(6)
+    rest = resp.read
+    assert_equal body.bytesize - 10, rest.bytesize
+    assert_nil resp.read(1)
+    assert_equal "", resp.read
+  ensure
+    server&.close
+  end
+
+  def test_read_requires_streaming_response
+    server = TestServer.new { |_req| TestServer.response(200, "x") }
+    assert_raises(Wreq::Error) { Wreq.get(server.url).read(1) }
+  ensure
+    server&.close
+  end
+
+  def test_large_json_parsed_like_json_parse
+    doc = { "items" => Array.new(30_000) { |i| { "id" => i, "name" => "item #{i}", "price" => i * 0.5, "tags" => ["a", nil, true] } } }
+    payload = JSON.generate(doc)
+    assert_operator payload.bytesize, :>, 1024 * 1024
+    server = TestServer.new do |req|
+      body = req[:path] == "/bad" ? payload[0..-2] : payload
+      TestServer.response(200, body, "Content-Type" => "application/json")
+    end
+
+    assert_equal JSON.parse(payload), Wreq.get(server.url).json
+    assert_raises(JSON::ParserError) { Wreq.get(server.url("/bad")).json }
+  ensure
+    server&.close
+  end
+
+  def test_invalid_utf8_json_raises_decode_error_at_any_size
+    small = "{\"name\": \"\xFF\"}".b
+    large = "{\"pad\": \"#{"x" * (1024 * 1024)}\xFF\"}".b
+    server = TestServer.new do |req|
+      body = req[:path] == "/large" ? large : small
+      TestServer.response(200, body, "Content-Type" => "application/json")
+    end
+
+    assert_raises(Wreq::DecodeError) { Wreq.get(server.url("/small")).json }
+    assert_raises(Wreq::DecodeError) { Wreq.get(server.url("/large")).json }
+  ensure
+    server&.close
+  end
+
+  def test_large_json_keeps_key_order_and_big_numbers
+    doc = { "zebra" => 1, "apple" => 2, "mango" => 3, "big" => 2**70, "neg" => -(2**70), "exp" => 1.5e300,
+            "pad" => "x" * (1024 * 1024) }
+    payload = JSON.generate(doc)
+    server = TestServer.new { |_req| TestServer.response(200, payload, "Content-Type" => "application/json") }
+
+    parsed = Wreq.get(server.url).json
+    assert_equal JSON.parse(payload).keys, parsed.keys
+    assert_equal 2**70, parsed["big"]
+    assert_equal(-(2**70), parsed["neg"])
+    assert_kind_of Integer, parsed["big"]
+    assert_equal 1.5e300, parsed["exp"]
+  ensure
+    server&.close
+  end
+
+  def test_filename_from_content_disposition
+    dispositions = {
+      "/plain" => 'attachment; filename="report 2024.pdf"',
+      "/encoded" => "attachment; filename=\"fallback.txt\"; filename*=UTF-8''%E2%82%AC%20rates.txt",
+      "/latin1" => "attachment; filename*=iso-8859-1'en'caf%E9.txt",
+      "/traversal" => 'attachment; filename="../../etc/passwd"',
+      "/raw" => 'attachment; filename="résumé.pdf"',
+    }
+    server = TestServer.new do |req|
+      headers = dispositions.key?(req[:path]) ? { "Content-Disposition" => dispositions[req[:path]] } : {}
+      headers["X-Raw"] = "café" if req[:path] == "/raw"
+      TestServer.response(200, "x", headers)
+    end
+    client = Wreq::Client.new
+
+    assert_equal "report 2024.pdf", client.get(server.url("/plain")).filename
+    assert_equal "€ rates.txt", client.get(server.url("/encoded")).filename
+    assert_equal "café.txt", client.get(server.url("/latin1")).filename
+    assert_equal "passwd", client.get(server.url("/traversal")).filename
+    # Raw UTF-8 is kept for Content-Disposition only; other headers with
+    # bytes outside visible ASCII read as empty, as before.
+    raw = client.get(server.url("/raw"))
+    assert_equal "résumé.pdf", raw.filename
+    assert_equal [""], raw.headers["x-raw"]
+  ensure
+    server&.close
+  end
+
+  def test_filename_fallback_to_url
+    server = TestServer.new { |_req| TestServer.response(200, "x") }
+    resp = Wreq.get(server.url("/files/my%20data.csv?sig=abc"))
+    assert_nil resp.filename
+    assert_equal "my data.csv", resp.filename(fallback_to_url: true)
+    assert_nil Wreq.get(server.url("/")).filename(fallback_to_url: true)
+    assert_equal "my data.csv", resp.filename("fallback_to_url" => true)
+    assert_raises(TypeError) { resp.filename(true) }
+  ensure
+    server&.close
+  end
+
+  def test_stream_to_io
+    require "stringio"
+    payload = Random.new(3).bytes(200_000)
+    server = TestServer.new { |_req| TestServer.response(200, payload) }
+    io = StringIO.new(+"", "wb")
+    assert_equal payload.bytesize, Wreq::Client.new.stream_to_io(server.url, io)
+    assert_equal payload, io.string.b
+    refute io.closed?
+  ensure
+    server&.close
+  end
+
+  def test_stream_to_io_raises_on_error_status_without_writing
+    require "stringio"
+    server = TestServer.new { |_req| TestServer.response(503, "<html>maintenance</html>") }
+    io = StringIO.new(+"", "wb")
+    err = assert_raises(Wreq::HTTPStatusError) { Wreq::Client.new.stream_to_io(server.url, io) }
+    assert_equal 503, err.response.status
+    assert_includes err.message, "maintenance"
+    assert_equal "", io.string
+  ensure
+    server&.close
+  end
+
+  def test_stream_to_io_propagates_write_errors
+    server = TestServer.new { |_req| TestServer.response(200, "x" * 1000) }
+    sink = Object.new
+    def sink.write(_chunk)
+      raise IOError, "broken pipe"
+    end
+    assert_raises(IOError) { Wreq::Client.new.stream_to_io(server.url, sink) }
+    assert_raises(Wreq::Error) { Wreq::Client.new.stream_to_io(server.url, Object.new) }
+  ensure
+    server&.close
+  end
+
+  def test_text_is_tagged_utf8
+    body = "naïve café – 日本語"
+    server = TestServer.new { |_req| TestServer.response(200, body, "Content-Type" => "text/plain") }
+    previous = Encoding.default_external
+    Encoding.default_external = Encoding::ISO_8859_1
+    text = Wreq.get(server.url).text
+    assert_equal Encoding::UTF_8, text.encoding
+    assert_equal body, text
+    assert_match(/日本/, text)
+  ensure
+    Encoding.default_external = previous if previous
+    server&.close
+  end
+
+  def test_peek_reads_prefix_and_drops_connection
+    payload = "\x89PNG\r\n\x1A\n".b + Random.new(5).bytes(2_000_000)
+    server = TestServer.new { |_req| TestServer.response(200, payload, "Content-Type" => "image/png") }
+    client = Wreq::Client.new
+    resp = client.peek(server.url("/big.png"), bytes: 8)
+    assert_equal 200, resp.status
+    assert_equal "image/png", resp.content_type
+    assert_equal payload.byteslice(0, 8), resp.body
+    assert resp.body_truncated?
+    refute resp.streaming?
+
+    client.get(server.url("/next"))
+    assert_equal 2, server.connections
+  ensure
+    server&.close
+  end
+
+  def test_peek_short_body_is_not_truncated
+    server = TestServer.new { |_req| TestServer.response(200, "tiny") }
+    resp = Wreq::Client.new.peek(server.url)
+    assert_equal "tiny", resp.body
+    refute resp.body_truncated?
+
+    unbounded = Wreq::Client.new.peek(server.url, bytes: 2**64 - 1)
+    assert_equal "tiny", unbounded.body
+    refute unbounded.body_truncated?
+  ensure
+    server&.close
+  end
+
+  def test_decode_body_applies_one_coding
+    require "zlib"
+    inner = Zlib.gzip("double encoded")
+    server = TestServer.new do |req|
+      case req[:path]
+      when "/double" then TestServer.response(200, Zlib.gzip(inner), "Content-Encoding" => "gzip")
+      else TestServer.response(200, Zlib::Deflate.deflate("deflated"))
+      end
+    end
+
+    resp = Wreq.get(server.url("/double"))
+    assert_equal inner, resp.body
+    decoded = resp.decode_body(:gzip)
+    assert_equal "double encoded", decoded
+    assert_equal Encoding::BINARY, decoded.encoding
+    assert_equal inner, resp.body
+
+    raw = Wreq.get(server.url("/raw"))
+    assert_equal "deflated", raw.decode_body("deflate")
+    assert_raises(Wreq::DecodeError) { raw.decode_body(:zstd) }
+    assert_raises(Wreq::DecodeError) { raw.decode_body(:lzma) }
+  ensure
+    server&.close
+  end
+
+  def test_short_body_raises_incomplete_body
+    listener = TCPServer.new("127.0.0.1", 0)
+    port = listener.addr[1]
+    thread = Thread.new do
+      loop do
+        conn = listener.accept
+        while (line = conn.gets) && line != "\r\n"; end
+        conn.write("HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n" + "x" * 10)
+        conn.close
+      end
+    rescue IOError, SystemCallError
+      nil
+    end
+    url = "http://127.0.0.1:#{port}/file"
+
+    err = assert_raises(Wreq::Error) { Wreq.get(url) }
+    assert_equal :incomplete_body, err.error_kind
+    assert_match(/10 of 100 bytes/, err.message)
+
+    resp = Wreq.get(url, allow_incomplete: true)
+    assert resp.incomplete?
+    assert_equal "x" * 10, resp.body
+    refute Wreq::Client.new(allow_incomplete: true).get(url).body.empty?
+  ensure
+    listener&.close
+    thread&.join(1)
+  end
+
+  def test_links_from_link_headers
+    link = [
+      '<https://api.example.com/items?page=2&q=a,b>; rel="next", <https://api.example.com/items?page=9>; rel=last',
+      '</items?page=1>; title="first, really"; rel="first prev"',
+    ]
+    server = TestServer.new do |req|
+      req[:path] == "/plain" ? TestServer.response(200) : TestServer.response(200, "", "Link" => link)
+    end
+    links = Wreq.get(server.url("/items")).links
+    assert_equal "https://api.example.com/items?page=2&q=a,b", links["next"]
+    assert_equal "https://api.example.com/items?page=9", links["last"]
+    assert_equal server.url("/items?page=1"), links["first"]
+    assert_equal links["first"], links["prev"]
+    assert_equal({}, Wreq.get(server.url("/plain")).links)
+  ensure
+    server&.close
+  end
+
+  def test_transfer_encoding_gzip_is_decoded
+    require "zlib"
+    text = "transfer-coded " * 20
+    compressed = Zlib.gzip(text)
+    chunked = "#{compressed.bytesize.to_s(16)}\r\n".b + compressed + "\r\n0\r\n\r\n".b
+    server = TestServer.new do |req|
+      coding = req[:path] == "/both" ? "gzip, chunked" : "chunked"
+      TestServer.response(200, req[:path] == "/both" ? chunked : "3\r\nabc\r\n0\r\n\r\n", "Transfer-Encoding" => coding)
+    end
+
+    resp = Wreq.get(server.url("/both"))
+    assert_equal text, resp.text
+    assert_equal "gzip, chunked", resp.transfer_encoding
+    assert_nil resp.content_encoding
+
+    plain = Wreq.get(server.url("/plain"))
+    assert_equal "abc", plain.text
+    assert_equal "chunked", plain.transfer_encoding
+  ensure
+    server&.close
+  end
+
+  def test_raise_for_status_maps_codes_to_subclasses
+    server = TestServer.new do |req|
+      status = req[:path].delete_prefix("/").to_i
+      TestServer.response(status, status == 304 ? "" : "status body")
+    end
+    {
+      401 => Wreq::UnauthorizedError,
+      403 => Wreq::ForbiddenError,
+      404 => Wreq::NotFoundError,
+      429 => Wreq::TooManyRequestsError,
+      500 => Wreq::ServerError,
+      503 => Wreq::ServerError,
+      418 => Wreq::HTTPStatusError,
+    }.each do |status, klass|
+      resp = Wreq.get(server.url("/#{status}"))
+      err = assert_raises(klass) { resp.raise_for_status }
+      assert_equal klass, err.class
+      assert_same resp, err.response
+      assert_equal status, err.status
+      assert_equal :status, err.error_kind
+      assert_includes err.message, status.to_s
+      assert_includes err.message, "status body"
+    end
+    assert_operator Wreq::NotFoundError, :<, Wreq::HTTPStatusError
+    assert_operator Wreq::HTTPStatusError, :<, Wreq::Error
+
+    ok = Wreq.get(server.url("/200"))
+    assert_same ok, ok.raise_for_status
+    assert_equal 304, Wreq.get(server.url("/304")).raise_for_status.status
+  ensure
+    server&.close
+  end
+
+  def test_unsupported_content_encoding_left_raw
+    require "zlib"
+    raw = "\xFFDCB#{"\x01" * 32}compressed".b
+    server = TestServer.new do |req|
+      if req[:path] == "/gzip"
+        TestServer.response(200, Zlib.gzip("plain"), "Content-Encoding" => "gzip")
+      else
+        TestServer.response(200, raw, "Content-Encoding" => "dcb")
+      end
+    end
+
+    [Wreq::Client.new, Wreq::Client.new(send_accept_encoding: false)].each do |client|
+      resp = client.get(server.url("/dcb"))
+      assert_equal raw, resp.body_bytes.pack("C*")
+      assert_equal "dcb", resp.content_encoding
+
+      decoded = client.get(server.url("/gzip"))
+      assert_equal "plain", decoded.text
+      assert_nil decoded.content_encoding
+    end
+
+    kept = Wreq::Client.new(send_accept_encoding: false, gzip: false).get(server.url("/gzip"))
+    assert_equal "gzip", kept.content_encoding
+  ensure
+    server&.close
+  end
+
+  def test_cache_control_expires_and_date
+    server = TestServer.new do |req|
+      if req[:path] == "/cached"
+        TestServer.response(200, "ok",
+          "Cache-Control" => ["public, max-age=3600", 'no-cache="Set-Cookie", s-maxage=bogus, x-unknown, MUST-REVALIDATE'],
+          "Expires" => "Wed, 21 Oct 2026 07:28:00 GMT",
+          "Date" => "Tue, 20 Oct 2026 07:28:00 GMT")
+      else
+        TestServer.response(200, "ok", "Expires" => "0")
+      end
+    end
+    client = Wreq::Client.new
+
+    resp = client.get(server.url("/cached"))
+    cc = resp.cache_control
+    assert_equal 3600, cc[:max_age]
+    assert_nil cc[:s_maxage]
+    assert_nil cc[:stale_while_revalidate]
+    assert cc[:public]
+    assert cc[:no_cache]
+    assert cc[:must_revalidate]
+    refute cc[:no_store]
+    refute cc[:private]
+    assert_equal Time.utc(2026, 10, 21, 7, 28, 0), resp.expires
+    assert resp.expires.utc?
+    assert_equal Time.utc(2026, 10, 20, 7, 28, 0), resp.date
+
+    plain = client.get(server.url("/plain"))
+    assert_nil plain.cache_control
+    assert_nil plain.expires
+    assert_nil plain.date
+  ensure
+    server&.close
+  end
+
+  def test_json_bang_requires_json_content_type
+    server = TestServer.new do |req|
+      type = req[:path] == "/api" ? "application/vnd.api+json" : "text/html"
+      TestServer.response(200, '{"ok":true}', "Content-Type" => type)
+    end
+    client = Wreq::Client.new
+
+    mislabeled = client.get(server.url("/html"))
+    assert_equal({ "ok" => true }, mislabeled.json)
+    err = assert_raises(Wreq::DecodeError) { mislabeled.json! }
+    assert_match(%r{got 'text/html'}, err.message)
+
+    labeled = client.get(server.url("/api"))
+    assert_equal({ ok: true }, labeled.json!(symbolize_keys: true))
+    assert_equal({ ok: true }, labeled.json("symbolize_keys" => true))
+  ensure
+    server&.close
+  end
+
+  def test_header_int_and_header_time
+    server = TestServer.new do |_req|
+      TestServer.response(200, "ok",
+        "Retry-After" => " 120 ",
+        "X-Offset" => "-5",
+        "X-Bogus" => "12abc",
+        "Last-Modified" => "Sunday, 06-Nov-94 08:49:37 GMT",
+        "X-Asctime" => "Sun Nov  6 08:49:37 1994",
+        "X-Bad-Date" => "yesterday")
+    end
+    resp = Wreq::Client.new.get(server.url)
+
+    assert_equal 120, resp.header_int("retry-after")
+    assert_equal(-5, resp.header_int("X-Offset"))
+    assert_equal 2, resp.header_int("Content-Length")
+    assert_nil resp.header_int("X-Bogus")
+    assert_nil resp.header_int("X-Missing")
+
+    assert_equal Time.utc(1994, 11, 6, 8, 49, 37), resp.header_time("Last-Modified")
+    assert_equal Time.utc(1994, 11, 6, 8, 49, 37), resp.header_time("x-asctime")
+    assert_nil resp.header_time("X-Bad-Date")
+    assert_nil resp.header_time("X-Missing")
+  ensure
+    server&.close
+  end
+
+  def test_server_timing_parses_every_metric
+    server = TestServer.new do |req|
+      if req[:path] == "/timed"
+        TestServer.response(200, "ok",
+          "Server-Timing" => ['cache;desc="Cache Read, L2";dur=23.2, db;dur=53', "app;DUR=bogus;dur=1, total;dur=80.5;desc=all"])
+      else
+        TestServer.response(200, "ok")
+      end
+    end
+    client = Wreq::Client.new
+
+    assert_equal [
+      { name: "cache", dur: 23.2, desc: "Cache Read, L2" },
+      { name: "db", dur: 53.0, desc: nil },
+      { name: "app", dur: nil, desc: nil },
+      { name: "total", dur: 80.5, desc: "all" },
+    ], client.get(server.url("/timed")).server_timing
+    assert_equal [], client.get(server.url("/plain")).server_timing
+  ensure
+    server&.close
+  end
 end
@@ -1,4 +1,137 @@
 # frozen_string_literal: true
 
 require "minitest/autorun"
+require "socket"
+require "openssl"
 require "wreq-rb"
+
+# Minimal local HTTP/1.1 server for tests that need to inspect the raw request
+# or control the raw response. The handler receives each parsed request as a
+# Hash (:method, :path, :headers, :body) and returns the raw response string.
+# With `tls: true` it serves HTTPS with a self-signed certificate (`cert`) for
+# 127.0.0.1.
+class TestServer
+  attr_reader :requests, :port, :cert
+
+  def self.response(status, body = "", headers = {})
+    head = +"HTTP/1.1 #{status} Status\r\n"
+    head << "Content-Length: #{body.bytesize}\r\n" unless headers.keys.any? { |k| k.casecmp?("content-length") || k.casecmp?("transfer-encoding") }
+    headers.each { |k, v| Array(v).each { |val| head << "#{k}: #{val}\r\n" } }
+    head << "\r\n"
+    head.b + body.b
+  end
+
+  # The key and self-signed certificate shared by every TLS server.
+  def self.tls_identity
+    @tls_identity ||= begin
+      key = OpenSSL::PKey::RSA.new(2048)
+      cert = OpenSSL::X509::Certificate.new
+      cert.version = 2
+      cert.serial = 1
+      cert.subject = cert.issuer = OpenSSL::X509::Name.parse("/CN=127.0.0.1")
+      cert.public_key = key.public_key
+      cert.not_before = Time.now - 60
+      cert.not_after = Time.now + 86_400
+      extensions = OpenSSL::X509::ExtensionFactory.new(cert, cert)
+      cert.add_extension(extensions.create_extension("subjectAltName", "IP:127.0.0.1"))
+      cert.sign(key, OpenSSL::Digest.new("SHA256"))
+      [key, cert]
+    end
+  end
+
+  def initialize(tls: false, &handler)
+    @handler = handler || ->(_req) { TestServer.response(200) }
+    if tls
+      key, @cert = TestServer.tls_identity
+      @ssl_context = OpenSSL::SSL::SSLContext.new
+      @ssl_context.key = key
+      @ssl_context.cert = @cert
+    end
+    @server = TCPServer.new("127.0.0.1", 0)
+    @port = @server.addr[1]
+    @requests = []
+    @connections = 0
+    @lock = Mutex.new
+    @thread = Thread.new { accept_loop }
+  end
+
+  def url(path = "/")
+    "#{@ssl_context ? "https" : "http"}://127.0.0.1:#{@port}#{path}"
+  end
+
+  def connections
+    @lock.synchronize { @connections }
+  end
+
+  def close
+    @server.close rescue nil
+    @thread.kill
+    @thread.join(1)
+  end
+
+  private
+
+  def accept_loop
+    loop do
+      conn = @server.accept
+      @lock.synchronize { @connections += 1 }
+      Thread.new(conn) { |c| serve(c) }
+    end
+  rescue IOError, Errno::EBADF
+    nil
+  end
+
+  def serve(conn)
+    if @ssl_context
+      conn = OpenSSL::SSL::SSLSocket.new(conn, @ssl_context)
+      conn.sync_close = true
+      conn.accept
+    end
+    while (req = read_request(conn))
+      @lock.synchronize { @requests << req }
+      resp = @handler.call(req)
+      break if resp.nil?
+      conn.write(resp)
+      break if req[:headers]["connection"]&.casecmp?("close")
+    end
+  rescue IOError, SystemCallError, OpenSSL::SSL::SSLError
+    nil
+  ensure
+    conn.close rescue nil
+  end
+
+  def read_request(conn)
+    line = conn.gets
+    return nil if line.nil?
+    method, path, = line.split(" ", 3)
+    headers = {}
+    order = []
+    while (h = conn.gets&.chomp) && !h.empty?
+      name, value = h.split(":", 2)
+      order << name.downcase
+      headers[name.downcase] = value.to_s.strip
+    end
+    body = if headers["transfer-encoding"]&.include?("chunked")
+      read_chunked(conn)
+    elsif headers["content-length"]
+      conn.read(headers["content-length"].to_i) || +""
+    else
+      +""
+    end
+    { method: method, path: path, headers: headers, header_order: order, body: body.b }
+  end
+
+  def read_chunked(conn)
+    body = +""
+    loop do
+      size = conn.gets.to_s.strip.to_i(16)
+      if size.zero?
+        conn.gets
+        break
+      end
+      body << conn.read(size)
+      conn.read(2)
+    end
+    body
+  end
+end
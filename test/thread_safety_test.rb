@@ -197,4 +197,37 @@ class ThreadSafetyTest < Minitest::Test
     resp = client.get("https://httpbin.org/get")
     assert_equal 200, resp.status
   end
+
+  def test_abort_all_interrupts_in_flight_requests
+    server = TestServer.new do |req|
+      sleep 5 if req[:path] == "/slow"
+      TestServer.response(200, "ok")
+    end
+    client = Wreq::Client.new(emulation: false)
+
+    errors = Queue.new
+    threads = 3.times.map do
+      Thread.new do
+        client.get(server.url("/slow"))
+        errors << nil
+      rescue => e
+        errors << e
+      end
+    end
+    sleep 0.3
+
+    client.abort_all
+    threads.each { |t| t.join(2) }
+    assert threads.none?(&:alive?), "requests still running 2s after abort_all"
+
+    3.times do
+      error = errors.pop
+      assert_kind_of Wreq::InterruptedError, error
+      assert_equal :interrupted, error.error_kind
+    end
+
+    assert_equal "ok", client.get(server.url("/fast")).text
+  ensure
+    server&.close rescue nil
+  end
 end